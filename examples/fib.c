@@ -1,3 +1,5 @@
+int printf();
+
 int fibdp[100];
 
 int fib(int n) {
@@ -1,3 +1,5 @@
+int printf();
+
 int prime(int n) {
   if (n % 2 == 0) return 0;
   int k = 3;
@@ -0,0 +1,99 @@
+use crate::parse::{Node, NodeType};
+use crate::{Ctype, Type};
+
+use std::fmt::Write;
+
+// The C spelling of a type, as far as it can be told from the type.
+fn type_text(ty: &Type) -> String {
+    let sign = if ty.is_unsigned { "unsigned " } else { "" };
+    match ty.ty {
+        Ctype::Int if ty.size == 8 => format!("{}long", sign),
+        Ctype::Int => format!("{}int", sign),
+        Ctype::Char => format!("{}char", sign),
+        Ctype::Bool => "_Bool".into(),
+        Ctype::Void => "void".into(),
+        Ctype::Ptr(ref ptr_to) => format!("{}*", type_text(ptr_to)),
+        Ctype::Ary(ref ary_of, len) => format!("{}[{}]", type_text(ary_of), len),
+        Ctype::Struct(_) => "struct".into(),
+        Ctype::Func(ref returning, ..) => format!("{}()", type_text(returning)),
+    }
+}
+
+// The node's own line, and its children.
+fn head(node: &Node) -> (String, Vec<&Node>) {
+    use self::NodeType::*;
+    let name = |s: &str| s.to_string();
+    match node.op {
+        Num(val) => (format!("Num {}", val), vec![]),
+        Str(ref data, _) => (format!("Str {:?}", data), vec![]),
+        Ident(ref name) => (format!("Ident {}", name), vec![]),
+        Decl(ref name) => (format!("Decl {} {}", name, type_text(&node.ty)), vec![]),
+        Vardef(ref name, ref init, _) => (
+            format!("Vardef {} {}", name, type_text(&node.ty)),
+            init.iter().map(|init| &**init).collect(),
+        ),
+        Lvar(ref scope) => (format!("Lvar {:?}", scope), vec![]),
+        Gvar(ref name, ..) => (format!("Gvar {}", name), vec![]),
+        BinOp(ref op, ref lhs, ref rhs) => (format!("BinOp {:?}", op), vec![lhs, rhs]),
+        If(ref cond, ref then, ref els) => {
+            let mut children = vec![&**cond, &**then];
+            children.extend(els.iter().map(|els| &**els));
+            (name("If"), children)
+        }
+        Ternary(ref cond, ref then, ref els) => (name("Ternary"), vec![cond, then, els]),
+        For(ref init, ref cond, ref inc, ref body) => (name("For"), vec![init, cond, inc, body]),
+        Break => (name("Break"), vec![]),
+        Continue => (name("Continue"), vec![]),
+        Goto(ref label) => (format!("Goto {}", label), vec![]),
+        GotoPtr(ref expr) => (name("GotoPtr"), vec![expr]),
+        Label(ref label, ref stmt) => (format!("Label {}", label), vec![stmt]),
+        LabelAddr(ref label) => (format!("LabelAddr {}", label), vec![]),
+        DoWhile(ref body, ref cond) => (name("DoWhile"), vec![body, cond]),
+        Addr(ref expr) => (name("Addr"), vec![expr]),
+        Deref(ref expr) => (name("Deref"), vec![expr]),
+        Dot(ref expr, ref member, _) => (format!("Dot {}", member), vec![expr]),
+        Exclamation(ref expr) => (name("Exclamation"), vec![expr]),
+        Neg(ref expr) => (name("Neg"), vec![expr]),
+        Cast(ref expr) => (format!("Cast {}", type_text(&node.ty)), vec![expr]),
+        PostInc(ref expr) => (name("PostInc"), vec![expr]),
+        PostDec(ref expr) => (name("PostDec"), vec![expr]),
+        Return(ref expr) => (name("Return"), vec![expr]),
+        Sizeof(ref expr) => (name("Sizeof"), vec![expr]),
+        Alignof(ref expr) => (name("Alignof"), vec![expr]),
+        Call(ref callee, ref args) => (format!("Call {}", callee), args.iter().collect()),
+        CallPtr(ref callee, ref args) => {
+            let mut children = vec![&**callee];
+            children.extend(args);
+            (name("CallPtr"), children)
+        }
+        Func(ref name, ref args, ref body, ..) => {
+            let mut children: Vec<&Node> = args.iter().collect();
+            children.push(body);
+            (format!("Func {} {}", name, type_text(&node.ty)), children)
+        }
+        CompStmt(ref stmts) => (name("CompStmt"), stmts.iter().collect()),
+        VecStmt(ref stmts) => (name("VecStmt"), stmts.iter().collect()),
+        ExprStmt(ref expr) => (name("ExprStmt"), vec![expr]),
+        StmtExpr(ref body) => (name("StmtExpr"), vec![body]),
+        CompLit(ref init, ref var) => (name("CompLit"), vec![init, var]),
+        GlobalInit(ref parts) => (
+            name("GlobalInit"),
+            parts.iter().map(|(_, _, expr)| expr).collect(),
+        ),
+        Null => (name("Null"), vec![]),
+    }
+}
+
+// Prints a syntax tree one node per line, each child indented under
+// its parent. The nodes are walked with a stack rather than by
+// recursion, as a tree can be as deep as the parser allows.
+pub fn ast_text(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    let mut stack: Vec<(&Node, usize)> = nodes.iter().rev().map(|node| (node, 0)).collect();
+    while let Some((node, depth)) = stack.pop() {
+        let (line, children) = head(node);
+        writeln!(out, "{}{}", "  ".repeat(depth), line).unwrap();
+        stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+    }
+    out
+}
@@ -0,0 +1,171 @@
+use crate::gen_ir::{Function, IROp, IR};
+use crate::token::Token;
+
+use std::collections::HashMap;
+
+// Control-flow graph of a function's IR.
+//
+// A basic block is a maximal run of instructions that is entered only at
+// its first instruction and left only at its last one. A new block starts
+// at every label and after every jump, branch or return. KILLs and NOPs
+// that follow a jump emit no code, so they stay in the jump's block.
+//
+// Block 0 is the entry block. A block with no successors either returns
+// or falls off the end of the function. Blocks that cannot be reached
+// from the entry are reported by warn_unreachable() and dashed in DOT.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,     // Index of the first instruction in f.ir
+    pub end: usize,       // One past the last instruction
+    pub succ: Vec<usize>, // Successor blocks
+}
+
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+}
+
+fn is_terminator(ir: &IR) -> bool {
+    matches!(
+        ir.op,
+        IROp::Jmp
+            | IROp::JmpReg
+            | IROp::If(_)
+            | IROp::Unless(_)
+            | IROp::Return
+            | IROp::TailCall(..)
+    )
+}
+
+impl Cfg {
+    pub fn new(f: &Function) -> Self {
+        let mut leaders = vec![0];
+        let mut after_jump = false;
+        for (i, ir) in f.ir.iter().enumerate() {
+            let bookkeeping = ir.op == IROp::Kill || ir.op == IROp::Nop;
+            if i != 0 && (ir.op == IROp::Label || (after_jump && !bookkeeping)) {
+                leaders.push(i);
+                after_jump = false;
+            }
+            if is_terminator(ir) {
+                after_jump = true;
+            }
+        }
+        leaders.dedup();
+
+        let mut labels = HashMap::new();
+        for (n, &start) in leaders.iter().enumerate() {
+            if let Some(ir) = f.ir.get(start) {
+                if ir.op == IROp::Label {
+                    labels.insert(ir.lhs.unwrap(), n);
+                }
+            }
+        }
+        let target = |label: Option<usize>| labels[&label.unwrap()];
+
+        // An indirect jump may go to any label whose address is taken.
+        let mut taken: Vec<usize> =
+            f.ir.iter()
+                .filter(|ir| ir.op == IROp::CodeAddr)
+                .map(|ir| target(ir.rhs))
+                .collect();
+        taken.sort();
+        taken.dedup();
+
+        let mut blocks = vec![];
+        for (n, &start) in leaders.iter().enumerate() {
+            let end = leaders.get(n + 1).cloned().unwrap_or(f.ir.len());
+            let fallthrough = if n + 1 < leaders.len() {
+                vec![n + 1]
+            } else {
+                vec![]
+            };
+
+            // The last instruction that actually transfers control.
+            let last = f.ir[start..end]
+                .iter()
+                .rev()
+                .find(|ir| ir.op != IROp::Kill && ir.op != IROp::Nop);
+            let succ = match last.map(|ir| (&ir.op, ir)) {
+                Some((IROp::Jmp, ir)) => vec![target(ir.lhs)],
+                Some((IROp::If(_), ir)) | Some((IROp::Unless(_), ir)) => {
+                    let taken = target(ir.rhs);
+                    let mut succ = vec![taken];
+                    succ.extend(fallthrough.into_iter().filter(|&s| s != taken));
+                    succ
+                }
+                Some((IROp::JmpReg, _)) => taken.clone(),
+                Some((IROp::Return, _)) => vec![],
+                // A tail call jumps back to the start of the body.
+                Some((IROp::TailCall(..), _)) => vec![0],
+                _ => fallthrough,
+            };
+            blocks.push(BasicBlock { start, end, succ });
+        }
+        Cfg { blocks }
+    }
+
+    // Returns the blocks that cannot be reached from the entry block.
+    pub fn unreachable(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            if seen[n] {
+                continue;
+            }
+            seen[n] = true;
+            stack.extend(&self.blocks[n].succ);
+        }
+        (0..self.blocks.len()).filter(|&n| !seen[n]).collect()
+    }
+}
+
+// Warns of statements that cannot be reached, such as those after a
+// `return`. `stmts` has the first instruction of each statement in
+// source order, so only the first of a run of them is reported.
+pub fn warn_unreachable(f: &Function, stmts: &[(usize, Token)]) {
+    let cfg = Cfg::new(f);
+    let unreachable = cfg.unreachable();
+    let mut in_dead_code = false;
+    for (i, t) in stmts {
+        let block = cfg
+            .blocks
+            .iter()
+            .position(|bb| bb.start <= *i && *i < bb.end);
+        let is_dead = block.is_some_and(|n| unreachable.contains(&n));
+        if is_dead && !in_dead_code {
+            t.warn("code will never be executed");
+        }
+        in_dead_code = is_dead;
+    }
+}
+
+// Prints the CFG of each function in the DOT language of Graphviz.
+// Unreachable blocks are drawn with dashed borders.
+pub fn dump_cfg(fns: &[Function]) {
+    for f in fns {
+        let cfg = Cfg::new(f);
+        let unreachable = cfg.unreachable();
+        println!("digraph {} {{", f.name);
+        println!("  node [shape=box, fontname=monospace];");
+        for (n, bb) in cfg.blocks.iter().enumerate() {
+            let mut label = format!("B{}\\l", n);
+            for ir in &f.ir[bb.start..bb.end] {
+                label.push_str(&format!("{}\\l", ir));
+            }
+            let style = if unreachable.contains(&n) {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            println!("  B{} [label=\"{}\"{}];", n, label, style);
+        }
+        for (n, bb) in cfg.blocks.iter().enumerate() {
+            for s in &bb.succ {
+                println!("  B{} -> B{};", n, s);
+            }
+        }
+        println!("}}");
+    }
+}
@@ -0,0 +1,251 @@
+use crate::cfg::Cfg;
+use crate::gen_ir::{Function, IROp, IR};
+
+use std::collections::{HashMap, HashSet};
+
+// Local common subexpression elimination.
+//
+// Within each basic block, every register value is given a value number,
+// and an instruction whose operation and operand values match those of
+// a value that is still held in another register is replaced with a MOV
+// from that register. For `a[i] + a[i]`:
+//
+//   BPREL r1, 8          BPREL r1, 8
+//   ...                  ...
+//   LOADSX4 r1, 1        LOADSX4 r1, 1
+//   BPREL r2, 8    =>    BPREL r2, 8
+//   ...                  ...
+//   LOADSX4 r2, 2        MOV r2, r1
+//
+// The instructions that computed the address for the replaced load are
+// now dead, and are removed afterwards.
+//
+// Loads are numbered together with the memory state, and every store or
+// call starts a new memory state, since we don't know what a pointer may
+// point to. Values are never reused across blocks. Volatile loads are
+// neither numbered nor removed, as each one must reach memory.
+
+#[derive(PartialEq, Eq, Hash)]
+enum Key {
+    Imm(usize),
+    Bprel(usize),
+    LabelAddr(String),
+    CodeAddr(usize),
+    Unary(String, usize),         // (op, lhs value)
+    Binary(String, usize, usize), // (op, lhs value, rhs value)
+    RegImm(String, usize, usize), // (op, lhs value, immediate)
+    Load(String, usize, usize),   // (op, address value, memory state)
+}
+
+struct Numbering {
+    keys: HashMap<Key, usize>,
+    regs: HashMap<usize, usize>, // register -> value number
+    next: usize,
+    mem: usize, // memory state
+}
+
+impl Numbering {
+    fn new() -> Self {
+        Numbering {
+            keys: HashMap::new(),
+            regs: HashMap::new(),
+            next: 0,
+            mem: 0,
+        }
+    }
+
+    fn fresh(&mut self) -> usize {
+        self.next += 1;
+        self.next
+    }
+
+    // A register defined in another block holds some unknown value.
+    fn value(&mut self, r: usize) -> usize {
+        if let Some(&v) = self.regs.get(&r) {
+            return v;
+        }
+        let v = self.fresh();
+        self.regs.insert(r, v);
+        v
+    }
+
+    fn number(&mut self, key: Key) -> usize {
+        if let Some(&v) = self.keys.get(&key) {
+            return v;
+        }
+        let v = self.fresh();
+        self.keys.insert(key, v);
+        v
+    }
+
+    // The lowest-numbered register other than `r` that holds `v`.
+    fn holder(&self, v: usize, r: usize) -> Option<usize> {
+        self.regs
+            .iter()
+            .filter(|&(&reg, &val)| val == v && reg != r)
+            .map(|(&reg, _)| reg)
+            .min()
+    }
+}
+
+// The value an instruction computes into its lhs, if it is pure.
+fn key(n: &mut Numbering, ir: &IR) -> Option<Key> {
+    use self::IROp::*;
+    let lhs = ir.lhs.unwrap();
+    let name = format!("{:?}", ir.op);
+    let key = match ir.op {
+        Imm => Key::Imm(ir.rhs.unwrap()),
+        Bprel => Key::Bprel(ir.rhs.unwrap()),
+        LabelAddr(ref label) => Key::LabelAddr(label.clone()),
+        CodeAddr => Key::CodeAddr(ir.rhs.unwrap()),
+        Neg | Sext(_) | Zext(_) => Key::Unary(name, n.value(lhs)),
+        AddImm | SubImm | MulImm | ShlImm | DivPow2(_) | UDivPow2(_) => {
+            Key::RegImm(name, n.value(lhs), ir.rhs.unwrap())
+        }
+        Add | Sub | Mul | Div(_) | UDiv(_) | Mod(_) | UMod(_) | EQ(_) | NE(_) | LE(_) | LT(_)
+        | ULE(_) | ULT(_) | AND | OR | XOR | SHL | SHR(_) | SAR(_) => {
+            let rhs = n.value(ir.rhs.unwrap());
+            Key::Binary(name, n.value(lhs), rhs)
+        }
+        Load(_, false) | LoadSx(_, false) => Key::Load(name, n.value(ir.rhs.unwrap()), n.mem),
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn number_block(ir: &mut [IR]) {
+    use self::IROp::*;
+    let mut n = Numbering::new();
+
+    for ir in ir.iter_mut() {
+        match ir.op {
+            Mov => {
+                let v = n.value(ir.rhs.unwrap());
+                n.regs.insert(ir.lhs.unwrap(), v);
+            }
+            Kill => {
+                n.regs.remove(&ir.lhs.unwrap());
+            }
+            Store(..) | StoreArg(_) | Memcpy(_) | Memset(_) => n.mem += 1,
+            Call(..) | CallPtr(..) => {
+                n.mem += 1;
+                let v = n.fresh();
+                n.regs.insert(ir.lhs.unwrap(), v);
+            }
+            Load(_, true) | LoadSx(_, true) => {
+                let v = n.fresh();
+                n.regs.insert(ir.lhs.unwrap(), v);
+            }
+            _ => {
+                if let Some(key) = key(&mut n, ir) {
+                    let lhs = ir.lhs.unwrap();
+                    let v = n.number(key);
+                    if let Some(r) = n.holder(v, lhs) {
+                        *ir = IR {
+                            op: Mov,
+                            lhs: Some(lhs),
+                            rhs: Some(r),
+                        };
+                    }
+                    n.regs.insert(lhs, v);
+                }
+            }
+        }
+    }
+}
+
+// Removes pure instructions whose results are never read. A register
+// that is not killed in the block may be read by a later block. The
+// KILL of a register goes too once its definition has been removed.
+fn remove_dead(ir: &[IR], removed: &mut [bool], start: usize) {
+    use self::IROp::*;
+    let mut dead = HashSet::new();
+    let mut kills = HashMap::new(); // Register -> index of its KILL
+
+    for (i, ir) in ir.iter().enumerate().rev() {
+        let lhs = ir.lhs;
+        match ir.op {
+            Kill => {
+                dead.insert(lhs.unwrap());
+                kills.insert(lhs.unwrap(), i);
+            }
+            Store(..) | Memcpy(_) | Memset(_) => {
+                dead.remove(&lhs.unwrap());
+                dead.remove(&ir.rhs.unwrap());
+            }
+            Load(_, true) | LoadSx(_, true) => {
+                dead.insert(lhs.unwrap());
+                kills.remove(&lhs.unwrap());
+                dead.remove(&ir.rhs.unwrap());
+            }
+            Return | If(_) | Unless(_) | JmpReg | StackArg => {
+                dead.remove(&lhs.unwrap());
+            }
+            Call(_, nargs, args) | TailCall(_, nargs, args) | CallPtr(nargs, args) => {
+                if let Call(..) | CallPtr(..) = ir.op {
+                    dead.insert(lhs.unwrap());
+                    kills.remove(&lhs.unwrap());
+                }
+                if let CallPtr(..) = ir.op {
+                    dead.remove(&ir.rhs.unwrap());
+                }
+                for arg in &args[..nargs.min(6)] {
+                    dead.remove(arg);
+                }
+            }
+            Imm | Bprel | LabelAddr(_) | CodeAddr | Mov | Load(..) | LoadSx(..) => {
+                let lhs = lhs.unwrap();
+                if dead.contains(&lhs) {
+                    removed[start + i] = true;
+                    if let Some(kill) = kills.remove(&lhs) {
+                        removed[start + kill] = true;
+                    }
+                    continue;
+                }
+                dead.insert(lhs);
+                kills.remove(&lhs);
+                if let Mov | Load(..) | LoadSx(..) = ir.op {
+                    dead.remove(&ir.rhs.unwrap());
+                }
+            }
+            Neg | Sext(_) | Zext(_) | AddImm | SubImm | MulImm | ShlImm | DivPow2(_)
+            | UDivPow2(_) => {
+                if dead.contains(&lhs.unwrap()) {
+                    removed[start + i] = true;
+                }
+            }
+            Add | Sub | Mul | Div(_) | UDiv(_) | Mod(_) | UMod(_) | EQ(_) | NE(_) | LE(_)
+            | LT(_) | ULE(_) | ULT(_) | AND | OR | XOR | SHL | SHR(_) | SAR(_) => {
+                if dead.contains(&lhs.unwrap()) {
+                    removed[start + i] = true;
+                    continue;
+                }
+                dead.remove(&ir.rhs.unwrap());
+            }
+            Jmp | Label | StoreArg(_) | ArgArea | Nop => (),
+        }
+    }
+}
+
+fn optimize(f: &mut Function) {
+    let cfg = Cfg::new(f);
+    for bb in &cfg.blocks {
+        number_block(&mut f.ir[bb.start..bb.end]);
+    }
+
+    let mut removed = vec![false; f.ir.len()];
+    for bb in &cfg.blocks {
+        remove_dead(&f.ir[bb.start..bb.end], &mut removed, bb.start);
+    }
+    let mut i = 0;
+    f.ir.retain(|_| {
+        i += 1;
+        !removed[i - 1]
+    });
+}
+
+pub fn cse(fns: &mut Vec<Function>) {
+    for f in fns {
+        optimize(f);
+    }
+}
@@ -0,0 +1,229 @@
+// Entry points for using r9cc as a library.
+//
+// The passes report errors by panicking, so compile() runs them under
+// catch_unwind and turns the panic into a CompileError. A panic that is
+// not a CompileError is a bug in the compiler, and the error is marked
+// as internal. Each pass keeps
+// its state in a value local to the call, so compilations may run
+// concurrently on different threads. Nothing is printed: warnings and
+// IR dumps are returned with the result.
+
+use crate::cse::cse;
+use crate::gen_ir::{gen_ir, Function};
+use crate::gen_x86::gen_x86;
+use crate::irdump::ir_text;
+use crate::parse::{parse_with, Node, MAX_DEPTH};
+use crate::peephole::peephole;
+use crate::preprocess::Preprocessor;
+use crate::regalloc::alloc_regs;
+use crate::sema::sema;
+use crate::symbols::{self, Symbol};
+use crate::tailcall::tail_call;
+use crate::token::{take_warnings, tokenize_str, Token};
+use crate::Var;
+
+use std::cell::Cell;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+thread_local! {
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    // The offending line with a caret under the error, if the error has
+    // a position in the input.
+    pub context: String,
+    pub internal: bool, // A bug in the compiler rather than in the input
+}
+
+impl CompileError {
+    pub fn new(message: String, context: String) -> Self {
+        CompileError {
+            message,
+            context,
+            internal: false,
+        }
+    }
+
+    fn internal(message: String) -> Self {
+        CompileError {
+            internal: true,
+            ..CompileError::new(message, String::new())
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.context.is_empty() {
+            writeln!(f, "{}", self.context)?;
+        }
+        if self.internal {
+            write!(f, "internal compiler error: {}", self.message)
+        } else {
+            write!(f, "error: {}", self.message)
+        }
+    }
+}
+
+// The result of a compilation that succeeded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output<T> {
+    pub value: T,
+    // Each warning as "file:line:col: warning: message".
+    pub warnings: Vec<String>,
+    // The IR printed by -dump-ir1 and -dump-ir2. Only compile_with()
+    // fills it in.
+    pub dump: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub pic: bool,                    // -fPIC
+    pub inline: bool,                 // -finline
+    pub opt_level: u8,                // -O0 or -O1
+    pub disabled_passes: Vec<String>, // -fno-<pass>
+    pub dump_ir1: bool,               // Dump the IR before register allocation
+    pub dump_ir2: bool,               // Dump the IR after register allocation
+    pub max_depth: Option<usize>,     // -fbracket-depth=N
+}
+
+type Pass = fn(&mut Vec<Function>);
+
+// The IR optimization passes in the order they run at -O1. Each can be
+// turned off by name with -fno-<name>.
+pub const PASSES: [(&str, Pass); 3] = [
+    ("tail-call", tail_call),
+    ("peephole", peephole),
+    ("cse", cse),
+];
+
+// The panic hook is process-wide, so rather than swapping it around
+// each compilation, a hook is installed once that stays silent on a
+// thread that is inside catch() and defers to the previous hook otherwise.
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING.with(|c| c.get()) {
+                prev(info);
+            }
+        }));
+    });
+}
+
+// Runs f, turning a panic into an error and collecting the warnings
+// reported on the way.
+fn catch<T>(f: impl FnOnce() -> T) -> Result<Output<T>, Vec<CompileError>> {
+    install_hook();
+    take_warnings();
+    let was_catching = CATCHING.with(|c| c.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|c| c.set(was_catching));
+    let warnings = take_warnings();
+
+    let value = result.map_err(|payload| {
+        let err = if let Some(err) = payload.downcast_ref::<CompileError>() {
+            err.clone()
+        } else if let Some(msg) = payload.downcast_ref::<&str>() {
+            CompileError::internal(msg.to_string())
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            CompileError::internal(msg.clone())
+        } else {
+            CompileError::internal("unknown panic".into())
+        };
+        vec![err]
+    })?;
+    Ok(Output {
+        value,
+        warnings,
+        dump: String::new(),
+    })
+}
+
+fn to_ir(filename: &str, src: &str, opts: &Options) -> (Vec<Function>, Vec<Var>) {
+    let tokens = tokenize_str(filename.into(), src, &mut Preprocessor::new());
+    let nodes = parse_with(&tokens, opts.max_depth.unwrap_or(MAX_DEPTH));
+    let (nodes, globals) = sema(nodes, opts.inline);
+    let mut fns = gen_ir(nodes);
+    if opts.opt_level > 0 {
+        for (name, pass) in PASSES.iter() {
+            if !opts.disabled_passes.iter().any(|p| p == name) {
+                pass(&mut fns);
+            }
+        }
+    }
+    (fns, globals)
+}
+
+// Returns the tokens of `src` after preprocessing.
+pub fn tokens(filename: &str, src: &str) -> Result<Output<Vec<Token>>, Vec<CompileError>> {
+    catch(|| tokenize_str(filename.into(), src, &mut Preprocessor::new()))
+}
+
+// Returns the syntax tree of `src`, before types are checked.
+pub fn ast(
+    filename: &str,
+    src: &str,
+    opts: &Options,
+) -> Result<Output<Vec<Node>>, Vec<CompileError>> {
+    catch(|| {
+        let tokens = tokenize_str(filename.into(), src, &mut Preprocessor::new());
+        parse_with(&tokens, opts.max_depth.unwrap_or(MAX_DEPTH))
+    })
+}
+
+// Returns the IR of `src` before register allocation.
+pub fn ir(
+    filename: &str,
+    src: &str,
+    opts: &Options,
+) -> Result<Output<Vec<Function>>, Vec<CompileError>> {
+    catch(|| to_ir(filename, src, opts).0)
+}
+
+// Returns the symbols `src` defines and references.
+pub fn symbols(
+    filename: &str,
+    src: &str,
+    opts: &Options,
+) -> Result<Output<Vec<Symbol>>, Vec<CompileError>> {
+    catch(|| {
+        let (fns, globals) = to_ir(filename, src, opts);
+        symbols::symbols(&fns, &globals)
+    })
+}
+
+// Compiles `src` to x86-64 assembly. `filename` is used in error
+// messages, and #include paths are relative to the working directory.
+pub fn compile_with(
+    filename: &str,
+    src: &str,
+    opts: &Options,
+) -> Result<Output<String>, Vec<CompileError>> {
+    let mut dump = String::new();
+    let mut out = catch(|| {
+        let (mut fns, globals) = to_ir(filename, src, opts);
+        if opts.dump_ir1 {
+            dump.push_str(&ir_text(&fns));
+        }
+        alloc_regs(&mut fns);
+        if opts.dump_ir2 {
+            dump.push_str(&ir_text(&fns));
+        }
+        gen_x86(globals, fns, opts.pic)
+    })?;
+    out.dump = dump;
+    Ok(out)
+}
+
+// Compiles `src` with the default options, dropping any warnings.
+pub fn compile(src: &str) -> Result<String, Vec<CompileError>> {
+    compile_with("<input>", src, &Options::default()).map(|out| out.value)
+}
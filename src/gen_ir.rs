@@ -10,24 +10,14 @@
 // > Such infinite number of registers are mapped to a finite registers
 // > in a later pass.
 
+use crate::cfg::warn_unreachable;
 use crate::parse::{Node, NodeType};
+use crate::token::{error, Token};
+use crate::util::roundup;
 use crate::{Ctype, Scope, TokenType, Type};
 
-use std::sync::Mutex;
-
-lazy_static! {
-    static ref NUM_REGS: Mutex<usize> = Mutex::new(0);
-    static ref NLABEL: Mutex<usize> = Mutex::new(1);
-    static ref RETURN_LABEL: Mutex<usize> = Mutex::new(0);
-    static ref RETURN_REG: Mutex<usize> = Mutex::new(0);
-    static ref BREAK_LABEL: Mutex<usize> = Mutex::new(0);
-    static ref CODE: Mutex<Vec<IR>> = Mutex::new(vec![]);
-}
-
-fn add(op: IROp, lhs: Option<usize>, rhs: Option<usize>) {
-    let ir = IR::new(op, lhs, rhs);
-    CODE.lock().unwrap().push(ir.clone());
-}
+use std::collections::{HashMap, HashSet};
+use std::mem;
 
 #[derive(Clone, Debug)]
 pub enum IRType {
@@ -50,14 +40,16 @@ pub struct Function {
     pub name: String,
     pub ir: Vec<IR>,
     pub stacksize: usize,
+    pub is_static: bool,
 }
 
 impl Function {
-    fn new(name: String, ir: Vec<IR>, stacksize: usize) -> Self {
+    fn new(name: String, ir: Vec<IR>, stacksize: usize, is_static: bool) -> Self {
         Function {
             name,
             ir,
             stacksize,
+            is_static,
         }
     }
 }
@@ -70,35 +62,62 @@ pub enum IROp {
     SubImm,
     Mul,
     MulImm,
-    Div,
+    Div(u8),
+    UDiv(u8),
     Imm,
     Bprel,
     Mov,
     Return,
-    Call(String, usize, [usize; 6]),
+    Call(String, usize, [usize; 6]), // (name, nargs, registers of the first six)
+    CallPtr(usize, [usize; 6]),      // Calls the address in rhs. (nargs, registers)
+    TailCall(String, usize, [usize; 6]),
+    ArgArea,  // Reserves stack_args_size(lhs) bytes below rsp
+    StackArg, // Stores lhs to [rsp+rhs]
     Label,
     LabelAddr(String),
-    EQ,
-    NE,
-    LE,
-    LT,
+    EQ(u8),
+    NE(u8),
+    LE(u8),
+    LT(u8),
+    ULE(u8),
+    ULT(u8),
     AND,
     OR,
     XOR,
     SHL,
-    SHR,
-    Mod,
+    SHR(u8),
+    SAR(u8),
+    ShlImm,
+    DivPow2(u8),
+    UDivPow2(u8),
+    Mod(u8),
+    UMod(u8),
     Neg,
+    Sext(u8), // Sign-extends the low bytes to 64 bits
+    Zext(u8), // Zero-extends the low bytes to 64 bits
     Jmp,
-    If,
-    Unless,
-    Load(u8),
-    Store(u8),
+    JmpReg,
+    CodeAddr,
+    If(u8),
+    Unless(u8),
+    Load(u8, bool),   // (size, is_volatile)
+    LoadSx(u8, bool), // (size, is_volatile)
+    Store(u8, bool),  // (size, is_volatile)
     StoreArg(u8),
+    Memcpy(usize),
+    Memset(usize),
     Kill,
     Nop,
 }
 
+// Arguments after the sixth are passed on the stack, the seventh at
+// [rsp] on entry to the call. Below them the caller keeps two slots for
+// r10 and r11, and the whole area is a multiple of 16 bytes so that
+// calls made while evaluating the arguments stay aligned.
+pub fn stack_args_size(nargs: usize) -> usize {
+    roundup(8 * (nargs - 6), 16) + 16
+}
+
 impl From<NodeType> for IROp {
     fn from(node_type: NodeType) -> Self {
         match node_type {
@@ -114,8 +133,6 @@ impl From<TokenType> for IROp {
             TokenType::Plus => IROp::Add,
             TokenType::Minus => IROp::Sub,
             TokenType::Mul => IROp::Mul,
-            TokenType::Div => IROp::Div,
-            TokenType::LeftAngleBracket | TokenType::RightAngleBracket => IROp::LT,
             e => panic!("cannot convert: {:?}", e),
         }
     }
@@ -134,123 +151,110 @@ impl IR {
     }
 }
 
-fn kill(r: Option<usize>) {
-    add(IROp::Kill, r, None);
+// Width of the compare-against-zero for a condition of a given type.
+// Pointers are tested as a whole; anything narrower is computed with
+// int arithmetic, so only the low 32 bits are significant.
+fn cond_size(ty: &Type) -> u8 {
+    if ty.size == 8 {
+        8
+    } else {
+        4
+    }
 }
 
-fn label(x: Option<usize>) {
-    add(IROp::Label, x, None);
+// Values are moved through registers, so only scalars can be loaded
+// and stored. Struct copies are not supported.
+fn check_scalar(ty: &Type) {
+    match ty.ty {
+        Ctype::Struct(_) => error("struct used as a value"),
+        Ctype::Void => error("void value not ignored as it ought to be"),
+        _ => (),
+    }
 }
 
-fn jmp(x: Option<usize>) {
-    add(IROp::Jmp, x, None);
+// __builtin_memcpy and __builtin_memset with a constant size are done
+// inline. Otherwise they are calls to the library functions.
+fn mem_op(name: &str, args: &[Node]) -> Option<IROp> {
+    let len = match args.get(2).map(|arg| &arg.op) {
+        Some(NodeType::Num(len)) if *len >= 0 => *len as usize,
+        _ => return None,
+    };
+    match name {
+        "__builtin_memcpy" => Some(IROp::Memcpy(len)),
+        "__builtin_memset" => Some(IROp::Memset(len)),
+        _ => None,
+    }
 }
 
-fn load(ty: &Type, dst: Option<usize>, src: Option<usize>) {
-    add(IROp::Load(ty.size as u8), dst, src);
+// Division and comparison are done at the width of the wider operand.
+fn op_size(lhs: &Type, rhs: &Type) -> u8 {
+    if lhs.size == 8 || rhs.size == 8 {
+        8
+    } else {
+        4
+    }
 }
 
-fn store(ty: &Type, dst: Option<usize>, src: Option<usize>) {
-    add(IROp::Store(ty.size as u8), dst, src);
+// Division is unsigned if the common type of the operands is.
+fn div_op(op: &TokenType, ty: &Type) -> IROp {
+    let size = op_size(ty, ty);
+    let is_mod = *op == TokenType::Mod || *op == TokenType::ModEQ;
+    match (is_mod, ty.is_unsigned) {
+        (false, false) => IROp::Div(size),
+        (false, true) => IROp::UDiv(size),
+        (true, false) => IROp::Mod(size),
+        (true, true) => IROp::UMod(size),
+    }
 }
 
-fn store_arg(ty: &Type, bpoff: Option<usize>, argreg: Option<usize>) {
-    add(IROp::StoreArg(ty.size as u8), bpoff, argreg);
+// A right shift is logical if the left operand is unsigned and
+// arithmetic otherwise. The right operand does not matter.
+fn shr_op(lhs: &Type) -> IROp {
+    let size = op_size(lhs, lhs);
+    if lhs.is_unsigned && lhs.size >= 4 {
+        IROp::SHR(size)
+    } else {
+        IROp::SAR(size)
+    }
 }
 
-// Quoted from 9cc
-// > In C, all expressions that can be written on the left-hand side of
-// > the '=' operator must have an address in memory. In other words, if
-// > you can apply the '&' operator to take an address of some
-// > expression E, you can assign E to a new value.
-//
-// > Other expressions, such as `1+2`, cannot be written on the lhs of
-// > '=', since they are just temporary values that don't have an address.
-//
-// > The stuff that can be written on the lhs of '=' is called lvalue.
-// > Other values are called rvalue. An lvalue is essentially an address.
-//
-// > When lvalues appear on the rvalue context, they are converted to
-// > rvalues by loading their values from their addresses. You can think
-// > '&' as an operator that suppresses such automatic lvalue-to-rvalue
-// > conversion.
-//
-// > This function evaluates a given node as an lvalue.
-
-fn gen_lval(node: Box<Node>) -> Option<usize> {
-    match node.op {
-        NodeType::Deref(expr) => gen_expr(expr),
-        NodeType::Dot(ref expr, _, ref offset) => {
-            let r = gen_lval(expr.clone());
-            add(IROp::AddImm, r, Some(*offset));
-            r
-        }
-        NodeType::Lvar(Scope::Local(offset)) => {
-            let r = Some(*NUM_REGS.lock().unwrap());
-            *NUM_REGS.lock().unwrap() += 1;
-            add(IROp::Bprel, r, Some(offset));
-            r
-        }
-        NodeType::Gvar(name, _, _) => {
-            let r = Some(*NUM_REGS.lock().unwrap());
-            *NUM_REGS.lock().unwrap() += 1;
-            add(IROp::LabelAddr(name), r, None);
-            r
-        }
-        _ => unreachable!(),
+// A relational comparison is unsigned if the common type of the
+// operands is.
+fn cmp_op(op: &TokenType, ty: &Type) -> IROp {
+    let size = op_size(ty, ty);
+    match (op, ty.is_unsigned) {
+        (TokenType::LE, false) => IROp::LE(size),
+        (TokenType::LE, true) => IROp::ULE(size),
+        (_, false) => IROp::LT(size),
+        (_, true) => IROp::ULT(size),
     }
 }
 
-fn gen_binop(ty: IROp, lhs: Box<Node>, rhs: Box<Node>) -> Option<usize> {
-    let r1 = gen_expr(lhs);
-    let r2 = gen_expr(rhs);
-    add(ty, r1, r2);
-    kill(r2);
-    r1
+// A value loaded from memory is already extended to 64 bits, as is a
+// constant, but the result of an operation on narrower operands may
+// not be: `u + 1` wraps at 32 bits only in the low half of the register.
+fn is_extended(node: &Node) -> bool {
+    match node.op {
+        NodeType::Lvar(_) | NodeType::Gvar(..) | NodeType::Deref(_) | NodeType::Num(_) => true,
+        NodeType::Dot(..) => node.ty.bitfield.is_none(),
+        _ => false,
+    }
 }
 
 fn get_inc_scale(ty: &Type) -> usize {
     match ty.ty {
-        Ctype::Ptr(ref ptr_to) => ptr_to.size,
+        Ctype::Ptr(ref ptr_to) => ptr_to.size.max(1),
         _ => 1,
     }
 }
 
-fn gen_pre_inc(ty: &Type, expr: Box<Node>, num: i32) -> i32 {
-    let addr = gen_lval(expr);
-    let val = *NUM_REGS.lock().unwrap();
-    *NUM_REGS.lock().unwrap() += 1;
-    load(ty, Some(val), addr);
-    add(
-        IROp::AddImm,
-        Some(val),
-        Some(num as usize * get_inc_scale(ty)),
-    );
-    store(ty, addr, Some(val));
-    kill(addr);
-    val as i32
-}
-
-fn gen_post_inc(ty: &Type, expr: Box<Node>, num: i32) -> i32 {
-    let val = gen_pre_inc(ty, expr, num);
-    add(
-        IROp::SubImm,
-        Some(val as usize),
-        Some(num as usize * get_inc_scale(ty)),
-    );
-    val as i32
-}
-
 fn to_assign_op(op: &TokenType) -> IROp {
     use self::TokenType::*;
     match op {
         MulEQ => IROp::Mul,
-        DivEQ => IROp::Div,
-        ModEQ => IROp::Mod,
         AddEQ => IROp::Add,
         SubEQ => IROp::Sub,
         ShlEQ => IROp::SHL,
-        ShrEQ => IROp::SHR,
         BitandEQ => IROp::AND,
         XorEQ => IROp::XOR,
         BitorEQ => IROp::OR,
@@ -258,309 +262,659 @@ fn to_assign_op(op: &TokenType) -> IROp {
     }
 }
 
-fn gen_assign_op(op: &TokenType, ty: &Type, lhs: Box<Node>, rhs: Box<Node>) -> Option<usize> {
-    let src = gen_expr(rhs);
-    let dst = gen_lval(lhs);
-    let val = Some(*NUM_REGS.lock().unwrap());
-    *NUM_REGS.lock().unwrap() += 1;
-
-    load(ty, val, dst);
-    add(to_assign_op(op), val, src);
-    kill(src);
-    store(ty, dst, val);
-    kill(dst);
-    val
+struct Gen {
+    num_regs: usize,
+    nlabel: usize,
+    return_label: usize,
+    return_reg: usize,
+    code: Vec<IR>,
+
+    // The (break, continue) labels of the enclosing loops, innermost last
+    loops: Vec<(usize, usize)>,
+
+    // Labels of the current function, by name, and those that are defined
+    user_labels: HashMap<String, usize>,
+    defined_labels: HashSet<String>,
+
+    // The first instruction of each statement of the current function
+    stmts: Vec<(usize, Token)>,
 }
 
-fn gen_expr(node: Box<Node>) -> Option<usize> {
-    let node = *node;
-    match node.op {
-        NodeType::Num(val) => {
-            let r = Some(*NUM_REGS.lock().unwrap());
-            *NUM_REGS.lock().unwrap() += 1;
-            add(IROp::Imm, r, Some(val as usize));
-            r
+impl Gen {
+    fn new() -> Self {
+        Gen {
+            num_regs: 0,
+            nlabel: 1,
+            return_label: 0,
+            return_reg: 0,
+            code: vec![],
+            loops: vec![],
+            user_labels: HashMap::new(),
+            defined_labels: HashSet::new(),
+            stmts: vec![],
+        }
+    }
+
+    fn new_reg(&mut self) -> usize {
+        self.num_regs += 1;
+        self.num_regs - 1
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.nlabel += 1;
+        self.nlabel - 1
+    }
+
+    fn add(&mut self, op: IROp, lhs: Option<usize>, rhs: Option<usize>) {
+        self.code.push(IR::new(op, lhs, rhs));
+    }
+
+    fn kill(&mut self, r: Option<usize>) {
+        self.add(IROp::Kill, r, None);
+    }
+
+    fn label(&mut self, x: Option<usize>) {
+        self.add(IROp::Label, x, None);
+    }
+
+    fn jmp(&mut self, x: Option<usize>) {
+        self.add(IROp::Jmp, x, None);
+    }
+
+    // Returns the break and continue labels of a new innermost loop.
+    fn enter_loop(&mut self) -> (usize, usize) {
+        let labels = (self.new_label(), self.new_label());
+        self.loops.push(labels);
+        labels
+    }
+
+    // Places the label of `continue` if the loop has one, so that the
+    // body of a loop without one stays in the same block.
+    fn continue_target(&mut self, x: usize) {
+        let x = Some(x);
+        if self.code.iter().any(|ir| ir.op == IROp::Jmp && ir.lhs == x) {
+            self.label(x);
+        }
+    }
+
+    // The IR label of a label in the source, which may not be defined yet.
+    fn user_label(&mut self, name: &str) -> usize {
+        if let Some(&x) = self.user_labels.get(name) {
+            return x;
         }
-        NodeType::Lvar(_) | NodeType::Dot(_, _, _) | NodeType::Gvar(_, _, _) => {
-            let r = gen_lval(Box::new(node.clone()));
-            load(&node.ty, r, r);
-            r
+        let x = self.new_label();
+        self.user_labels.insert(name.into(), x);
+        x
+    }
+
+    fn load(&mut self, ty: &Type, dst: Option<usize>, src: Option<usize>) {
+        check_scalar(ty);
+        // Signed char and int values are sign-extended to the register
+        // width; unsigned ones are zero-extended.
+        match ty.ty {
+            Ctype::Char | Ctype::Int if !ty.is_unsigned => {
+                self.add(IROp::LoadSx(ty.size as u8, ty.is_volatile), dst, src)
+            }
+            _ => self.add(IROp::Load(ty.size as u8, ty.is_volatile), dst, src),
         }
-        NodeType::Call(name, args) => {
-            let mut args_ir: [usize; 6] = [0; 6];
-            for i in 0..args.len() {
-                args_ir[i] = gen_expr(Box::new(args[i].clone())).unwrap();
+
+        // Extract a bit-field from its storage unit. A signed one is
+        // shifted to the top of the register and back to extend its sign.
+        if let Some((bit_offset, width)) = ty.bitfield {
+            let r = Some(self.new_reg());
+            if ty.is_unsigned {
+                self.add(IROp::Imm, r, Some(bit_offset));
+                self.add(IROp::SHR(8), dst, r);
+                self.add(IROp::Imm, r, Some((1 << width) - 1));
+                self.add(IROp::AND, dst, r);
+            } else {
+                self.add(IROp::Imm, r, Some(64 - bit_offset - width));
+                self.add(IROp::SHL, dst, r);
+                self.add(IROp::Imm, r, Some(64 - width));
+                self.add(IROp::SAR(8), dst, r);
             }
+            self.kill(r);
+        }
+    }
 
-            let r = Some(*NUM_REGS.lock().unwrap());
-            *NUM_REGS.lock().unwrap() += 1;
+    fn store(&mut self, ty: &Type, dst: Option<usize>, src: Option<usize>) {
+        check_scalar(ty);
+        // Any nonzero value stored to a _Bool becomes 1.
+        if let Ctype::Bool = ty.ty {
+            let val = Some(self.new_reg());
+            let zero = Some(self.new_reg());
+            self.add(IROp::Mov, val, src);
+            self.add(IROp::Imm, zero, Some(0));
+            self.add(IROp::NE(8), val, zero);
+            self.add(IROp::Store(ty.size as u8, ty.is_volatile), dst, val);
+            self.kill(zero);
+            self.kill(val);
+            return;
+        }
+
+        let (bit_offset, width) = match ty.bitfield {
+            Some(bitfield) => bitfield,
+            None => {
+                self.add(IROp::Store(ty.size as u8, ty.is_volatile), dst, src);
+                return;
+            }
+        };
+
+        // Read-modify-write the storage unit of a bit-field.
+        let mask = (1 << width) - 1;
+        let unit = Some(self.new_reg());
+        let r = Some(self.new_reg());
+        let val = Some(self.new_reg());
+
+        self.add(IROp::Load(ty.size as u8, ty.is_volatile), unit, dst);
+        self.add(IROp::Imm, r, Some(!(mask << bit_offset)));
+        self.add(IROp::AND, unit, r);
+        self.add(IROp::Mov, val, src);
+        self.add(IROp::Imm, r, Some(mask));
+        self.add(IROp::AND, val, r);
+        self.add(IROp::Imm, r, Some(bit_offset));
+        self.add(IROp::SHL, val, r);
+        self.add(IROp::OR, unit, val);
+        self.add(IROp::Store(ty.size as u8, ty.is_volatile), dst, unit);
+        self.kill(val);
+        self.kill(r);
+        self.kill(unit);
+    }
 
-            add(IROp::Call(name, args.len(), args_ir), r, None);
+    fn store_arg(&mut self, ty: &Type, bpoff: Option<usize>, argreg: Option<usize>) {
+        self.add(IROp::StoreArg(ty.size as u8), bpoff, argreg);
+    }
 
-            for arg in args_ir.iter().take(args.len()) {
-                kill(Some(*arg));
+    // Quoted from 9cc
+    // > In C, all expressions that can be written on the left-hand side of
+    // > the '=' operator must have an address in memory. In other words, if
+    // > you can apply the '&' operator to take an address of some
+    // > expression E, you can assign E to a new value.
+    //
+    // > Other expressions, such as `1+2`, cannot be written on the lhs of
+    // > '=', since they are just temporary values that don't have an address.
+    //
+    // > The stuff that can be written on the lhs of '=' is called lvalue.
+    // > Other values are called rvalue. An lvalue is essentially an address.
+    //
+    // > When lvalues appear on the rvalue context, they are converted to
+    // > rvalues by loading their values from their addresses. You can think
+    // > '&' as an operator that suppresses such automatic lvalue-to-rvalue
+    // > conversion.
+    //
+    // > This function evaluates a given node as an lvalue.
+
+    fn gen_lval(&mut self, node: Node) -> Option<usize> {
+        match node.op {
+            NodeType::Deref(expr) => self.gen_expr(*expr),
+            NodeType::Dot(expr, _, offset) => {
+                let r = self.gen_lval(*expr);
+                self.add(IROp::AddImm, r, Some(offset));
+                r
+            }
+            NodeType::CompLit(init, var) => {
+                self.gen_stmt(*init);
+                self.gen_lval(*var)
             }
-            r
+            NodeType::Lvar(Scope::Local(offset)) => {
+                let r = Some(self.new_reg());
+                self.add(IROp::Bprel, r, Some(offset));
+                r
+            }
+            NodeType::Gvar(name, _, _) => {
+                let r = Some(self.new_reg());
+                self.add(IROp::LabelAddr(name), r, None);
+                r
+            }
+            _ => unreachable!(),
         }
-        NodeType::Addr(expr) => gen_lval(expr),
-        NodeType::Deref(expr) => {
-            let r = gen_expr(expr);
-            load(&node.ty, r, r);
-            r
+    }
+
+    fn gen_binop(&mut self, ty: IROp, lhs: Node, rhs: Node) -> Option<usize> {
+        let r1 = self.gen_expr(lhs);
+        let r2 = self.gen_expr(rhs);
+        self.add(ty, r1, r2);
+        self.kill(r2);
+        r1
+    }
+
+    fn gen_pre_inc(&mut self, ty: &Type, expr: Node, num: i32) -> i32 {
+        let addr = self.gen_lval(expr);
+        let val = self.new_reg();
+        self.load(ty, Some(val), addr);
+        self.add(
+            IROp::AddImm,
+            Some(val),
+            Some((num * get_inc_scale(ty) as i32) as usize),
+        );
+        self.store(ty, addr, Some(val));
+        self.kill(addr);
+        val as i32
+    }
+
+    fn gen_post_inc(&mut self, ty: &Type, expr: Node, num: i32) -> i32 {
+        let val = self.gen_pre_inc(ty, expr, num);
+        self.add(
+            IROp::SubImm,
+            Some(val as usize),
+            Some((num * get_inc_scale(ty) as i32) as usize),
+        );
+        val
+    }
+
+    fn gen_assign_op(&mut self, op: &TokenType, ty: &Type, lhs: Node, rhs: Node) -> Option<usize> {
+        let irop = match op {
+            TokenType::DivEQ | TokenType::ModEQ => div_op(op, &rhs.ty),
+            TokenType::ShrEQ => shr_op(ty),
+            _ => to_assign_op(op),
+        };
+        let src = self.gen_expr(rhs);
+        let dst = self.gen_lval(lhs);
+        let val = Some(self.new_reg());
+
+        self.load(ty, val, dst);
+        self.add(irop, val, src);
+        self.kill(src);
+        self.store(ty, dst, val);
+        self.kill(dst);
+        val
+    }
+
+    // Computes the arguments of a call and returns the registers of the
+    // first six. Stack arguments are stored right to left as soon as
+    // they are computed, so they hold no registers.
+    fn gen_args(&mut self, args: &[Node]) -> [usize; 6] {
+        if args.len() > 6 {
+            self.add(IROp::ArgArea, Some(args.len()), None);
+            for i in (6..args.len()).rev() {
+                let r = self.gen_expr(args[i].clone());
+                self.add(IROp::StackArg, r, Some(8 * (i - 6)));
+                self.kill(r);
+            }
         }
-        NodeType::StmtExpr(body) => {
-            let orig_label = *RETURN_LABEL.lock().unwrap();
-            let orig_reg = *RETURN_REG.lock().unwrap();
-            *RETURN_LABEL.lock().unwrap() = *NLABEL.lock().unwrap();
-            *NLABEL.lock().unwrap() += 1;
-            let r = *NUM_REGS.lock().unwrap();
-            *NUM_REGS.lock().unwrap() += 1;
-            *RETURN_REG.lock().unwrap() = r;
-
-            gen_stmt(*body);
-            label(Some(*RETURN_LABEL.lock().unwrap()));
-
-            *RETURN_LABEL.lock().unwrap() = orig_label;
-            *RETURN_REG.lock().unwrap() = orig_reg;
-            Some(r)
+
+        let mut args_ir: [usize; 6] = [0; 6];
+        for i in 0..args.len().min(6) {
+            args_ir[i] = self.gen_expr(args[i].clone()).unwrap();
         }
-        NodeType::BinOp(op, lhs, rhs) => {
-            use self::TokenType::*;
-            match op {
-                Equal => {
-                    let rhs = gen_expr(rhs);
-                    let lhs = gen_lval(lhs);
-                    store(&node.ty, lhs, rhs);
-                    kill(lhs);
-                    rhs
+        args_ir
+    }
+
+    fn gen_expr(&mut self, node: Node) -> Option<usize> {
+        match node.op {
+            NodeType::Num(val) => {
+                let r = Some(self.new_reg());
+                self.add(IROp::Imm, r, Some(val as usize));
+                r
+            }
+            NodeType::Lvar(_) | NodeType::Dot(_, _, _) | NodeType::Gvar(_, _, _) => {
+                let r = self.gen_lval(node.clone());
+                self.load(&node.ty, r, r);
+                r
+            }
+            NodeType::Call(name, args) => {
+                if let Some(op) = mem_op(&name, &args) {
+                    let dst = self.gen_expr(args[0].clone());
+                    let src = self.gen_expr(args[1].clone());
+                    self.add(op, dst, src);
+                    self.kill(src);
+                    return dst;
                 }
-                Plus => gen_binop(IROp::Add, lhs, rhs),
-                Minus => gen_binop(IROp::Sub, lhs, rhs),
-                Logand => {
-                    let x = Some(*NLABEL.lock().unwrap());
-                    *NLABEL.lock().unwrap() += 1;
-
-                    let r1 = gen_expr(lhs);
-                    add(IROp::Unless, r1, x);
-                    let r2 = gen_expr(rhs);
-                    add(IROp::Mov, r1, r2);
-                    kill(r2);
-                    add(IROp::Unless, r1, x);
-                    add(IROp::Imm, r1, Some(1));
-                    label(x);
-                    r1
+                let name = match name.as_str() {
+                    "__builtin_memcpy" => "memcpy".into(),
+                    "__builtin_memset" => "memset".into(),
+                    _ => name,
+                };
+
+                let args_ir = self.gen_args(&args);
+                let r = Some(self.new_reg());
+                self.add(IROp::Call(name, args.len(), args_ir), r, None);
+                for arg in args_ir.iter().take(args.len()) {
+                    self.kill(Some(*arg));
                 }
-                Logor => {
-                    let x = Some(*NLABEL.lock().unwrap());
-                    *NLABEL.lock().unwrap() += 1;
-                    let y = Some(*NLABEL.lock().unwrap());
-                    *NLABEL.lock().unwrap() += 1;
-
-                    let r1 = gen_expr(lhs);
-                    add(IROp::Unless, r1, x);
-                    add(IROp::Imm, r1, Some(1));
-                    jmp(y);
-                    label(x);
-
-                    let r2 = gen_expr(rhs);
-                    add(IROp::Mov, r1, r2);
-                    kill(r2);
-                    add(IROp::Unless, r1, y);
-                    add(IROp::Imm, r1, Some(1));
-                    label(y);
-                    r1
+                r
+            }
+            NodeType::CallPtr(callee, args) => {
+                let args_ir = self.gen_args(&args);
+                // The result goes to the callee's register, as the six
+                // arguments and the callee already take all but one.
+                let callee = self.gen_expr(*callee);
+                self.add(IROp::CallPtr(args.len(), args_ir), callee, callee);
+                for arg in args_ir.iter().take(args.len()) {
+                    self.kill(Some(*arg));
                 }
-                MulEQ | DivEQ | ModEQ | AddEQ | SubEQ | ShlEQ | ShrEQ | BitandEQ | XorEQ
-                | BitorEQ => gen_assign_op(&op, &node.ty, lhs, rhs),
-                EQ => gen_binop(IROp::EQ, lhs, rhs),
-                NE => gen_binop(IROp::NE, lhs, rhs),
-                LE => gen_binop(IROp::LE, lhs, rhs),
-                And => gen_binop(IROp::AND, lhs, rhs),
-                VerticalBar => gen_binop(IROp::OR, lhs, rhs),
-                Hat => gen_binop(IROp::XOR, lhs, rhs),
-                SHL => gen_binop(IROp::SHL, lhs, rhs),
-                SHR => gen_binop(IROp::SHR, lhs, rhs),
-                Mod => gen_binop(IROp::Mod, lhs, rhs),
-                Comma => {
-                    kill(gen_expr(lhs));
-                    gen_expr(rhs)
+                callee
+            }
+            NodeType::Addr(expr) => self.gen_lval(*expr),
+            NodeType::LabelAddr(name) => {
+                let r = Some(self.new_reg());
+                let x = self.user_label(&name);
+                self.add(IROp::CodeAddr, r, Some(x));
+                r
+            }
+            NodeType::Deref(expr) => {
+                let r = self.gen_expr(*expr);
+                self.load(&node.ty, r, r);
+                r
+            }
+            NodeType::StmtExpr(body) => {
+                let orig_label = self.return_label;
+                let orig_reg = self.return_reg;
+                self.return_label = self.new_label();
+                let r = self.new_reg();
+                self.return_reg = r;
+
+                self.gen_stmt(*body);
+                self.label(Some(self.return_label));
+
+                self.return_label = orig_label;
+                self.return_reg = orig_reg;
+                Some(r)
+            }
+            NodeType::BinOp(op, lhs, rhs) => {
+                use self::TokenType::*;
+                match op {
+                    Equal => {
+                        let rhs = self.gen_expr(*rhs);
+                        let lhs = self.gen_lval(*lhs);
+                        self.store(&node.ty, lhs, rhs);
+                        self.kill(lhs);
+                        rhs
+                    }
+                    Plus => self.gen_binop(IROp::Add, *lhs, *rhs),
+                    Minus => self.gen_binop(IROp::Sub, *lhs, *rhs),
+                    Logand => {
+                        let x = Some(self.new_label());
+
+                        let (lsize, rsize) = (cond_size(&lhs.ty), cond_size(&rhs.ty));
+                        let r1 = self.gen_expr(*lhs);
+                        self.add(IROp::Unless(lsize), r1, x);
+                        let r2 = self.gen_expr(*rhs);
+                        self.add(IROp::Mov, r1, r2);
+                        self.kill(r2);
+                        self.add(IROp::Unless(rsize), r1, x);
+                        self.add(IROp::Imm, r1, Some(1));
+                        self.label(x);
+                        r1
+                    }
+                    Logor => {
+                        let x = Some(self.new_label());
+                        let y = Some(self.new_label());
+
+                        let (lsize, rsize) = (cond_size(&lhs.ty), cond_size(&rhs.ty));
+                        let r1 = self.gen_expr(*lhs);
+                        self.add(IROp::Unless(lsize), r1, x);
+                        self.add(IROp::Imm, r1, Some(1));
+                        self.jmp(y);
+                        self.label(x);
+
+                        let r2 = self.gen_expr(*rhs);
+                        self.add(IROp::Mov, r1, r2);
+                        self.kill(r2);
+                        self.add(IROp::Unless(rsize), r1, y);
+                        self.add(IROp::Imm, r1, Some(1));
+                        self.label(y);
+                        r1
+                    }
+                    MulEQ | DivEQ | ModEQ | AddEQ | SubEQ | ShlEQ | ShrEQ | BitandEQ | XorEQ
+                    | BitorEQ => self.gen_assign_op(&op, &node.ty, *lhs, *rhs),
+                    EQ => self.gen_binop(IROp::EQ(op_size(&lhs.ty, &rhs.ty)), *lhs, *rhs),
+                    NE => self.gen_binop(IROp::NE(op_size(&lhs.ty, &rhs.ty)), *lhs, *rhs),
+                    LE | LeftAngleBracket => self.gen_binop(cmp_op(&op, &lhs.ty), *lhs, *rhs),
+                    And => self.gen_binop(IROp::AND, *lhs, *rhs),
+                    VerticalBar => self.gen_binop(IROp::OR, *lhs, *rhs),
+                    Hat => self.gen_binop(IROp::XOR, *lhs, *rhs),
+                    SHL => self.gen_binop(IROp::SHL, *lhs, *rhs),
+                    SHR => self.gen_binop(shr_op(&lhs.ty), *lhs, *rhs),
+                    Div | Mod => self.gen_binop(div_op(&op, &lhs.ty), *lhs, *rhs),
+                    Comma => {
+                        let r = self.gen_expr(*lhs);
+                        self.kill(r);
+                        self.gen_expr(*rhs)
+                    }
+                    _ => self.gen_binop(IROp::from(op), *lhs, *rhs),
                 }
-                _ => gen_binop(IROp::from(op), lhs, rhs),
             }
+            NodeType::Neg(expr) => {
+                let r = self.gen_expr(*expr);
+                self.add(IROp::Neg, r, None);
+                r
+            }
+            NodeType::Cast(expr) => {
+                let size = expr.ty.size as u8;
+                let op = if expr.ty.is_unsigned {
+                    IROp::Zext(size)
+                } else {
+                    IROp::Sext(size)
+                };
+                let is_retype = is_extended(&expr) || expr.ty.size == node.ty.size;
+                let r = self.gen_expr(*expr);
+                if !is_retype {
+                    self.add(op, r, None);
+                }
+                r
+            }
+            NodeType::PostInc(expr) => Some(self.gen_post_inc(&node.ty, *expr, 1) as usize),
+            NodeType::PostDec(expr) => Some(self.gen_post_inc(&node.ty, *expr, -1) as usize),
+            NodeType::Ternary(cond, then, els) => {
+                //      cond then els  then
+                // return 1 ? 3 : 5; => 3
+                let x = Some(self.new_label());
+                let y = Some(self.new_label());
+                let size = cond_size(&cond.ty);
+                let r = self.gen_expr(*cond);
+
+                self.add(IROp::Unless(size), r, x);
+                // With `cond ?: els`, r already holds the value to yield.
+                if !matches!(then.op, NodeType::Null) {
+                    let r2 = self.gen_expr(*then);
+                    self.add(IROp::Mov, r, r2);
+                    self.kill(r2);
+                }
+                self.jmp(y);
+
+                self.label(x);
+                let r3 = self.gen_expr(*els);
+                self.add(IROp::Mov, r, r3);
+                self.kill(r3);
+                self.label(y);
+                r
+            }
+            NodeType::CompLit(init, var) => {
+                self.gen_stmt(*init);
+                self.gen_expr(*var)
+            }
+            NodeType::Exclamation(expr) => {
+                let size = cond_size(&expr.ty);
+                let lhs = self.gen_expr(*expr);
+                let rhs = Some(self.new_reg());
+                self.add(IROp::Imm, rhs, Some(0));
+                self.add(IROp::EQ(size), lhs, rhs);
+                self.kill(rhs);
+                lhs
+            }
+            e => unreachable!("{:?}", e),
         }
-        NodeType::Neg(expr) => {
-            let r = gen_expr(expr);
-            add(IROp::Neg, r, None);
-            r
-        }
-        NodeType::PostInc(expr) => Some(gen_post_inc(&node.ty, expr, 1) as usize),
-        NodeType::PostDec(expr) => Some(gen_post_inc(&node.ty, expr, -1) as usize),
-        NodeType::Ternary(cond, then, els) => {
-            //      cond then els  then
-            // return 1 ? 3 : 5; => 3
-            let x = Some(*NLABEL.lock().unwrap());
-            *NLABEL.lock().unwrap() += 1;
-            let y = Some(*NLABEL.lock().unwrap());
-            *NLABEL.lock().unwrap() += 1;
-            let r = gen_expr(cond);
-
-            add(IROp::Unless, r, x);
-            let r2 = gen_expr(then);
-            add(IROp::Mov, r, r2);
-            kill(r2);
-            jmp(y);
-
-            label(x);
-            let r3 = gen_expr(els);
-            add(IROp::Mov, r, r3);
-            kill(r3);
-            label(y);
-            r
-        }
-        NodeType::Exclamation(expr) => {
-            let lhs = gen_expr(expr);
-            let rhs = Some(*NUM_REGS.lock().unwrap());
-            *NUM_REGS.lock().unwrap() += 1;
-            add(IROp::Imm, rhs, Some(0));
-            add(IROp::EQ, lhs, rhs);
-            kill(rhs);
-            lhs
-        }
-        e => unreachable!("{:?}", e),
     }
-}
 
-fn gen_stmt(node: Node) {
-    match node.op {
-        NodeType::Null => return,
-        NodeType::Vardef(_, init_may, Scope::Local(offset)) => {
-            if let Some(init) = init_may {
-                let rhs = gen_expr(init);
-                let lhs = Some(*NUM_REGS.lock().unwrap());
-                *NUM_REGS.lock().unwrap() += 1;
-                add(IROp::Bprel, lhs, Some(offset));
-                store(&node.ty, lhs, rhs);
-                kill(lhs);
-                kill(rhs);
-            }
-            return;
+    fn gen_stmt(&mut self, node: Node) {
+        // `break` after a `return` is common, and harmless.
+        let is_checked = !std::matches!(
+            node.op,
+            NodeType::Null
+                | NodeType::Break
+                | NodeType::Continue
+                | NodeType::CompStmt(_)
+                | NodeType::Vardef(_, None, _)
+        );
+        if let (true, Some(t)) = (is_checked, &node.token) {
+            self.stmts.push((self.code.len(), t.clone()));
         }
-        NodeType::If(cond, then, els_may) => {
-            if let Some(els) = els_may {
-                let x = Some(*NLABEL.lock().unwrap());
-                *NLABEL.lock().unwrap() += 1;
-                let y = Some(*NLABEL.lock().unwrap());
-                *NLABEL.lock().unwrap() += 1;
-                let r = gen_expr(cond.clone());
-                add(IROp::Unless, r, x);
-                kill(r);
-                gen_stmt(*then.clone());
-                jmp(y);
-                label(x);
-                gen_stmt(*els);
-                label(y);
-                return;
+
+        match node.op {
+            NodeType::Null => (),
+            NodeType::Vardef(_, init_may, Scope::Local(offset)) => {
+                if let Some(init) = init_may {
+                    let rhs = self.gen_expr(*init);
+                    let lhs = Some(self.new_reg());
+                    self.add(IROp::Bprel, lhs, Some(offset));
+                    self.store(&node.ty, lhs, rhs);
+                    self.kill(lhs);
+                    self.kill(rhs);
+                }
             }
+            NodeType::If(cond, then, els_may) => {
+                if let Some(els) = els_may {
+                    let x = Some(self.new_label());
+                    let y = Some(self.new_label());
+                    let r = self.gen_expr(*cond.clone());
+                    self.add(IROp::Unless(cond_size(&cond.ty)), r, x);
+                    self.kill(r);
+                    self.gen_stmt(*then.clone());
+                    self.jmp(y);
+                    self.label(x);
+                    self.gen_stmt(*els);
+                    self.label(y);
+                    return;
+                }
 
-            let x = Some(*NLABEL.lock().unwrap());
-            *NLABEL.lock().unwrap() += 1;
-            let r = gen_expr(cond);
-            add(IROp::Unless, r, x);
-            kill(r);
-            gen_stmt(*then);
-            label(x);
-        }
-        NodeType::For(init, cond, inc, body) => {
-            let x = Some(*NLABEL.lock().unwrap());
-            *NLABEL.lock().unwrap() += 1;
-            let y = Some(*NLABEL.lock().unwrap());
-            *NLABEL.lock().unwrap() += 1;
-            let orig = *BREAK_LABEL.lock().unwrap();
-            *BREAK_LABEL.lock().unwrap() = *NLABEL.lock().unwrap();
-            *NLABEL.lock().unwrap() += 1;
-
-            gen_stmt(*init);
-            label(x);
-            if !cond.is_null() {
-                let r2 = gen_expr(cond);
-                add(IROp::Unless, r2, y);
-                kill(r2);
-            }
-            gen_stmt(*body);
-            if !inc.is_null() {
-                gen_stmt(*inc);
-            }
-            jmp(x);
-            label(y);
-            label(Some(*BREAK_LABEL.lock().unwrap()));
-            *BREAK_LABEL.lock().unwrap() = orig;
-        }
-        NodeType::DoWhile(body, cond) => {
-            let x = Some(*NLABEL.lock().unwrap());
-            *NLABEL.lock().unwrap() += 1;
-            let orig = *BREAK_LABEL.lock().unwrap();
-            *BREAK_LABEL.lock().unwrap() = *NLABEL.lock().unwrap();
-            *NLABEL.lock().unwrap() += 1;
-            label(x);
-            gen_stmt(*body);
-            let r = gen_expr(cond);
-            add(IROp::If, r, x);
-            kill(r);
-            label(Some(*BREAK_LABEL.lock().unwrap()));
-            *BREAK_LABEL.lock().unwrap() = orig;
-        }
-        NodeType::Break => {
-            let break_label = *BREAK_LABEL.lock().unwrap();
-            if break_label == 0 {
-                panic!("stray 'break' statement");
+                let x = Some(self.new_label());
+                let size = cond_size(&cond.ty);
+                let r = self.gen_expr(*cond);
+                self.add(IROp::Unless(size), r, x);
+                self.kill(r);
+                self.gen_stmt(*then);
+                self.label(x);
             }
-            jmp(Some(break_label));
-        }
-        NodeType::Return(expr) => {
-            let r = gen_expr(expr);
-
-            // Statement expression (GNU extension)
-            if *RETURN_LABEL.lock().unwrap() != 0 {
-                add(IROp::Mov, Some(*RETURN_REG.lock().unwrap()), r);
-                kill(r);
-                jmp(Some(*RETURN_LABEL.lock().unwrap()));
-                return;
+            NodeType::For(init, cond, inc, body) => {
+                // `continue` runs the increment, which a while loop
+                // does not have, before testing the condition again.
+                let x = Some(self.new_label());
+                let (brk, cont) = self.enter_loop();
+
+                self.gen_stmt(*init);
+                self.label(x);
+                if !cond.is_null() {
+                    let size = cond_size(&cond.ty);
+                    let r2 = self.gen_expr(*cond);
+                    self.add(IROp::Unless(size), r2, Some(brk));
+                    self.kill(r2);
+                }
+                self.gen_stmt(*body);
+                self.continue_target(cont);
+                if !inc.is_null() {
+                    self.gen_stmt(*inc);
+                }
+                self.jmp(x);
+                self.loops.pop();
+                self.label(Some(brk));
+            }
+            NodeType::DoWhile(body, cond) => {
+                let x = Some(self.new_label());
+                let (brk, cont) = self.enter_loop();
+                self.label(x);
+                self.gen_stmt(*body);
+                self.continue_target(cont);
+                let size = cond_size(&cond.ty);
+                let r = self.gen_expr(*cond);
+                self.add(IROp::If(size), r, x);
+                self.kill(r);
+                self.loops.pop();
+                self.label(Some(brk));
+            }
+            NodeType::Break => match self.loops.last() {
+                Some(&(brk, _)) => self.jmp(Some(brk)),
+                None => error("stray 'break' statement"),
+            },
+            NodeType::Continue => match self.loops.last() {
+                Some(&(_, cont)) => self.jmp(Some(cont)),
+                None => error("stray 'continue' statement"),
+            },
+            NodeType::Goto(name) => {
+                let x = self.user_label(&name);
+                self.jmp(Some(x));
             }
+            NodeType::GotoPtr(expr) => {
+                let r = self.gen_expr(*expr);
+                self.add(IROp::JmpReg, r, None);
+                self.kill(r);
+            }
+            NodeType::Label(name, stmt) => {
+                if !self.defined_labels.insert(name.clone()) {
+                    error(&format!("duplicate label '{}'", name));
+                }
+                let x = self.user_label(&name);
+                self.label(Some(x));
+                self.gen_stmt(*stmt);
+            }
+            NodeType::Return(expr) => {
+                let r = self.gen_expr(*expr);
+
+                // Statement expression (GNU extension)
+                if self.return_label != 0 {
+                    self.add(IROp::Mov, Some(self.return_reg), r);
+                    self.kill(r);
+                    self.jmp(Some(self.return_label));
+                    return;
+                }
 
-            add(IROp::Return, r, None);
-            kill(r);
-        }
-        NodeType::ExprStmt(expr) => {
-            let r = gen_expr(expr);
-            kill(r);
-        }
-        NodeType::VecStmt(stmts) | NodeType::CompStmt(stmts) => {
-            for n in stmts {
-                gen_stmt(n);
+                self.add(IROp::Return, r, None);
+                self.kill(r);
+            }
+            NodeType::ExprStmt(expr) => {
+                let r = self.gen_expr(*expr);
+                self.kill(r);
+            }
+            NodeType::VecStmt(stmts) | NodeType::CompStmt(stmts) => {
+                for n in stmts {
+                    self.gen_stmt(n);
+                }
             }
+            e => panic!("unknown node: {:?}", e),
         }
-        e => panic!("unknown node: {:?}", e),
     }
 }
 
 pub fn gen_ir(nodes: Vec<Node>) -> Vec<Function> {
     let mut v = vec![];
+    let mut gen = Gen::new();
     for node in nodes {
         match node.op {
-            NodeType::Func(name, args, body, stacksize) => {
-                *CODE.lock().unwrap() = vec![];
-                // *NUM_REGS.lock().unwrap() = 0;
+            NodeType::Func(name, args, body, stacksize, _, is_static) => {
+                gen.code = vec![];
+                gen.user_labels.clear();
+                gen.defined_labels.clear();
 
                 for (i, arg) in args.iter().enumerate() {
                     if let NodeType::Vardef(_, _, Scope::Local(offset)) = arg.op {
-                        store_arg(&arg.ty, Some(offset), Some(i));
+                        gen.store_arg(&arg.ty, Some(offset), Some(i));
                     } else {
                         unreachable!();
                     }
                 }
-                gen_stmt(*body);
+                gen.gen_stmt(*body);
+
+                let mut undefined: Vec<&String> = gen
+                    .user_labels
+                    .keys()
+                    .filter(|name| !gen.defined_labels.contains(*name))
+                    .collect();
+                undefined.sort();
+                if let Some(name) = undefined.first() {
+                    error(&format!("label '{}' used but not defined", name));
+                }
+
+                // Reaching the end of main returns 0, as in C99.
+                if name == "main" {
+                    let r = Some(gen.new_reg());
+                    gen.add(IROp::Imm, r, Some(0));
+                    gen.add(IROp::Return, r, None);
+                    gen.kill(r);
+                }
 
-                v.push(Function::new(name, CODE.lock().unwrap().clone(), stacksize));
+                let f = Function::new(name, mem::take(&mut gen.code), stacksize, is_static);
+                warn_unreachable(&f, &mem::take(&mut gen.stmts));
+                v.push(f);
             }
             NodeType::Vardef(_, _, _) => (),
             _ => panic!("parse error."),
@@ -1,4 +1,4 @@
-use crate::gen_ir::{Function, IROp, IR};
+use crate::gen_ir::{stack_args_size, Function, IROp, IR};
 use crate::util::roundup;
 use crate::{Scope, Var, REGS_N};
 
@@ -6,7 +6,8 @@ const REGS: [&str; REGS_N] = ["r10", "r11", "rbx", "r12", "r13", "r14", "r15"];
 const REGS8: [&str; REGS_N] = ["r10b", "r11b", "bl", "r12b", "r13b", "r14b", "r15b"];
 const REGS32: [&str; REGS_N] = ["r10d", "r11d", "ebx", "r12d", "r13d", "r14d", "r15d"];
 
-use std::sync::Mutex;
+use std::collections::HashSet;
+use std::fmt::Write;
 
 // Quoted from 9cc
 // > This pass generates x86-64 assembly from IR.
@@ -15,11 +16,9 @@ const ARGREGS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
 const ARGREGS8: [&str; 6] = ["dil", "sil", "dl", "cl", "r8b", "r9b"];
 const ARGREGS32: [&str; 6] = ["edi", "esi", "edx", "ecx", "r8d", "r9d"];
 
-lazy_static! {
-    static ref LABEL: Mutex<usize> = Mutex::new(0);
-}
+const CALLEE_SAVED: [&str; 5] = ["rbx", "r12", "r13", "r14", "r15"];
 
-fn backslash_escape(s: String, len: usize) -> String {
+fn backslash_escape(s: &str, len: usize) -> String {
     let mut sb = String::new();
     for i in 0..len {
         if let Some(c) = s.chars().collect::<Vec<char>>().get(i) {
@@ -39,12 +38,9 @@ fn backslash_escape(s: String, len: usize) -> String {
                 sb.push('\\');
                 sb.push(esc);
             } else if c.is_ascii_graphic() || c == &' ' {
-                sb.push(c.clone());
+                sb.push(*c);
             } else {
-                sb.push_str(&format!("\\{:o}", *c as i8));
-            }
-            if i == len - 1 {
-                sb.push_str("\\000");
+                sb.push_str(&format!("\\{:03o}", *c as u8));
             }
         } else {
             sb.push_str("\\000");
@@ -53,17 +49,117 @@ fn backslash_escape(s: String, len: usize) -> String {
     sb
 }
 
+// Appends a line of assembly to the output.
+macro_rules! out{
+    ($out:expr, $($arg:tt)*) => (writeln!($out, $($arg)*).unwrap());
+}
+
 macro_rules! emit{
-    ($fmt:expr) => (print!(concat!("\t", $fmt, "\n")));
-    ($fmt:expr, $($arg:tt)*) => (print!(concat!("\t", $fmt, "\n"), $($arg)*));
+    ($out:expr, $fmt:expr) => (out!($out, concat!("\t", $fmt)));
+    ($out:expr, $fmt:expr, $($arg:tt)*) => (out!($out, concat!("\t", $fmt), $($arg)*));
 }
 
-fn emit_cmp(ir: IR, insn: &'static str) {
+fn emit_cmp(out: &mut String, ir: IR, insn: &'static str, size: u8) {
     let lhs = ir.lhs.unwrap();
     let rhs = ir.rhs.unwrap();
-    emit!("cmp {}, {}", REGS[lhs], REGS[rhs]);
-    emit!("{} {}", insn, REGS8[lhs]);
-    emit!("movzb {}, {}", REGS[lhs], REGS8[lhs]);
+    emit!(out, "cmp {}, {}", reg(lhs, size), reg(rhs, size));
+    emit!(out, "{} {}", insn, REGS8[lhs]);
+    emit!(out, "movzb {}, {}", REGS[lhs], REGS8[lhs]);
+}
+
+// Blocks up to this size are copied or filled with unrolled moves through
+// rax. Larger ones use `rep movsb` and `rep stosb`.
+const MEM_UNROLL_MAX: usize = 64;
+
+// Splits `len` bytes into the widest moves that fit, as (offset, size).
+fn mem_chunks(len: usize) -> Vec<(usize, usize)> {
+    let mut v = vec![];
+    let mut off = 0;
+    for &size in &[8, 4, 2, 1] {
+        while len - off >= size {
+            v.push((off, size));
+            off += size;
+        }
+    }
+    v
+}
+
+fn rax(size: usize) -> &'static str {
+    match size {
+        1 => "al",
+        2 => "ax",
+        4 => "eax",
+        8 => "rax",
+        _ => unreachable!(),
+    }
+}
+
+fn emit_memcpy(out: &mut String, dst: usize, src: usize, len: usize) {
+    if len > MEM_UNROLL_MAX {
+        emit!(out, "mov rdi, {}", REGS[dst]);
+        emit!(out, "mov rsi, {}", REGS[src]);
+        emit!(out, "mov rcx, {}", len);
+        emit!(out, "rep movsb");
+        return;
+    }
+    for (off, size) in mem_chunks(len) {
+        emit!(out, "mov {}, [{}+{}]", rax(size), REGS[src], off);
+        emit!(out, "mov [{}+{}], {}", REGS[dst], off, rax(size));
+    }
+}
+
+fn emit_memset(out: &mut String, dst: usize, val: usize, len: usize) {
+    emit!(out, "movzb eax, {}", REGS8[val]);
+    if len > MEM_UNROLL_MAX {
+        emit!(out, "mov rdi, {}", REGS[dst]);
+        emit!(out, "mov rcx, {}", len);
+        emit!(out, "rep stosb");
+        return;
+    }
+
+    // Repeat the byte in every byte of rax.
+    emit!(out, "mov rdx, 0x0101010101010101");
+    emit!(out, "imul rax, rdx");
+    for (off, size) in mem_chunks(len) {
+        emit!(out, "mov [{}+{}], {}", REGS[dst], off, rax(size));
+    }
+}
+
+// Signed division sign-extends the dividend into rdx and truncates
+// toward zero; unsigned division zero-extends it. The quotient is left
+// in rax and the remainder in rdx.
+fn emit_div(out: &mut String, lhs: usize, rhs: usize, size: u8, is_signed: bool, is_mod: bool) {
+    let (ax, dx) = if size == 8 {
+        ("rax", "rdx")
+    } else {
+        ("eax", "edx")
+    };
+    emit!(out, "mov {}, {}", ax, reg(lhs, size));
+    if is_signed {
+        emit!(out, "{}", if size == 8 { "cqo" } else { "cdq" });
+        emit!(out, "idiv {}", reg(rhs, size));
+    } else {
+        emit!(out, "mov {}, 0", dx);
+        emit!(out, "div {}", reg(rhs, size));
+    }
+    emit!(
+        out,
+        "mov {}, {}",
+        reg(lhs, size),
+        if is_mod { dx } else { ax }
+    );
+}
+
+// Signed division by 2^k. An arithmetic shift rounds toward negative
+// infinity, so 2^k-1 is added to a negative dividend first to make it
+// round toward zero like idiv.
+fn emit_div_pow2(out: &mut String, lhs: usize, k: usize, size: u8) {
+    let (ax, bits) = if size == 8 { ("rax", 64) } else { ("eax", 32) };
+    emit!(out, "mov {}, {}", ax, reg(lhs, size));
+    emit!(out, "sar {}, {}", ax, bits - 1);
+    emit!(out, "shr {}, {}", ax, bits - k);
+    emit!(out, "add {}, {}", reg(lhs, size), ax);
+    emit!(out, "sar {}, {}", reg(lhs, size), k);
 }
 
 fn reg(r: usize, size: u8) -> &'static str {
@@ -84,145 +180,307 @@ fn argreg(r: usize, size: u8) -> &'static str {
     }
 }
 
-fn gen(f: Function) {
+// With -fPIC, a symbol defined in this file is addressed relative to
+// rip, and any other symbol through the GOT.
+fn emit_label_addr(out: &mut String, r: usize, name: &str, pic: bool, defined: &HashSet<String>) {
+    if !pic {
+        emit!(out, "lea {}, {}", REGS[r], name);
+    } else if defined.contains(name) {
+        emit!(out, "lea {}, [rip + {}]", REGS[r], name);
+    } else {
+        emit!(out, "mov {}, [rip + {}@GOTPCREL]", REGS[r], name);
+    }
+}
+
+// The SysV ABI requires rsp to be 16-byte aligned at a call. Below the
+// caller's aligned rsp are the return address, the saved rbp, the local
+// variables, the callee-saved registers and, at a call site, r10 and
+// r11, so the local area is padded to make all of them a multiple of 16.
+fn frame_size(stacksize: usize) -> usize {
+    let pushed = 8 * (2 + CALLEE_SAVED.len() + 2);
+    let frame = roundup(stacksize + pushed, 16) - pushed;
+    assert_eq!((frame + pushed) % 16, 0, "misaligned stack frame");
+    frame
+}
+
+// Calls `target`, a label or a register holding the address, and puts
+// the result in REGS[lhs].
+fn emit_call(out: &mut String, target: &str, nargs: usize, args: &[usize; 6], lhs: usize) {
+    for i in 0..nargs.min(6) {
+        emit!(out, "mov {}, {}", ARGREGS[i], REGS[args[i]]);
+    }
+    // With stack arguments, r10 and r11 go to the top of
+    // the argument area rather than between it and the call.
+    let area = if nargs > 6 { stack_args_size(nargs) } else { 0 };
+    if area > 0 {
+        emit!(out, "mov [rsp+{}], r10", area - 16);
+        emit!(out, "mov [rsp+{}], r11", area - 8);
+    } else {
+        emit!(out, "push r10");
+        emit!(out, "push r11");
+    }
+    emit!(out, "mov rax, 0");
+    emit!(out, "call {}", target);
+    if area > 0 {
+        emit!(out, "mov r11, [rsp+{}]", area - 8);
+        emit!(out, "mov r10, [rsp+{}]", area - 16);
+        emit!(out, "add rsp, {}", area);
+    } else {
+        emit!(out, "pop r11");
+        emit!(out, "pop r10");
+    }
+
+    emit!(out, "mov {}, rax", REGS[lhs]);
+}
+
+fn gen(out: &mut String, f: Function, label: usize, pic: bool, defined: &HashSet<String>) {
     use self::IROp::*;
-    let ret = format!(".Lend{}", *LABEL.lock().unwrap());
-    *LABEL.lock().unwrap() += 1;
-
-    println!(".text");
-    println!(".global {}", f.name);
-    println!("{}:", f.name);
-    emit!("push rbp");
-    emit!("mov rbp, rsp");
-    emit!("sub rsp, {}", roundup(f.stacksize, 16));
-    emit!("push r12");
-    emit!("push r13");
-    emit!("push r14");
-    emit!("push r15");
+    let ret = format!(".Lend{}", label);
+    let body = format!(".Lbody{}", label);
+
+    out!(out, ".text");
+    if !f.is_static {
+        out!(out, ".global {}", f.name);
+    }
+    emit!(out, ".type {}, @function", f.name);
+    out!(out, "{}:", f.name);
+    emit!(out, "push rbp");
+    emit!(out, "mov rbp, rsp");
+    emit!(out, "sub rsp, {}", frame_size(f.stacksize));
+    for reg in CALLEE_SAVED.iter() {
+        emit!(out, "push {}", reg);
+    }
+    out!(out, "{}:", body);
 
     for ir in f.ir {
         let lhs = ir.lhs.unwrap();
         let rhs = ir.rhs.unwrap_or(0);
         match ir.op {
-            Imm => emit!("mov {}, {}", REGS[lhs], rhs as i32),
-            Mov => emit!("mov {}, {}", REGS[lhs], REGS[rhs]),
+            Imm => emit!(out, "mov {}, {}", REGS[lhs], rhs as i64),
+            Mov => emit!(out, "mov {}, {}", REGS[lhs], REGS[rhs]),
             Return => {
-                emit!("mov rax, {}", REGS[lhs]);
-                emit!("jmp {}", ret);
+                emit!(out, "mov rax, {}", REGS[lhs]);
+                emit!(out, "jmp {}", ret);
             }
             Call(name, nargs, args) => {
+                let target = if pic { format!("{}@PLT", name) } else { name };
+                emit_call(out, &target, nargs, &args, lhs);
+            }
+            CallPtr(nargs, args) => emit_call(out, REGS[rhs], nargs, &args, lhs),
+            // A self tail call reuses the current frame: the arguments
+            // are stored to the parameter slots again by the body.
+            TailCall(_, nargs, args) => {
                 for i in 0..nargs {
-                    emit!("mov {}, {}", ARGREGS[i], REGS[args[i]]);
+                    emit!(out, "mov {}, {}", ARGREGS[i], REGS[args[i]]);
                 }
-                emit!("push r10");
-                emit!("push r11");
-                emit!("mov rax, 0");
-                emit!("call {}", name);
-                emit!("pop r11");
-                emit!("pop r10");
-
-                emit!("mov {}, rax", REGS[lhs]);
+                emit!(out, "jmp {}", body);
             }
-            Label => println!(".L{}:", lhs),
-            LabelAddr(name) => emit!("lea {}, {}", REGS[lhs], name),
-            Neg => emit!("neg {}", REGS[lhs]),
-            EQ => emit_cmp(ir, "sete"),
-            NE => emit_cmp(ir, "setne"),
-            LT => emit_cmp(ir, "setl"),
-            LE => emit_cmp(ir, "setle"),
-            AND => emit!("and {}, {}", REGS[lhs], REGS[rhs]),
-            OR => emit!("or {}, {}", REGS[lhs], REGS[rhs]),
-            XOR => emit!("xor {}, {}", REGS[lhs], REGS[rhs]),
+            Label => out!(out, ".L{}:", lhs),
+            LabelAddr(name) => emit_label_addr(out, lhs, &name, pic, defined),
+            Neg => emit!(out, "neg {}", REGS[lhs]),
+            Sext(4) => emit!(out, "movsxd {}, {}", REGS[lhs], REGS32[lhs]),
+            Sext(_) => emit!(out, "movsx {}, {}", REGS[lhs], REGS8[lhs]),
+            Zext(4) => emit!(out, "mov {}, {}", REGS32[lhs], REGS32[lhs]),
+            Zext(_) => emit!(out, "movzb {}, {}", REGS[lhs], REGS8[lhs]),
+            EQ(size) => emit_cmp(out, ir, "sete", size),
+            NE(size) => emit_cmp(out, ir, "setne", size),
+            LT(size) => emit_cmp(out, ir, "setl", size),
+            LE(size) => emit_cmp(out, ir, "setle", size),
+            ULT(size) => emit_cmp(out, ir, "setb", size),
+            ULE(size) => emit_cmp(out, ir, "setbe", size),
+            AND => emit!(out, "and {}, {}", REGS[lhs], REGS[rhs]),
+            OR => emit!(out, "or {}, {}", REGS[lhs], REGS[rhs]),
+            XOR => emit!(out, "xor {}, {}", REGS[lhs], REGS[rhs]),
             SHL => {
-                emit!("mov cl, {}", REGS8[rhs]);
-                emit!("shl {}, cl", REGS[lhs]);
+                emit!(out, "mov cl, {}", REGS8[rhs]);
+                emit!(out, "shl {}, cl", REGS[lhs]);
             }
-            SHR => {
-                emit!("mov cl, {}", REGS8[rhs]);
-                emit!("shr {}, cl", REGS[lhs]);
+            SHR(size) => {
+                emit!(out, "mov cl, {}", REGS8[rhs]);
+                emit!(out, "shr {}, cl", reg(lhs, size));
             }
-            Mod => {
-                /* Same meaning(?).
-                 * emit!("mov rdx, 0");
-                 * emit!("mov rax, {}", REGS[lhs]);
-                 */
-                emit!("mov rax, {}", REGS[lhs]);
-                emit!("cqo"); // rax -> rdx:rax
-                emit!("div {}", REGS[rhs]);
-                emit!("mov {}, rdx", REGS[lhs]);
+            // The result is sign-extended so that a negative int can
+            // still be added to a pointer.
+            SAR(size) => {
+                emit!(out, "mov cl, {}", REGS8[rhs]);
+                emit!(out, "sar {}, cl", reg(lhs, size));
+                if size == 4 {
+                    emit!(out, "movsxd {}, {}", REGS[lhs], REGS32[lhs]);
+                }
             }
-            Jmp => emit!("jmp .L{}", lhs),
-            If => {
-                emit!("cmp {}, 0", REGS[lhs]);
-                emit!("jne .L{}", rhs);
+            ShlImm => emit!(out, "shl {}, {}", REGS[lhs], rhs),
+            DivPow2(size) => emit_div_pow2(out, lhs, rhs, size),
+            UDivPow2(size) => emit!(out, "shr {}, {}", reg(lhs, size), rhs),
+            Div(size) => emit_div(out, lhs, rhs, size, true, false),
+            UDiv(size) => emit_div(out, lhs, rhs, size, false, false),
+            Mod(size) => emit_div(out, lhs, rhs, size, true, true),
+            UMod(size) => emit_div(out, lhs, rhs, size, false, true),
+            Jmp => emit!(out, "jmp .L{}", lhs),
+            JmpReg => emit!(out, "jmp {}", REGS[lhs]),
+            CodeAddr => emit!(out, "lea {}, [rip + .L{}]", REGS[lhs], rhs),
+            If(size) => {
+                emit!(out, "cmp {}, 0", reg(lhs, size));
+                emit!(out, "jne .L{}", rhs);
             }
-            Unless => {
-                emit!("cmp {}, 0", REGS[lhs]);
-                emit!("je .L{}", rhs);
+            Unless(size) => {
+                emit!(out, "cmp {}, 0", reg(lhs, size));
+                emit!(out, "je .L{}", rhs);
             }
-            Load(size) => {
-                emit!("mov {}, [{}]", reg(lhs, size), REGS[rhs]);
+            Load(size, _) => {
+                emit!(out, "mov {}, [{}]", reg(lhs, size), REGS[rhs]);
                 if size == 1 {
-                    emit!("movzb {}, {}", REGS[lhs], REGS8[lhs]);
+                    emit!(out, "movzb {}, {}", REGS[lhs], REGS8[lhs]);
                 }
             }
-            Store(size) => emit!("mov [{}], {}", REGS[lhs], reg(rhs, size)),
-            StoreArg(size) => emit!("mov [rbp-{}], {}", lhs, argreg(rhs, size)),
-            Add => emit!("add {}, {}", REGS[lhs], REGS[rhs]),
-            AddImm => emit!("add {}, {}", REGS[lhs], rhs as i32),
-            Sub => emit!("sub {}, {}", REGS[lhs], REGS[rhs]),
-            SubImm => emit!("sub {}, {}", REGS[lhs], rhs as i32),
-            Bprel => emit!("lea {}, [rbp-{}]", REGS[lhs], rhs),
+            LoadSx(1, _) => emit!(out, "movsx {}, byte ptr [{}]", REGS[lhs], REGS[rhs]),
+            LoadSx(4, _) => emit!(out, "movsxd {}, dword ptr [{}]", REGS[lhs], REGS[rhs]),
+            LoadSx(size, _) => emit!(out, "mov {}, [{}]", reg(lhs, size), REGS[rhs]),
+            Store(size, _) => emit!(out, "mov [{}], {}", REGS[lhs], reg(rhs, size)),
+            Memcpy(len) => emit_memcpy(out, lhs, rhs, len),
+            Memset(len) => emit_memset(out, lhs, rhs, len),
+            // Parameters after the sixth are above the return address.
+            StoreArg(size) if rhs >= 6 => {
+                emit!(out, "mov rax, [rbp+{}]", 16 + 8 * (rhs - 6));
+                emit!(out, "mov [rbp-{}], {}", lhs, rax(size as usize));
+            }
+            StoreArg(size) => emit!(out, "mov [rbp-{}], {}", lhs, argreg(rhs, size)),
+            ArgArea => emit!(out, "sub rsp, {}", stack_args_size(lhs)),
+            StackArg => emit!(out, "mov [rsp+{}], {}", rhs, REGS[lhs]),
+            Add => emit!(out, "add {}, {}", REGS[lhs], REGS[rhs]),
+            AddImm => emit!(out, "add {}, {}", REGS[lhs], rhs as i32),
+            Sub => emit!(out, "sub {}, {}", REGS[lhs], REGS[rhs]),
+            SubImm => emit!(out, "sub {}, {}", REGS[lhs], rhs as i32),
+            Bprel => emit!(out, "lea {}, [rbp-{}]", REGS[lhs], rhs),
             Mul => {
-                emit!("mov rax, {}", REGS[rhs]);
-                emit!("mul {}", REGS[lhs]);
-                emit!("mov {}, rax", REGS[lhs]);
+                emit!(out, "mov rax, {}", REGS[rhs]);
+                emit!(out, "mul {}", REGS[lhs]);
+                emit!(out, "mov {}, rax", REGS[lhs]);
             }
             MulImm => {
                 if rhs < 256 && rhs.count_ones() == 1 {
-                    emit!("shl {}, {}", REGS[lhs], rhs.trailing_zeros());
+                    emit!(out, "shl {}, {}", REGS[lhs], rhs.trailing_zeros());
                 } else {
-                    emit!("mov rax, {}", rhs as i32);
-                    emit!("mul {}", REGS[lhs]);
-                    emit!("mov {}, rax", REGS[lhs]);
+                    emit!(out, "mov rax, {}", rhs as i32);
+                    emit!(out, "mul {}", REGS[lhs]);
+                    emit!(out, "mov {}, rax", REGS[lhs]);
                 }
             }
-            Div => {
-                emit!("mov rax, {}", REGS[lhs]);
-                emit!("cqo");
-                emit!("div {}", REGS[rhs]);
-                emit!("mov {}, rax", REGS[lhs]);
-            }
             Nop | Kill => (),
         }
     }
 
-    println!("{}:", ret);
-    emit!("pop r15");
-    emit!("pop r14");
-    emit!("pop r13");
-    emit!("pop r12");
-    emit!("mov rsp, rbp");
-    emit!("pop rbp");
-    emit!("ret");
+    out!(out, "{}:", ret);
+    for reg in CALLEE_SAVED.iter().rev() {
+        emit!(out, "pop {}", reg);
+    }
+    emit!(out, "mov rsp, rbp");
+    emit!(out, "pop rbp");
+    emit!(out, "ret");
+    emit!(out, ".size {}, .-{}", f.name, f.name);
 }
 
-pub fn gen_x86(globals: Vec<Var>, fns: Vec<Function>) {
-    println!(".intel_syntax noprefix");
-    println!(".data");
-    for var in globals {
-        if let Scope::Global(data, len, is_extern) = var.scope {
-            if is_extern {
-                continue;
+#[derive(Debug, PartialEq)]
+pub enum Section {
+    Data,
+    Rodata,
+    Bss,
+}
+
+// Globals without an initializer are zero-filled, so they go to .bss.
+// Const globals never change, so they go to the read-only .rodata,
+// unless they hold an address that the linker has to fill in.
+pub fn section(var: &Var) -> Section {
+    match var.scope {
+        Scope::Global(ref data, ..) if data.is_empty() && var.relocs.is_empty() => Section::Bss,
+        _ if var.ty.is_const && var.relocs.is_empty() => Section::Rodata,
+        _ => Section::Data,
+    }
+}
+
+// The ELF symbol type and size let gdb and objdump tell a symbol's
+// extent. String literals have local labels, which are not symbols.
+fn begin_object(out: &mut String, var: &Var) {
+    if let Scope::Global(_, _, _, false) = var.scope {
+        out!(out, ".global {}", var.name);
+    }
+    emit!(out, ".align {}", var.ty.align);
+    if !var.name.starts_with('.') {
+        emit!(out, ".type {}, @object", var.name);
+    }
+    out!(out, "{}:", var.name);
+}
+
+fn end_object(out: &mut String, var: &Var) {
+    if !var.name.starts_with('.') {
+        emit!(out, ".size {}, .-{}", var.name, var.name);
+    }
+}
+
+fn emit_data(out: &mut String, var: Var) {
+    begin_object(out, &var);
+    if let Scope::Global(ref data, len, ..) = var.scope {
+        // The bytes between the addresses, which the linker fills in.
+        let data: Vec<char> = data.chars().collect();
+        let mut pos = 0;
+        for (offset, label) in &var.relocs {
+            if pos < *offset {
+                let s: String = data[pos..*offset].iter().collect();
+                emit!(out, ".ascii \"{}\"", backslash_escape(&s, offset - pos));
             }
-            println!("{}:", var.name);
-            emit!(".ascii \"{}\"", backslash_escape(data, len));
-            continue;
+            emit!(out, ".quad {}", label);
+            pos = offset + 8;
         }
-        unreachable!();
+        if pos < len || var.relocs.is_empty() {
+            let s: String = data.iter().skip(pos).collect();
+            emit!(out, ".ascii \"{}\"", backslash_escape(&s, len - pos));
+        }
+        end_object(out, &var);
+        return;
+    }
+    unreachable!();
+}
+
+// Returns the assembly for the whole program.
+pub fn gen_x86(globals: Vec<Var>, fns: Vec<Function>, pic: bool) -> String {
+    let mut out = String::new();
+    out!(out, ".intel_syntax noprefix");
+
+    let defined: HashSet<String> = globals
+        .iter()
+        .filter(|var| !matches!(var.scope, Scope::Global(_, _, true, _)))
+        .map(|var| var.name.clone())
+        .collect();
+
+    // Extern globals are defined elsewhere and emit nothing.
+    let (bss, data): (Vec<Var>, Vec<Var>) = globals
+        .into_iter()
+        .filter(|var| !matches!(var.scope, Scope::Global(_, _, true, _)))
+        .partition(|var| section(var) == Section::Bss);
+    let (rodata, data): (Vec<Var>, Vec<Var>) = data
+        .into_iter()
+        .partition(|var| section(var) == Section::Rodata);
+
+    out!(out, ".data");
+    for var in data {
+        emit_data(&mut out, var);
+    }
+
+    if !rodata.is_empty() {
+        out!(out, ".section .rodata");
+        for var in rodata {
+            emit_data(&mut out, var);
+        }
+    }
+
+    out!(out, ".bss");
+    for var in bss {
+        begin_object(&mut out, &var);
+        emit!(out, ".zero {}", var.ty.size);
+        end_object(&mut out, &var);
     }
 
-    for f in fns {
-        gen(f);
+    for (label, f) in fns.into_iter().enumerate() {
+        gen(&mut out, f, label, pic, &defined);
     }
+    out
 }
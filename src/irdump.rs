@@ -21,36 +21,55 @@ impl<'a> From<&'a IROp> for IRInfo {
             Add => IRInfo::new("ADD", IRType::RegReg),
             AddImm => IRInfo::new("ADD", IRType::RegImm),
             Call(_, _, _) => IRInfo::new("CALL", IRType::Call),
-            Div => IRInfo::new("DIV", IRType::RegReg),
+            CallPtr(_, _) => IRInfo::new("CALL", IRType::Call),
+            TailCall(_, _, _) => IRInfo::new("TAILCALL", IRType::Call),
+            ArgArea => IRInfo::new("ARG_AREA", IRType::Imm),
+            StackArg => IRInfo::new("STACK_ARG", IRType::RegImm),
+            Div(_) => IRInfo::new("DIV", IRType::RegReg),
+            UDiv(_) => IRInfo::new("UDIV", IRType::RegReg),
             Imm => IRInfo::new("MOV", IRType::RegImm),
             Jmp => IRInfo::new("JMP", IRType::Jmp),
+            JmpReg => IRInfo::new("JMP", IRType::Reg),
+            CodeAddr => IRInfo::new("CODE_ADDR", IRType::RegLabel),
             Kill => IRInfo::new("KILL", IRType::Reg),
             Label => IRInfo::new("", IRType::Label),
             LabelAddr(_) => IRInfo::new("LABEL_ADDR", IRType::LabelAddr),
-            EQ => IRInfo::new("EQ", IRType::RegReg),
-            NE => IRInfo::new("NE", IRType::RegReg),
-            LE => IRInfo::new("LE", IRType::RegReg),
-            LT => IRInfo::new("LT", IRType::RegReg),
+            EQ(_) => IRInfo::new("EQ", IRType::RegReg),
+            NE(_) => IRInfo::new("NE", IRType::RegReg),
+            LE(_) => IRInfo::new("LE", IRType::RegReg),
+            LT(_) => IRInfo::new("LT", IRType::RegReg),
+            ULE(_) => IRInfo::new("ULE", IRType::RegReg),
+            ULT(_) => IRInfo::new("ULT", IRType::RegReg),
             AND => IRInfo::new("AND", IRType::RegReg),
             OR => IRInfo::new("OR", IRType::RegReg),
             XOR => IRInfo::new("XOR", IRType::RegReg),
             SHL => IRInfo::new("SHL", IRType::RegReg),
-            SHR => IRInfo::new("SHR", IRType::RegReg),
-            Mod => IRInfo::new("MOD", IRType::RegReg),
+            SHR(_) => IRInfo::new("SHR", IRType::RegReg),
+            SAR(_) => IRInfo::new("SAR", IRType::RegReg),
+            ShlImm => IRInfo::new("SHL", IRType::RegImm),
+            DivPow2(_) => IRInfo::new("DIV_POW2", IRType::RegImm),
+            UDivPow2(_) => IRInfo::new("UDIV_POW2", IRType::RegImm),
+            Mod(_) => IRInfo::new("MOD", IRType::RegReg),
+            UMod(_) => IRInfo::new("UMOD", IRType::RegReg),
             Neg => IRInfo::new("NEG", IRType::Reg),
-            Load(_) => IRInfo::new("LOAD", IRType::Mem),
+            Sext(_) => IRInfo::new("SEXT", IRType::Reg),
+            Zext(_) => IRInfo::new("ZEXT", IRType::Reg),
+            Load(..) => IRInfo::new("LOAD", IRType::Mem),
+            LoadSx(..) => IRInfo::new("LOADSX", IRType::Mem),
             Mov => IRInfo::new("MOV", IRType::RegReg),
             Mul => IRInfo::new("MUL", IRType::RegReg),
             MulImm => IRInfo::new("MUL", IRType::RegImm),
             Nop => IRInfo::new("NOP", IRType::Noarg),
             Return => IRInfo::new("RET", IRType::Reg),
-            Store(_) => IRInfo::new("STORE", IRType::Mem),
+            Store(..) => IRInfo::new("STORE", IRType::Mem),
             StoreArg(_) => IRInfo::new("STORE_ARG", IRType::StoreArg),
+            Memcpy(_) => IRInfo::new("MEMCPY", IRType::Mem),
+            Memset(_) => IRInfo::new("MEMSET", IRType::Mem),
             Sub => IRInfo::new("SUB", IRType::RegReg),
             SubImm => IRInfo::new("SUB", IRType::RegImm),
             Bprel => IRInfo::new("BPREL", IRType::RegImm),
-            If => IRInfo::new("IF", IRType::RegLabel),
-            Unless => IRInfo::new("UNLESS", IRType::RegLabel),
+            If(_) => IRInfo::new("IF", IRType::RegLabel),
+            Unless(_) => IRInfo::new("UNLESS", IRType::RegLabel),
         }
     }
 }
@@ -73,40 +92,69 @@ impl fmt::Display for IR {
             Jmp => write!(f, "  {} .L{}", info.name, lhs),
             RegReg => write!(f, "  {} r{}, r{}", info.name, lhs, self.rhs.unwrap()),
             Mem | StoreArg => match self.op {
-                IROp::Load(ref size) | IROp::Store(ref size) => {
-                    write!(f, "  {}{} r{}, {}", info.name, size, lhs, self.rhs.unwrap())
+                IROp::Load(size, is_volatile)
+                | IROp::LoadSx(size, is_volatile)
+                | IROp::Store(size, is_volatile) => {
+                    let rhs = self.rhs.unwrap();
+                    write!(f, "  {}{} r{}, {}", info.name, size, lhs, rhs)?;
+                    if is_volatile {
+                        write!(f, " volatile")?;
+                    }
+                    Ok(())
+                }
+                IROp::Memcpy(len) | IROp::Memset(len) => {
+                    write!(
+                        f,
+                        "  {} r{}, r{}, {}",
+                        info.name,
+                        lhs,
+                        self.rhs.unwrap(),
+                        len
+                    )
                 }
                 IROp::StoreArg(ref size) => {
                     write!(f, "  {}{} {}, {}", info.name, size, lhs, self.rhs.unwrap())
                 }
                 _ => unreachable!(),
             },
-            RegImm => write!(f, "  {} r{}, {}", info.name, lhs, self.rhs.unwrap() as i32),
+            RegImm => write!(f, "  {} r{}, {}", info.name, lhs, self.rhs.unwrap() as i64),
             RegLabel => write!(f, "  {} r{}, .L{}", info.name, lhs, self.rhs.unwrap()),
-            Call => match self.op {
-                IROp::Call(ref name, nargs, args) => {
-                    let mut sb: String = format!("  r{} = {}(", lhs, name);
-                    for (i, arg) in args.iter().enumerate().take(nargs) {
-                        if i != 0 {
-                            sb.push_str(&", ".to_string());
-                        }
-                        sb.push_str(&format!("r{}", *arg));
+            Call => {
+                let (mut sb, nargs, args) = match self.op {
+                    IROp::Call(ref name, nargs, args) => {
+                        (format!("  r{} = {}(", lhs, name), nargs, args)
+                    }
+                    IROp::TailCall(ref name, nargs, args) => {
+                        (format!("  {} {}(", info.name, name), nargs, args)
                     }
-                    sb.push_str(")");
-                    write!(f, "{}", sb)
+                    IROp::CallPtr(nargs, args) => (
+                        format!("  r{} = *r{}(", lhs, self.rhs.unwrap()),
+                        nargs,
+                        args,
+                    ),
+                    _ => unreachable!(),
+                };
+                for (i, arg) in args.iter().enumerate().take(nargs) {
+                    if i != 0 {
+                        sb.push_str(", ");
+                    }
+                    sb.push_str(&format!("r{}", *arg));
                 }
-                _ => unreachable!(),
-            },
+                sb.push(')');
+                write!(f, "{}", sb)
+            }
             Noarg => write!(f, "  {}", info.name),
         }
     }
 }
 
-pub fn dump_ir(fns: &[Function]) {
+pub fn ir_text(fns: &[Function]) -> String {
+    let mut s = String::new();
     for f in fns {
-        eprintln!("{}(): ", f.name);
+        s.push_str(&format!("{}(): \n", f.name));
         for ir in &f.ir {
-            eprintln!("{}", ir);
+            s.push_str(&format!("{}\n", ir));
         }
     }
+    s
 }
@@ -1,13 +1,25 @@
+pub mod astdump;
+pub mod cfg;
+pub mod cse;
+pub mod driver;
 pub mod gen_ir;
 pub mod gen_x86;
 pub mod irdump;
 pub mod parse;
+pub mod peephole;
 pub mod preprocess;
 pub mod regalloc;
 pub mod sema;
+pub mod symbols;
+pub mod tailcall;
 pub mod token;
+mod typing;
 mod util;
 
+pub use crate::driver::{compile, compile_with, CompileError, Options, Output};
+pub use crate::parse::Node;
+pub use crate::token::Token;
+
 #[macro_use]
 extern crate lazy_static;
 
@@ -26,18 +38,36 @@ macro_rules! matches(
 // Token type
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
-    Num(i32),            // Number literal
+    // Number literal. (val, size, is_unsigned)
+    Num(i64, usize, bool),
+    FloatNum(f64),       // Floating-point literal
     Str(String, usize),  // String literal. (str, len)
     CharLiteral(String), // Char literal.
     Ident(String),       // Identifier
     Param(usize),        // Function-like macro parameter
     Arrow,               // ->
+    Ellipsis,            // ...
     Extern,              // "extern"
     Typedef,             // "typedef"
+    Static,              // "static"
+    Inline,              // "inline"
+    Noreturn,            // "_Noreturn"
     Int,                 // "int"
     Char,                // "char"
+    Bool,                // "_Bool"
     Void,                // "void"
     Struct,              // "struct"
+    Enum,                // "enum"
+    Unsigned,            // "unsigned"
+    Signed,              // "signed"
+    Long,                // "long"
+    Register,            // "register"
+    Auto,                // "auto"
+    Attribute,           // "__attribute__"
+    Restrict,            // "restrict"
+    Const,               // "const"
+    Volatile,            // "volatile"
+    Extension,           // "__extension__"
     Plus,                // +
     Minus,               // -
     Mul,                 // *
@@ -57,6 +87,8 @@ pub enum TokenType {
     Do,                  // "do"
     While,               // "while"
     Break,               // "break"
+    Continue,            // "continue"
+    Goto,                // "goto"
     EQ,                  // ==
     NE,                  // !=
     LE,                  // <=
@@ -91,7 +123,10 @@ pub enum TokenType {
     Return,              // "return"
     Sizeof,              // "sizeof"
     Alignof,             // "_Alignof"
+    Offsetof,            // "__builtin_offsetof"
+    Typeof,              // "typeof"
     NewLine,             // preprocessor-only token
+    Eof,                 // end of input
 }
 
 // Character Kind
@@ -138,21 +173,17 @@ impl TokenType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum Ctype {
+    #[default]
     Int,
     Char,
+    Bool,
     Void,
-    Ptr(Box<Type>),           // ptr of
-    Ary(Box<Type>, usize),    // ary of, len
-    Struct(Vec<parse::Node>), // members
-    Func(Box<Type>),
-}
-
-impl Default for Ctype {
-    fn default() -> Ctype {
-        Ctype::Int
-    }
+    Ptr(Box<Type>),                           // ptr of
+    Ary(Box<Type>, usize),                    // ary of, len
+    Struct(Vec<parse::Node>),                 // members
+    Func(Box<Type>, Option<Vec<Type>>, bool), // returning, params (None if unspecified), is_variadic
 }
 
 #[derive(Debug, Clone)]
@@ -160,6 +191,17 @@ pub struct Type {
     pub ty: Ctype,
     pub size: usize,  // sizeof
     pub align: usize, // alignof
+    pub is_unsigned: bool,
+    pub is_const: bool,
+    pub is_volatile: bool,
+    pub is_noreturn: bool, // A function that never returns
+
+    // Function called with the address of a local variable when it goes
+    // out of scope, from `__attribute__((cleanup(fn)))`
+    pub cleanup: Option<String>,
+
+    // Bit-field struct member, (bit offset, bit width)
+    pub bitfield: Option<(usize, usize)>,
 }
 
 impl Default for Type {
@@ -168,14 +210,20 @@ impl Default for Type {
             ty: Ctype::default(),
             size: 4,
             align: 4,
+            is_unsigned: false,
+            is_const: false,
+            is_volatile: false,
+            is_noreturn: false,
+            cleanup: None,
+            bitfield: None,
         }
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum Scope {
-    Local(usize),                // offset
-    Global(String, usize, bool), // data, len, is_extern
+    Local(usize),                      // offset
+    Global(String, usize, bool, bool), // data, len, is_extern, is_static
 }
 
 #[derive(Debug, Clone)]
@@ -183,14 +231,34 @@ pub struct Var {
     ty: Box<Type>,
     pub name: String,
     pub scope: Scope,
+
+    // The addresses in the initializer of a global, as (offset, label)
+    // with the label written as `x` or `x+8`.
+    pub relocs: Vec<(usize, String)>,
 }
 
 impl Var {
     fn new(ty: Box<Type>, name: String, scope: Scope) -> Self {
-        Var { ty, name, scope }
+        Var {
+            ty,
+            name,
+            scope,
+            relocs: vec![],
+        }
     }
 
-    fn new_global(ty: Box<Type>, name: String, data: String, len: usize, is_extern: bool) -> Self {
-        Var::new(ty, name.clone(), Scope::Global(data, len, is_extern))
+    fn new_global(
+        ty: Box<Type>,
+        name: String,
+        data: String,
+        len: usize,
+        is_extern: bool,
+        is_static: bool,
+    ) -> Self {
+        Var::new(
+            ty,
+            name.clone(),
+            Scope::Global(data, len, is_extern, is_static),
+        )
     }
 }
@@ -1,61 +1,113 @@
 extern crate r9cc;
 
-use r9cc::gen_ir::gen_ir;
-use r9cc::gen_x86::gen_x86;
-use r9cc::irdump::dump_ir;
-use r9cc::parse::parse;
-use r9cc::preprocess::Preprocessor;
-use r9cc::regalloc::alloc_regs;
-use r9cc::sema::sema;
-use r9cc::token::tokenize;
+use r9cc::astdump::ast_text;
+use r9cc::cfg::dump_cfg;
+use r9cc::driver::{ast, ir, symbols, tokens, PASSES};
+use r9cc::irdump::ir_text;
+use r9cc::symbols::dump_symbols;
+use r9cc::token::dump_tokens;
+use r9cc::{compile_with, CompileError, Options, Output};
 
 use std::env;
+use std::fs;
+use std::io::{self, Read};
 use std::process;
 
 fn usage() -> ! {
-    eprintln!("Usage: 9cc [-dump-ir1] [-dump-ir2] <file>");
+    eprintln!(
+        "Usage: 9cc [--phase=tokens|ast|ir|asm] [--dump-tokens] [--dump-cfg] [--symbols] [-dump-ir1] [-dump-ir2] [-fPIC] [-finline] [-O0|-O1] [-fno-<pass>] [-fbracket-depth=N] <file>"
+    );
     process::exit(1)
 }
 
+// What to print. The phases stop after tokenizing, parsing, generating
+// IR or generating assembly, and print what that phase produced.
+enum Mode {
+    Tokens,
+    Ast,
+    Ir,
+    Cfg,
+    Symbols,
+    Asm,
+}
+
+fn read_file(path: &str) -> String {
+    let mut input = String::new();
+    let result = if path == "-" {
+        io::stdin().read_to_string(&mut input).map(|_| input)
+    } else {
+        fs::read_to_string(path)
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        process::exit(1)
+    })
+}
+
+// Prints the warnings and IR dumps of a result to stderr, so that they
+// can accompany the output, and exits on errors.
+fn check<T>(result: Result<Output<T>, Vec<CompileError>>) -> T {
+    let out = result.unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        process::exit(1)
+    });
+    for w in &out.warnings {
+        eprintln!("{}", w);
+    }
+    eprint!("{}", out.dump);
+    out.value
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
         usage();
     }
 
-    let mut dump_ir1 = false;
-    let mut dump_ir2 = false;
-    let path;
+    let mut mode = Mode::Asm;
+    let mut opts = Options::default();
+    let mut path = None;
 
-    if args.len() == 3 && args[1] == "-dump-ir1" {
-        dump_ir1 = true;
-        path = args[2].clone();
-    } else if args.len() == 3 && args[1] == "-dump-ir2" {
-        dump_ir2 = true;
-        path = args[2].clone();
-    } else {
-        if args.len() != 2 {
-            usage();
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--phase=tokens" | "--dump-tokens" => mode = Mode::Tokens,
+            "--phase=ast" => mode = Mode::Ast,
+            "--phase=ir" => mode = Mode::Ir,
+            "--phase=asm" => mode = Mode::Asm,
+            "--dump-cfg" => mode = Mode::Cfg,
+            "--symbols" => mode = Mode::Symbols,
+            "-dump-ir1" => opts.dump_ir1 = true,
+            "-dump-ir2" => opts.dump_ir2 = true,
+            "-fPIC" => opts.pic = true,
+            "-finline" => opts.inline = true,
+            "-O0" => opts.opt_level = 0,
+            "-O" | "-O1" => opts.opt_level = 1,
+            _ if arg.starts_with("-fbracket-depth=") => {
+                let depth = arg["-fbracket-depth=".len()..].parse();
+                opts.max_depth = Some(depth.unwrap_or_else(|_| usage()));
+            }
+            _ if arg.starts_with("-fno-") => {
+                let name = &arg["-fno-".len()..];
+                if !PASSES.iter().any(|(pass, _)| *pass == name) {
+                    usage();
+                }
+                opts.disabled_passes.push(name.to_string());
+            }
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => usage(),
         }
-        path = args[1].clone();
-    }
-
-    // Tokenize and parse.
-    let tokens = tokenize(path, &mut Preprocessor::new());
-
-    let nodes = parse(&tokens);
-    let (nodes, globals) = sema(nodes);
-    let mut fns = gen_ir(nodes);
-
-    if dump_ir1 {
-        dump_ir(&fns);
     }
+    let path = path.unwrap_or_else(|| usage());
+    let src = read_file(&path);
 
-    alloc_regs(&mut fns);
-
-    if dump_ir2 {
-        dump_ir(&fns);
+    match mode {
+        Mode::Tokens => dump_tokens(&check(tokens(&path, &src))),
+        Mode::Ast => print!("{}", ast_text(&check(ast(&path, &src, &opts)))),
+        Mode::Ir => print!("{}", ir_text(&check(ir(&path, &src, &opts)))),
+        Mode::Cfg => dump_cfg(&check(ir(&path, &src, &opts))),
+        Mode::Symbols => dump_symbols(&check(symbols(&path, &src, &opts))),
+        Mode::Asm => print!("{}", check(compile_with(&path, &src, &opts))),
     }
-
-    gen_x86(globals, fns);
 }
@@ -1,8 +1,11 @@
 use crate::token::Token;
+use crate::typing::{arith_conv, binop_type, decay};
 use crate::util::roundup;
 use crate::{Ctype, Scope, TokenType, Type};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::mem;
 
 // Quoted from 9cc
 // > This is a recursive-descendent parser which constructs abstract
@@ -23,14 +26,25 @@ int main() {     ; +-+                        int   []         2
 }                ; +-+                  return        []      +->primary
                                                                  0
 */
-pub fn parse(tokens: &Vec<Token>) -> Vec<Node> {
+// How deeply expressions and statements may nest before the parser
+// gives up, rather than running out of stack. Each operator of a chain
+// such as `1+1+...+1` nests its left operand one level deeper. The
+// same as clang's -fbracket-depth. It fits the 8MB stack of a main
+// thread; a thread with a smaller stack needs a lower limit.
+pub const MAX_DEPTH: usize = 256;
+
+// `tokens` must end with an Eof token, as returned by tokenize().
+pub fn parse(tokens: &[Token]) -> Vec<Node> {
+    parse_with(tokens, MAX_DEPTH)
+}
+
+pub fn parse_with(tokens: &[Token], max_depth: usize) -> Vec<Node> {
     let mut parser = Parser::new(tokens);
+    parser.max_depth = max_depth;
 
     let mut v = vec![];
-    while tokens.len() != parser.pos {
-        if let Some(node) = parser.toplevel() {
-            v.push(node);
-        }
+    while parser.peek().ty != TokenType::Eof {
+        v.extend(parser.toplevel());
     }
     v
 }
@@ -39,6 +53,11 @@ pub fn parse(tokens: &Vec<Token>) -> Vec<Node> {
 struct Env {
     tags: HashMap<String, Type>,
     typedefs: HashMap<String, Type>,
+    enums: HashMap<String, i32>,
+    vars: HashMap<String, Type>, // Variables and functions, for typeof
+    cleanups: Vec<Node>,         // Calls to run at the end of the scope
+    ret_ty: Option<Type>,        // Set in the outermost scope a return leaves
+    loops: usize,                // Loops whose body is being read in this scope
     next: Option<Box<Env>>,
 }
 
@@ -48,6 +67,11 @@ impl Env {
             next,
             tags: HashMap::new(),
             typedefs: HashMap::new(),
+            enums: HashMap::new(),
+            vars: HashMap::new(),
+            cleanups: vec![],
+            ret_ty: None,
+            loops: 0,
         }
     }
 }
@@ -58,9 +82,13 @@ macro_rules! new_expr(
     )
 );
 
+// An initializer in a global that is not an integer constant, such as
+// `&x` or `sizeof x`, as (offset in the global, type, expr).
+pub type InitPart = (usize, Type, Node);
+
 #[derive(Debug, Clone)]
 pub enum NodeType {
-    Num(i32),                                        // Number literal
+    Num(i64),                                        // Number literal
     Str(String, usize),                              // String literal, (data, len)
     Ident(String),                                   // Identifier
     Decl(String),                                    // declaration
@@ -72,23 +100,32 @@ pub enum NodeType {
     Ternary(Box<Node>, Box<Node>, Box<Node>),        // cond ? then : els
     For(Box<Node>, Box<Node>, Box<Node>, Box<Node>), // "for" ( init; cond; inc ) body
     Break,
-    DoWhile(Box<Node>, Box<Node>), // do { body } while(cond)
-    Addr(Box<Node>),               // address-of operator("&"), expr
-    Deref(Box<Node>),              // pointer dereference ("*"), expr
+    Continue,
+    Goto(String),                                          // "goto" label
+    GotoPtr(Box<Node>),                                    // "goto" *expr (GNU extn.)
+    Label(String, Box<Node>),                              // label: stmt
+    LabelAddr(String),                                     // &&label (GNU extn.)
+    DoWhile(Box<Node>, Box<Node>),                         // do { body } while(cond)
+    Addr(Box<Node>),                                       // address-of operator("&"), expr
+    Deref(Box<Node>),                                      // pointer dereference ("*"), expr
     Dot(Box<Node>, String, usize), // Struct member accessm, (expr, name, offset)
     Exclamation(Box<Node>),        // !, expr
     Neg(Box<Node>),                // -
+    Cast(Box<Node>),               // Conversion to the node's type
     PostInc(Box<Node>),            // post ++
     PostDec(Box<Node>),            // post --
     Return(Box<Node>),             // "return", stmt
     Sizeof(Box<Node>),             // "sizeof", expr
     Alignof(Box<Node>),            // "_Alignof", expr
     Call(String, Vec<Node>),       // Function call(name, args)
-    Func(String, Vec<Node>, Box<Node>, usize), // Function definition(name, args, body, stacksize)
-    CompStmt(Vec<Node>),           // Compound statement
-    VecStmt(Vec<Node>),            // For the purpose of assign a value when initializing an array.
-    ExprStmt(Box<Node>),           // Expression statement
-    StmtExpr(Box<Node>),           // Statement expression (GNU extn.)
+    CallPtr(Box<Node>, Vec<Node>), // Call through a function pointer(callee, args)
+    Func(String, Vec<Node>, Box<Node>, usize, bool, bool), // Function definition(name, args, body, stacksize, is_inline, is_static)
+    CompStmt(Vec<Node>),                                   // Compound statement
+    VecStmt(Vec<Node>), // For the purpose of assign a value when initializing an array.
+    ExprStmt(Box<Node>), // Expression statement
+    StmtExpr(Box<Node>), // Statement expression (GNU extn.)
+    CompLit(Box<Node>, Box<Node>), // Compound literal, (init, var)
+    GlobalInit(Vec<InitPart>), // Initializers of a global left to sema
     Null,
 }
 
@@ -96,6 +133,9 @@ pub enum NodeType {
 pub struct Node {
     pub op: NodeType,  // Node type
     pub ty: Box<Type>, // C type
+
+    // For warnings
+    pub token: Option<Token>,
 }
 
 impl Node {
@@ -103,18 +143,22 @@ impl Node {
         Self {
             op,
             ty: Box::new(Type::default()),
+            token: None,
         }
     }
 
     pub fn new_int(val: i32) -> Self {
-        Node::new(NodeType::Num(val))
+        Node::new(NodeType::Num(val.into()))
     }
 
-    pub fn scale_ptr(node: Box<Node>, ty: &Type) -> Self {
+    pub fn scale_ptr(node: Node, ty: &Type) -> Self {
         match ty.ty {
-            Ctype::Ptr(ref ptr_to) => {
-                Node::new_binop(TokenType::Mul, *node, Node::new_int(ptr_to.size as i32))
-            }
+            // void * is scaled by 1, as in GCC.
+            Ctype::Ptr(ref ptr_to) => Node::new_binop(
+                TokenType::Mul,
+                node,
+                Node::new_int(ptr_to.size.max(1) as i32),
+            ),
             _ => panic!("expect ptr type"),
         }
     }
@@ -124,14 +168,11 @@ impl Node {
     }
 
     pub fn new_num(val: i32) -> Self {
-        Node::new(NodeType::Num(val))
+        Node::new(NodeType::Num(val.into()))
     }
 
     pub fn is_null(&self) -> bool {
-        match self.op {
-            NodeType::Null => true,
-            _ => false,
-        }
+        matches!(self.op, NodeType::Null)
     }
 }
 
@@ -141,6 +182,7 @@ impl Type {
             ty,
             size,
             align: size,
+            ..Default::default()
         }
     }
 
@@ -152,36 +194,303 @@ impl Type {
         Type::new(Ctype::Char, 1)
     }
 
+    pub fn bool_ty() -> Self {
+        Type::new(Ctype::Bool, 1)
+    }
+
     pub fn int_ty() -> Self {
         Type::new(Ctype::Int, 4)
     }
 
+    // A struct that is declared but not defined, as in `struct tag *p;`,
+    // has no alignment. Even an empty struct is aligned to 1.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self.ty, Ctype::Struct(_)) && self.align == 0
+    }
+
+    // The type of sizeof and _Alignof, unsigned long.
+    pub fn size_t() -> Self {
+        let mut ty = Type::new(Ctype::Int, 8);
+        ty.is_unsigned = true;
+        ty
+    }
+
+    // The type of a pointer difference, long.
+    pub fn ptrdiff_t() -> Self {
+        Type::new(Ctype::Int, 8)
+    }
+
     pub fn ptr_to(base: Box<Type>) -> Self {
         Type::new(Ctype::Ptr(base), 8)
     }
 
+    // An array of const elements is itself const.
     pub fn ary_of(base: Box<Type>, len: usize) -> Self {
         let align = base.align;
         let size = base.size * len;
+        let is_const = base.is_const;
         let mut ty = Type::new(Ctype::Ary(base, len), size);
         ty.align = align;
+        ty.is_const = is_const;
         ty
     }
+
+    // The type and offset of the member `name` of a struct. A member of a
+    // const or volatile struct is too.
+    pub fn member(&self, name: &str) -> Option<(Type, usize)> {
+        let members = match self.ty {
+            Ctype::Struct(ref members) => members,
+            _ => return None,
+        };
+        members.iter().find_map(|m| match m.op {
+            NodeType::Vardef(ref m_name, _, Scope::Local(offset)) if m_name == name => {
+                let mut ty = (*m.ty).clone();
+                ty.is_const |= self.is_const;
+                ty.is_volatile |= self.is_volatile;
+                Some((ty, offset))
+            }
+            _ => None,
+        })
+    }
+}
+
+// A parenthesized declarator is read before the type it applies to, so it is
+// built around a placeholder. In `int (*a[3])[10]` the inner part is an array
+// of three pointers to the placeholder, which then becomes `int [10]`.
+// Array sizes are recomputed since the element type has changed.
+fn replace_placeholder(ty: Type, base: Type) -> Type {
+    match ty.ty {
+        Ctype::Ptr(ptr_to) => Type {
+            is_const: ty.is_const,
+            is_volatile: ty.is_volatile,
+            ..Type::ptr_to(Box::new(replace_placeholder(*ptr_to, base)))
+        },
+        Ctype::Ary(ary_of, len) => Type::ary_of(Box::new(replace_placeholder(*ary_of, base)), len),
+        _ => base,
+    }
+}
+
+// Resolves a combination of `int`, `char`, `long`, `signed` and
+// `unsigned`, such as `long unsigned long`. A long is 8 bytes, as is a
+// long long, and a plain char is signed.
+fn int_type(t: &Token, keywords: &[TokenType]) -> Type {
+    let count = |ty: TokenType| keywords.iter().filter(|k| **k == ty).count();
+    let (int, char, long) = (
+        count(TokenType::Int),
+        count(TokenType::Char),
+        count(TokenType::Long),
+    );
+    let (signed, unsigned) = (count(TokenType::Signed), count(TokenType::Unsigned));
+    if int > 1 || char > 1 || long > 2 || signed + unsigned > 1 || char > 0 && int + long > 0 {
+        t.bad_token("invalid combination of type specifiers");
+    }
+
+    let mut ty = if char > 0 {
+        Type::char_ty()
+    } else if long > 0 {
+        Type::new(Ctype::Int, 8)
+    } else {
+        Type::int_ty()
+    };
+    ty.is_unsigned = unsigned > 0;
+    ty
+}
+
+// The little-endian bytes of a constant of type `ty`.
+pub fn int_data(val: i64, ty: &Type) -> String {
+    let val = match ty.ty {
+        Ctype::Bool => (val != 0) as i64,
+        _ => val,
+    };
+    (0..ty.size)
+        .map(|i| (val >> (i * 8)) as u8 as char)
+        .collect()
+}
+
+// Evaluates a constant expression such as an enumerator value.
+pub fn eval(node: &Node) -> Option<i64> {
+    eval_typed(node).map(|(val, _)| val)
+}
+
+// The type of an integer constant as (size, is_unsigned). Operands
+// narrower than int are promoted to int.
+type ConstType = (usize, bool);
+
+const INT: ConstType = (4, false);
+
+fn const_type(ty: &Type) -> ConstType {
+    if ty.size < 4 {
+        INT
+    } else {
+        (ty.size, ty.is_unsigned)
+    }
+}
+
+// Converts `val` to `ty`, wrapping it at 32 bits for int and unsigned.
+fn wrap(val: i64, ty: ConstType) -> i64 {
+    match ty {
+        (4, true) => i64::from(val as u32),
+        (4, false) => i64::from(val as i32),
+        _ => val,
+    }
+}
+
+// Evaluates a constant expression along with its type, so that each
+// operation wraps at the width it has at runtime: `INT_MAX + 1` is
+// INT_MIN, but `INT_MAX + 1L` is not.
+fn eval_typed(node: &Node) -> Option<(i64, ConstType)> {
+    use self::TokenType::*;
+    match node.op {
+        NodeType::Num(val) => {
+            let ty = const_type(&node.ty);
+            Some((wrap(val, ty), ty))
+        }
+        NodeType::Neg(ref expr) => {
+            let (val, ty) = eval_typed(expr)?;
+            Some((wrap(val.wrapping_neg(), ty), ty))
+        }
+        // A conversion that sema inserted, which never narrows.
+        NodeType::Cast(ref expr) if std::matches!(node.ty.ty, Ctype::Int | Ctype::Char) => {
+            let (val, _) = eval_typed(expr)?;
+            let ty = const_type(&node.ty);
+            Some((wrap(val, ty), ty))
+        }
+        // Only the branch that is taken has to be a constant. `a ?: b`
+        // has no middle operand.
+        NodeType::Ternary(ref cond, ref then, ref els) => match eval_typed(cond)? {
+            (0, _) => eval_typed(els),
+            val if then.is_null() => Some(val),
+            _ => eval_typed(then),
+        },
+        NodeType::BinOp(ref op, ref lhs, ref rhs) => {
+            let ((lhs, lty), (rhs, rty)) = (eval_typed(lhs)?, eval_typed(rhs)?);
+            // The usual arithmetic conversions, except that a shift has
+            // the type of its left operand.
+            let ty = match op {
+                SHL | SHR => lty,
+                _ if lty.0 > rty.0 || (lty.0 == rty.0 && lty.1) => lty,
+                _ => rty,
+            };
+            let (lhs, rhs) = match op {
+                SHL | SHR => (lhs, rhs),
+                _ => (wrap(lhs, ty), wrap(rhs, ty)),
+            };
+            // Values of the other types are exact in an i64.
+            let is_ulong = ty == (8, true);
+            let val = match op {
+                // Shifting by the width of the type or more is undefined.
+                SHL | SHR if rhs < 0 || rhs >= 8 * ty.0 as i64 => return None,
+                Plus => lhs.wrapping_add(rhs),
+                Minus => lhs.wrapping_sub(rhs),
+                Mul => lhs.wrapping_mul(rhs),
+                Div if rhs != 0 && is_ulong => (lhs as u64 / rhs as u64) as i64,
+                Div if rhs != 0 => lhs.wrapping_div(rhs),
+                Mod if rhs != 0 && is_ulong => (lhs as u64 % rhs as u64) as i64,
+                Mod if rhs != 0 => lhs.wrapping_rem(rhs),
+                SHL => lhs.wrapping_shl(rhs as u32),
+                SHR if is_ulong => (lhs as u64).wrapping_shr(rhs as u32) as i64,
+                SHR => lhs.wrapping_shr(rhs as u32),
+                And => lhs & rhs,
+                VerticalBar => lhs | rhs,
+                Hat => lhs ^ rhs,
+                EQ => return Some(((lhs == rhs) as i64, INT)),
+                NE => return Some(((lhs != rhs) as i64, INT)),
+                LE if is_ulong => return Some(((lhs as u64 <= rhs as u64) as i64, INT)),
+                LE => return Some(((lhs <= rhs) as i64, INT)),
+                LeftAngleBracket if is_ulong => {
+                    return Some((((lhs as u64) < rhs as u64) as i64, INT))
+                }
+                LeftAngleBracket => return Some(((lhs < rhs) as i64, INT)),
+                _ => return None,
+            };
+            Some((wrap(val, ty), ty))
+        }
+        _ => None,
+    }
+}
+
+// Evaluates an integer constant expression, in which C does not allow
+// a comma operator even though `(1, 2)` would have a constant value.
+// As with eval(), only the branch of `?:` that is taken is checked.
+fn const_eval(t: &Token, node: &Node) -> Option<i64> {
+    fn has_comma(node: &Node) -> bool {
+        match node.op {
+            NodeType::BinOp(TokenType::Comma, ..) => true,
+            NodeType::BinOp(_, ref lhs, ref rhs) => has_comma(lhs) || has_comma(rhs),
+            NodeType::Neg(ref expr) => has_comma(expr),
+            NodeType::Ternary(ref cond, ref then, ref els) => {
+                has_comma(cond)
+                    || match eval(cond) {
+                        Some(0) => has_comma(els),
+                        Some(_) => has_comma(then),
+                        None => false,
+                    }
+            }
+            _ => false,
+        }
+    }
+    if has_comma(node) {
+        t.bad_token("comma operator in constant expression");
+    }
+    eval(node)
 }
 
 pub struct Parser<'a> {
-    tokens: &'a Vec<Token>,
+    tokens: &'a [Token],
     pos: usize,
     env: Env,
+    ncomplit: usize,
+    depth: usize,
+    max_depth: usize,
+    // Functions declared static, which stay static at their definition.
+    statics: HashSet<String>,
+    // Whether the last array declarator omitted its length, as `char d[]`
+    // does but `char d[0]` does not.
+    unsized_array: bool,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a Vec<Token>) -> Self {
+    pub fn new(tokens: &'a [Token]) -> Self {
         Parser {
             tokens,
             pos: 0,
             env: Env::new(None),
+            ncomplit: 0,
+            depth: 0,
+            max_depth: MAX_DEPTH,
+            statics: HashSet::new(),
+            unsized_array: false,
+        }
+    }
+
+    // Runs f one level deeper, so that pathological input such as
+    // thousands of parentheses is an error rather than a stack overflow.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        if self.depth == self.max_depth {
+            self.peek().bad_token("maximum nesting depth exceeded");
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    // Reads the right operand of an operator just consumed in a chain
+    // such as `1+1+...+1`. The loop reading the chain does not recurse,
+    // but the tree it builds is as deep as the chain is long, and the
+    // later passes recurse on it, so each operator counts as a level.
+    // The caller gives the levels back when the chain ends.
+    fn operand(&mut self, f: impl FnOnce(&mut Self) -> Node) -> Node {
+        self.deeper();
+        f(self)
+    }
+
+    // Counts one more level of a chain, as operand() does.
+    fn deeper(&mut self) {
+        if self.depth == self.max_depth {
+            self.tokens[self.pos - 1].bad_token("maximum nesting depth exceeded");
         }
+        self.depth += 1;
     }
 
     fn find_tag(&self, name: &str) -> Option<Type> {
@@ -214,8 +523,220 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn find_var(&self, name: &str) -> Option<&Type> {
+        let mut env = Some(&self.env);
+        while let Some(e) = env {
+            if let Some(ty) = e.vars.get(name) {
+                return Some(ty);
+            }
+            env = e.next.as_deref();
+        }
+        None
+    }
+
+    // Returns the type of `expr` without evaluating it, for `typeof`.
+    // sema has not run yet, so this types the expression by the same
+    // rules.
+    fn type_of(&self, expr: &Node) -> Type {
+        use self::NodeType::*;
+        let bad = |msg: &str| -> ! {
+            let t = expr.token.as_ref().unwrap_or_else(|| self.peek());
+            t.bad_token(msg)
+        };
+        let returning = |ty: &Type| match decay(ty).ty {
+            Ctype::Ptr(ref ptr_to) => match ptr_to.ty {
+                Ctype::Func(ref returning, ..) => (**returning).clone(),
+                _ => bad("called object is not a function"),
+            },
+            _ => bad("called object is not a function"),
+        };
+
+        let ty = match expr.op {
+            Ident(ref name) => self
+                .find_var(name)
+                .cloned()
+                .unwrap_or_else(|| bad(&format!("undeclared identifier: {}", name))),
+            Call(ref name, _) => match self.find_var(name) {
+                Some(ty) => returning(ty),
+                None => bad(&format!("undefined function: {}", name)),
+            },
+            CallPtr(ref callee, _) => returning(&self.type_of(callee)),
+            Dot(ref expr, ref name, _) => self
+                .type_of(expr)
+                .member(name)
+                .map(|(ty, _)| ty)
+                .unwrap_or_else(|| bad(&format!("member missing: {}", name))),
+            Deref(ref expr) => match decay(&self.type_of(expr)).ty {
+                Ctype::Ptr(ptr_to) => *ptr_to,
+                _ => bad("operand must be a pointer"),
+            },
+            Addr(ref expr) => Type::ptr_to(Box::new(self.type_of(expr))),
+            BinOp(ref op, ref lhs, ref rhs) => {
+                *binop_type(op, &self.type_of(lhs), &self.type_of(rhs))
+            }
+            // As in sema, a null pointer constant takes the type of a
+            // pointer in the other branch.
+            Ternary(ref cond, ref then, ref els) => {
+                let els = decay(&self.type_of(els));
+                match (&then.op, &els.ty) {
+                    (Null, _) => *arith_conv(&decay(&self.type_of(cond)), &els),
+                    (Num(0), Ctype::Ptr(_)) => els,
+                    _ => *arith_conv(&decay(&self.type_of(then)), &els),
+                }
+            }
+            Neg(ref expr) => decay(&self.type_of(expr)),
+            PostInc(ref expr) | PostDec(ref expr) => self.type_of(expr),
+            CompLit(_, ref var) => self.type_of(var),
+            Exclamation(_) | StmtExpr(_) => Type::int_ty(),
+            Sizeof(_) | Alignof(_) => Type::size_t(),
+            _ => (*expr.ty).clone(),
+        };
+        Type {
+            bitfield: None,
+            cleanup: None,
+            ..ty
+        }
+    }
+
+    fn declare(&mut self, node: &Node) {
+        if let NodeType::Vardef(ref name, ..) | NodeType::Decl(ref name) = node.op {
+            self.env.vars.insert(name.clone(), (*node.ty).clone());
+        }
+    }
+
+    // Registers the `fn(&var)` call of a local variable declared with
+    // `__attribute__((cleanup(fn)))`. It runs when control reaches the end
+    // of the block or leaves it by return, break or continue. A goto may
+    // not be used in its scope.
+    fn add_cleanup(&mut self, node: &Node) {
+        if let (NodeType::Vardef(ref name, ..), Some(ref func)) = (&node.op, &node.ty.cleanup) {
+            let var = new_expr!(NodeType::Addr, Node::new(NodeType::Ident(name.clone())));
+            let call = Node::new(NodeType::Call(func.clone(), vec![var]));
+            self.env.cleanups.push(new_expr!(NodeType::ExprStmt, call));
+        }
+    }
+
+    // A return leaves every scope up to the function body or statement
+    // expression, so their cleanups run first, innermost first. The value
+    // is computed before them, as it may read the variables they clean up.
+    fn return_stmt(&self, expr: Node) -> Node {
+        let mut cleanups = vec![];
+        let mut ret_ty = None;
+        let mut env = Some(&self.env);
+        while let Some(e) = env {
+            cleanups.extend(e.cleanups.iter().rev().cloned());
+            if e.ret_ty.is_some() {
+                ret_ty = e.ret_ty.clone();
+                break;
+            }
+            env = e.next.as_deref();
+        }
+        if cleanups.is_empty() {
+            return new_expr!(NodeType::Return, expr);
+        }
+
+        let ret_ty = ret_ty.unwrap();
+        let mut stmts = vec![];
+        let ret = if let Ctype::Void = ret_ty.ty {
+            stmts.push(new_expr!(NodeType::ExprStmt, expr));
+            Node::new(NodeType::Num(0))
+        } else {
+            let mut var = Node::new(NodeType::Vardef(
+                ".ret".into(),
+                Some(Box::new(expr)),
+                Scope::Local(0),
+            ));
+            var.ty = Box::new(ret_ty);
+            stmts.push(var);
+            Node::new(NodeType::Ident(".ret".into()))
+        };
+        stmts.extend(cleanups);
+        stmts.push(new_expr!(NodeType::Return, ret));
+        Node::new(NodeType::CompStmt(stmts))
+    }
+
+    // A break or continue leaves the scopes opened inside the innermost
+    // loop body, so their cleanups run first, innermost first.
+    fn loop_exit(&self, node: Node) -> Node {
+        let mut stmts = vec![];
+        let mut env = Some(&self.env);
+        while let Some(e) = env {
+            if e.loops > 0 {
+                break;
+            }
+            stmts.extend(e.cleanups.iter().rev().cloned());
+            env = e.next.as_deref();
+        }
+        if stmts.is_empty() {
+            return node;
+        }
+        stmts.push(node);
+        Node::new(NodeType::CompStmt(stmts))
+    }
+
+    fn has_cleanups(&self) -> bool {
+        let mut env = Some(&self.env);
+        while let Some(e) = env {
+            if !e.cleanups.is_empty() {
+                return true;
+            }
+            if e.ret_ty.is_some() {
+                return false;
+            }
+            env = e.next.as_deref();
+        }
+        false
+    }
+
+    fn loop_body(&mut self) -> Node {
+        self.env.loops += 1;
+        let body = self.stmt();
+        self.env.loops -= 1;
+        body
+    }
+
+    fn in_new_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let env = mem::replace(&mut self.env, Env::new(None));
+        self.env = Env::new(Some(Box::new(env)));
+        let ret = f(self);
+        self.env = *self.env.next.take().unwrap();
+        ret
+    }
+
+    // Returns the value of an enumerator, unless a variable of the same
+    // name in an inner scope hides it.
+    fn find_enum(&self, name: &str) -> Option<i32> {
+        let mut env = Some(&self.env);
+        while let Some(e) = env {
+            if e.vars.contains_key(name) {
+                return None;
+            }
+            if let Some(val) = e.enums.get(name) {
+                return Some(*val);
+            }
+            env = e.next.as_deref();
+        }
+        None
+    }
+
+    // Returns the current token. The parser never moves past the Eof
+    // token, so this is always in bounds.
+    fn peek(&self) -> &'a Token {
+        &self.tokens[self.pos]
+    }
+
+    // Returns the current token and moves past it.
+    fn next(&mut self) -> &'a Token {
+        let t = self.peek();
+        if t.ty == TokenType::Eof {
+            t.bad_token("unexpected end of file");
+        }
+        self.pos += 1;
+        t
+    }
+
     fn expect(&mut self, ty: TokenType) {
-        let t = &self.tokens[self.pos];
+        let t = self.peek();
         if t.ty != ty {
             t.bad_token(&format!("{:?} expected", ty));
         }
@@ -223,8 +744,7 @@ impl<'a> Parser<'a> {
     }
 
     fn consume(&mut self, ty: TokenType) -> bool {
-        let t = &self.tokens[self.pos];
-        if t.ty != ty {
+        if self.peek().ty != ty {
             return false;
         }
         self.pos += 1;
@@ -236,27 +756,52 @@ impl<'a> Parser<'a> {
         if let TokenType::Ident(ref name) = t.ty {
             return self.find_typedef(name).is_some();
         }
-        t.ty == Int || t.ty == Char || t.ty == Void || t.ty == Struct
+        t.ty == Int
+            || t.ty == Char
+            || t.ty == Bool
+            || t.ty == Void
+            || t.ty == Struct
+            || t.ty == Enum
+            || t.ty == Unsigned
+            || t.ty == Signed
+            || t.ty == Long
+            || t.ty == Register
+            || t.ty == Auto
+            || t.ty == Const
+            || t.ty == Volatile
+            || t.ty == Typeof
     }
 
+    // Bit-fields are packed into storage units of their declared type.
+    // A bit-field that would straddle a unit boundary starts a new unit.
     fn set_offset(members: &mut Vec<Node>) -> (usize, usize) {
-        let mut off = 0;
-        let mut align = 0;
+        let mut bits = 0;
+        let mut align = 1;
         for node in members {
             if let NodeType::Vardef(_, _, Scope::Local(offset)) = &mut node.op {
-                let t = &node.ty;
-                off = roundup(off, t.align);
-                *offset = off;
-                off += t.size;
+                let t = &mut node.ty;
+                let unit = t.size * 8;
+                if let Some((ref mut bit_offset, width)) = t.bitfield {
+                    if width == 0 || bits / unit != (bits + width - 1) / unit {
+                        bits = roundup(bits, unit);
+                    }
+                    *offset = bits / unit * t.size;
+                    *bit_offset = bits % unit;
+                    bits += width;
+                } else {
+                    bits = roundup(bits, t.align * 8);
+                    *offset = bits / 8;
+                    bits += t.size * 8;
+                }
 
                 if align < t.align {
                     align = t.align;
                 }
             } else {
-                panic!();
+                unreachable!();
             }
         }
-        (off, align)
+        (roundup(bits, 8) / 8, align)
     }
 
     fn add_member(ty: &mut Type, mut members: Vec<Node>) {
@@ -265,62 +810,244 @@ impl<'a> Parser<'a> {
             *members2 = members;
         }
         ty.size = roundup(off, align);
+        ty.align = align;
     }
 
+    // Specifiers and qualifiers may come in any order, as in
+    // `unsigned const long int`. The arithmetic keywords are collected
+    // and resolved once a token that is not a specifier is reached.
     fn decl_specifiers(&mut self) -> Option<Type> {
-        let t = &self.tokens[self.pos];
-        self.pos += 1;
+        use self::TokenType::*;
+        let start = self.peek();
+        let mut quals = Type::default();
+        let mut base = None;
+        let mut keywords = vec![];
+        loop {
+            self.type_qualifiers(&mut quals);
+            let t = self.peek();
+            let seen = base.is_some() || !keywords.is_empty();
+            if std::matches!(t.ty, Int | Char | Long | Signed | Unsigned) {
+                if base.is_some() {
+                    t.bad_token("invalid combination of type specifiers");
+                }
+                keywords.push(t.ty.clone());
+                self.pos += 1;
+            } else if std::matches!(t.ty, Void | Bool | Struct | Enum | Typeof) {
+                if seen {
+                    t.bad_token("invalid combination of type specifiers");
+                }
+                base = self.type_specifiers();
+            } else if !seen && self.is_typename(t) {
+                // A typedef name; after a type, the same name would be
+                // the declarator.
+                base = self.type_specifiers();
+            } else {
+                break;
+            }
+        }
+
+        let mut ty = match base {
+            Some(ty) => ty,
+            None if keywords.is_empty() => return None,
+            None => int_type(start, &keywords),
+        };
+        ty.is_const |= quals.is_const;
+        ty.is_volatile |= quals.is_volatile;
+        Some(ty)
+    }
+
+    // Reads `const` and `volatile` into `ty`.
+    fn type_qualifiers(&mut self, ty: &mut Type) {
+        // `register` and `auto` are storage-class hints we can ignore, and
+        // `__extension__` only silences GCC's pedantic warnings.
+        loop {
+            if self.consume(TokenType::Const) {
+                ty.is_const = true;
+            } else if self.consume(TokenType::Volatile) {
+                ty.is_volatile = true;
+            } else if !self.consume(TokenType::Register)
+                && !self.consume(TokenType::Auto)
+                && !self.consume(TokenType::Extension)
+            {
+                return;
+            }
+        }
+    }
+
+    fn type_specifiers(&mut self) -> Option<Type> {
+        let t = self.next();
         match t.ty {
             TokenType::Ident(ref name) => {
                 if let Some(ty) = self.find_typedef(name) {
-                    return Some(ty.clone());
+                    Some(ty.clone())
                 } else {
                     self.pos -= 1;
-                    return None;
+                    None
                 }
             }
-            TokenType::Int => Some(Type::int_ty()),
-            TokenType::Char => Some(Type::char_ty()),
+            TokenType::Bool => Some(Type::bool_ty()),
             TokenType::Void => Some(Type::void_ty()),
+            TokenType::Typeof => {
+                // The operand is only typed, never evaluated.
+                self.expect(TokenType::LeftParen);
+                let ty = if self.is_typename(self.peek()) {
+                    self.type_name()
+                } else {
+                    let expr = self.expr();
+                    self.type_of(&expr)
+                };
+                self.expect(TokenType::RightParen);
+                Some(ty)
+            }
             TokenType::Struct => {
                 let mut tag_may: Option<String> = None;
-                let t = &self.tokens[self.pos];
+                let t = self.peek();
                 if let TokenType::Ident(ref name) = t.ty {
                     self.pos += 1;
                     tag_may = Some(name.clone())
                 }
 
+                // `struct tag` without a body refers to a previously defined type.
+                if !self.consume(TokenType::LeftBrace) {
+                    let ty_may = tag_may.and_then(|tag| self.find_tag(&tag));
+                    return Some(ty_may.unwrap_or(Type::new(Ctype::Struct(vec![]), 0)));
+                }
+
+                // An empty struct (`struct tag {}`) is a GNU extension with size 0.
                 let mut members = vec![];
-                if self.consume(TokenType::LeftBrace) {
-                    while !self.consume(TokenType::RightBrace) {
-                        members.push(self.declaration())
+                let mut flexible: Option<&Token> = None;
+                while !self.consume(TokenType::RightBrace) {
+                    let t = self.peek();
+                    if let Some(t) = flexible {
+                        t.bad_token("flexible array member not at end of struct");
                     }
-                }
 
-                let mut ty_may: Option<Type> = None;
-                if let Some(ref tag) = tag_may {
-                    if members.is_empty() {
-                        ty_may = self.find_tag(&tag);
+                    let member = self.member_declaration();
+                    if !matches!(member.op, NodeType::Vardef(_, None, _)) {
+                        t.bad_token("bad struct member");
                     }
-                }
-                let mut ty = ty_may.unwrap_or(Type::new(Ctype::Struct(vec![]), 10));
 
-                if !members.is_empty() {
-                    Self::add_member(&mut ty, members);
-                    if let Some(tag) = tag_may {
-                        self.env.tags.insert(tag, ty.clone());
+                    // A flexible array member (`char data[];`) takes no space
+                    // and lets `p->data[i]` index past the end of the struct.
+                    if matches!(member.ty.ty, Ctype::Ary(_, 0)) && self.unsized_array {
+                        flexible = Some(t);
                     }
+                    members.push(member);
                 }
-                Some(ty.clone())
+
+                let mut ty = Type::new(Ctype::Struct(vec![]), 0);
+                Self::add_member(&mut ty, members);
+                self.attributes(&mut ty);
+                ty.size = roundup(ty.size, ty.align);
+                if let Some(tag) = tag_may {
+                    self.env.tags.insert(tag, ty.clone());
+                }
+                Some(ty)
+            }
+            TokenType::Enum => {
+                if let TokenType::Ident(_) = self.peek().ty {
+                    self.pos += 1;
+                }
+
+                if self.consume(TokenType::LeftBrace) {
+                    self.enumerators();
+                }
+                Some(Type::int_ty())
             }
             _ => t.bad_token("typename expected"),
         }
     }
 
+    // Reads `A, B = 5, C }` and registers each enumerator as an int
+    // constant, so its value must fit in an int. A trailing comma before
+    // `}` is allowed.
+    fn enumerators(&mut self) {
+        let mut val = 0;
+        loop {
+            if self.consume(TokenType::RightBrace) {
+                return;
+            }
+
+            let mut t = self.peek();
+            let name = self.ident();
+            if self.consume(TokenType::Equal) {
+                t = self.peek();
+                let node = self.conditional();
+                val = const_eval(t, &node)
+                    .unwrap_or_else(|| t.bad_token("constant expression expected"));
+            }
+            let int = i32::try_from(val)
+                .unwrap_or_else(|_| t.bad_token("enumerator value is out of range of int"));
+            self.env.enums.insert(name, int);
+            val += 1;
+
+            if !self.consume(TokenType::Comma) {
+                self.expect(TokenType::RightBrace);
+                return;
+            }
+        }
+    }
+
+    // Reads GNU `__attribute__((...))` lists. `aligned(n)` raises the
+    // alignment of the given type; unknown attributes are skipped.
+    fn attributes(&mut self, ty: &mut Type) {
+        while self.consume(TokenType::Attribute) {
+            self.expect(TokenType::LeftParen);
+            self.expect(TokenType::LeftParen);
+            while !self.consume(TokenType::RightParen) {
+                let t = self.next();
+                let name = match t.ty {
+                    TokenType::Ident(ref name) => name.clone(),
+                    _ => t.tokstr(),
+                };
+
+                if name == "aligned" || name == "__aligned__" {
+                    let mut align = 16;
+                    if self.consume(TokenType::LeftParen) {
+                        let t = self.peek();
+                        align = const_eval(t, &self.conditional())
+                            .unwrap_or_else(|| t.bad_token("constant expression expected"));
+                        if align <= 0 || align & (align - 1) != 0 {
+                            t.bad_token("requested alignment is not a power of 2");
+                        }
+                        self.expect(TokenType::RightParen);
+                    }
+                    ty.align = ty.align.max(align as usize);
+                } else if name == "noreturn" || name == "__noreturn__" {
+                    ty.is_noreturn = true;
+                } else if name == "cleanup" || name == "__cleanup__" {
+                    self.expect(TokenType::LeftParen);
+                    ty.cleanup = Some(self.ident());
+                    self.expect(TokenType::RightParen);
+                } else if self.consume(TokenType::LeftParen) {
+                    self.skip_parens();
+                }
+
+                if !self.consume(TokenType::Comma) {
+                    self.expect(TokenType::RightParen);
+                    break;
+                }
+            }
+            self.expect(TokenType::RightParen);
+        }
+    }
+
+    // Skips tokens up to the `)` matching an already consumed `(`.
+    fn skip_parens(&mut self) {
+        let mut level = 1;
+        while level > 0 {
+            let t = self.next();
+            if t.ty == TokenType::LeftParen {
+                level += 1;
+            } else if t.ty == TokenType::RightParen {
+                level -= 1;
+            }
+        }
+    }
+
     fn ident(&mut self) -> String {
-        let t = &self.tokens[self.pos];
+        let t = self.next();
         if let TokenType::Ident(ref name) = t.ty {
-            self.pos += 1;
             name.clone()
         } else {
             t.bad_token("variable name expected");
@@ -328,35 +1055,44 @@ impl<'a> Parser<'a> {
     }
 
     fn primary(&mut self) -> Node {
-        let t = &self.tokens[self.pos];
-        self.pos += 1;
+        let t = self.next();
         match t.ty {
-            TokenType::Num(val) => Node::new_num(val),
+            TokenType::Num(val, size, is_unsigned) => {
+                let mut node = Node::new(NodeType::Num(val));
+                node.ty = Box::new(Type::new(Ctype::Int, size));
+                node.ty.is_unsigned = is_unsigned;
+                node
+            }
+            TokenType::FloatNum(_) => t.bad_token("floating-point constants are not supported"),
             TokenType::Str(ref str, len) => {
                 let mut node = Node::new(NodeType::Str(str.clone(), len));
                 node.ty = Box::new(Type::ary_of(Box::new(Type::char_ty()), len));
                 node
             }
             TokenType::Ident(ref name) => {
-                if !self.consume(TokenType::LeftParen) {
-                    return Node::new(NodeType::Ident(name.clone()));
-                }
-
-                let mut args = vec![];
-                if self.consume(TokenType::RightParen) {
-                    return Node::new(NodeType::Call(name.clone(), args));
-                }
-
-                args.push(self.assign());
-                while self.consume(TokenType::Comma) {
-                    args.push(self.assign());
-                }
-                self.expect(TokenType::RightParen);
-                Node::new(NodeType::Call(name.clone(), args))
+                let mut node = if !self.consume(TokenType::LeftParen) {
+                    if let Some(val) = self.find_enum(name) {
+                        return Node::new_num(val);
+                    }
+                    Node::new(NodeType::Ident(name.clone()))
+                } else {
+                    Node::new(NodeType::Call(name.clone(), self.call_args()))
+                };
+                node.token = Some(t.clone());
+                node
             }
             TokenType::LeftParen => {
+                if self.is_typename(self.peek()) {
+                    let ty = self.type_name();
+                    self.expect(TokenType::RightParen);
+                    self.expect(TokenType::LeftBrace);
+                    return self.compound_literal(ty);
+                }
                 if self.consume(TokenType::LeftBrace) {
-                    let stmt = Box::new(self.compound_stmt());
+                    let stmt = Box::new(self.in_new_scope(|p| {
+                        p.env.ret_ty = Some(Type::int_ty());
+                        p.compound_stmt()
+                    }));
                     self.expect(TokenType::RightParen);
                     return Node::new(NodeType::StmtExpr(stmt));
                 }
@@ -368,187 +1104,341 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Reads the arguments of a call after its `(`.
+    fn call_args(&mut self) -> Vec<Node> {
+        let mut args = vec![];
+        if self.consume(TokenType::RightParen) {
+            return args;
+        }
+
+        args.push(self.assign());
+        while self.consume(TokenType::Comma) {
+            args.push(self.assign());
+        }
+        self.expect(TokenType::RightParen);
+        args
+    }
+
     fn postfix(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.primary();
 
         loop {
+            let t = self.peek();
             if self.consume(TokenType::Inc) {
+                self.deeper();
                 lhs = new_expr!(NodeType::PostInc, lhs);
+                lhs.token = Some(t.clone());
                 continue;
             }
 
             if self.consume(TokenType::Dec) {
+                self.deeper();
                 lhs = new_expr!(NodeType::PostDec, lhs);
+                lhs.token = Some(t.clone());
                 continue;
             }
 
             if self.consume(TokenType::Dot) {
                 // TODO: Use new_expr!
+                self.deeper();
                 lhs = Node::new(NodeType::Dot(Box::new(lhs), self.ident(), 0));
+                lhs.token = Some(t.clone());
                 continue;
             }
 
             if self.consume(TokenType::Arrow) {
-                lhs = Node::new(NodeType::Dot(
-                    Box::new(new_expr!(NodeType::Deref, lhs)),
-                    self.ident(),
-                    0,
-                ));
+                self.deeper();
+                let mut deref = new_expr!(NodeType::Deref, lhs);
+                deref.token = Some(t.clone());
+                lhs = Node::new(NodeType::Dot(Box::new(deref), self.ident(), 0));
+                lhs.token = Some(t.clone());
                 continue;
             }
 
+            // The `[` token marks the addition as a subscript.
             if self.consume(TokenType::LeftBracket) {
-                lhs = new_expr!(
-                    NodeType::Deref,
-                    Node::new_binop(TokenType::Plus, lhs, self.assign())
-                );
+                self.deeper();
+                let mut addr = Node::new_binop(TokenType::Plus, lhs, self.assign());
+                addr.token = Some(t.clone());
+                lhs = new_expr!(NodeType::Deref, addr);
                 self.expect(TokenType::RightBracket);
                 continue;
             }
+
+            // A call of a name is read by primary(), so this one is through
+            // a function pointer, e.g. `(*fp)(1)`.
+            if self.consume(TokenType::LeftParen) {
+                self.deeper();
+                lhs = Node::new(NodeType::CallPtr(Box::new(lhs), self.call_args()));
+                lhs.token = Some(t.clone());
+                continue;
+            }
+            self.depth = depth;
             return lhs;
         }
     }
 
+    // Parentheses and prefix operators both recurse through here.
     fn unary(&mut self) -> Node {
-        if self.consume(TokenType::Minus) {
-            return new_expr!(NodeType::Neg, self.unary());
-        }
-        if self.consume(TokenType::Mul) {
-            return new_expr!(NodeType::Deref, self.unary());
-        }
-        if self.consume(TokenType::And) {
-            return new_expr!(NodeType::Addr, self.unary());
-        }
-        if self.consume(TokenType::Exclamation) {
-            return new_expr!(NodeType::Exclamation, self.unary());
+        self.nested(Self::unary_op)
+    }
+
+    fn unary_op(&mut self) -> Node {
+        if self.consume(TokenType::Extension) {
+            return self.unary();
         }
-        if self.consume(TokenType::Sizeof) {
-            return new_expr!(NodeType::Sizeof, self.unary());
+        if self.consume(TokenType::Logand) {
+            return Node::new(NodeType::LabelAddr(self.ident()));
         }
-        if self.consume(TokenType::Alignof) {
-            return new_expr!(NodeType::Alignof, self.unary());
+        if self.consume(TokenType::Offsetof) {
+            return self.offsetof();
         }
 
-        if self.consume(TokenType::Inc) {
-            return Node::new_binop(TokenType::AddEQ, self.unary(), Node::new_num(1));
+        // The operator's token is kept for errors found in sema.
+        let t = self.peek();
+        let prefix: Option<fn(Box<Node>) -> NodeType> = match t.ty {
+            TokenType::Minus => Some(NodeType::Neg),
+            TokenType::Mul => Some(NodeType::Deref),
+            TokenType::And => Some(NodeType::Addr),
+            TokenType::Exclamation => Some(NodeType::Exclamation),
+            TokenType::Sizeof => Some(NodeType::Sizeof),
+            TokenType::Alignof => Some(NodeType::Alignof),
+            _ => None,
+        };
+        if let Some(prefix) = prefix {
+            self.next();
+            let mut node = Node::new(prefix(Box::new(self.unary())));
+            node.token = Some(t.clone());
+            return node;
         }
-        if self.consume(TokenType::Dec) {
-            return Node::new_binop(TokenType::SubEQ, self.unary(), Node::new_num(1));
+
+        let op = if self.consume(TokenType::Inc) {
+            Some(TokenType::AddEQ)
+        } else if self.consume(TokenType::Dec) {
+            Some(TokenType::SubEQ)
+        } else {
+            None
+        };
+        if let Some(op) = op {
+            let mut node = Node::new_binop(op, self.unary(), Node::new_num(1));
+            node.token = Some(t.clone());
+            return node;
         }
 
         self.postfix()
     }
 
+    // Reads `(type, member)` of `__builtin_offsetof`, where member may be
+    // a chain such as `a.b[2].c`, and returns the offset as a constant.
+    fn offsetof(&mut self) -> Node {
+        self.expect(TokenType::LeftParen);
+        let mut ty = self.type_name();
+        self.expect(TokenType::Comma);
+
+        let mut offset = 0;
+        let mut t = self.peek();
+        let mut name = self.ident();
+        loop {
+            let member = match ty.ty {
+                Ctype::Struct(ref members) => members.iter().find_map(|m| match m.op {
+                    NodeType::Vardef(ref m_name, _, Scope::Local(off)) if *m_name == name => {
+                        Some((off, (*m.ty).clone()))
+                    }
+                    _ => None,
+                }),
+                _ => t.bad_token("struct expected in offsetof"),
+            };
+            let (off, member_ty) = member.unwrap_or_else(|| t.bad_token("no such member"));
+            offset += off;
+            ty = member_ty;
+
+            while self.consume(TokenType::LeftBracket) {
+                let t = self.peek();
+                let index = const_eval(t, &self.expr())
+                    .unwrap_or_else(|| t.bad_token("constant expression expected"));
+                self.expect(TokenType::RightBracket);
+                if index < 0 {
+                    t.bad_token("negative array index in offsetof");
+                }
+                ty = match ty.ty {
+                    Ctype::Ary(ref ary_of, _) => {
+                        offset = (index as usize)
+                            .checked_mul(ary_of.size)
+                            .and_then(|off| off.checked_add(offset))
+                            .filter(|off| *off <= i32::MAX as usize)
+                            .unwrap_or_else(|| t.bad_token("offsetof is too large"));
+                        (**ary_of).clone()
+                    }
+                    _ => t.bad_token("array expected in offsetof"),
+                };
+            }
+
+            if !self.consume(TokenType::Dot) {
+                break;
+            }
+            t = self.peek();
+            name = self.ident();
+        }
+        self.expect(TokenType::RightParen);
+        Node::new_int(offset as i32)
+    }
+
+    // x / 0 and x % 0 are undefined, so reject a constant zero divisor
+    // here rather than emitting a division that faults at runtime.
+    fn check_divisor(t: &Token, rhs: &Node) {
+        if eval(rhs) == Some(0) {
+            t.bad_token("division by zero");
+        }
+    }
+
     fn mul(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.unary();
 
         loop {
+            let t = self.peek();
             if self.consume(TokenType::Mul) {
-                lhs = Node::new_binop(TokenType::Mul, lhs, self.unary());
+                lhs = Node::new_binop(TokenType::Mul, lhs, self.operand(Self::unary));
             } else if self.consume(TokenType::Div) {
-                lhs = Node::new_binop(TokenType::Div, lhs, self.unary());
+                let rhs = self.operand(Self::unary);
+                Self::check_divisor(t, &rhs);
+                lhs = Node::new_binop(TokenType::Div, lhs, rhs);
             } else if self.consume(TokenType::Mod) {
-                lhs = Node::new_binop(TokenType::Mod, lhs, self.unary());
+                let rhs = self.operand(Self::unary);
+                Self::check_divisor(t, &rhs);
+                lhs = Node::new_binop(TokenType::Mod, lhs, rhs);
             } else {
+                self.depth = depth;
                 return lhs;
             }
         }
     }
 
     fn add(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.mul();
 
         loop {
-            if self.consume(TokenType::Plus) {
-                lhs = Node::new_binop(TokenType::Plus, lhs, self.mul());
+            let t = self.peek();
+            let mut node = if self.consume(TokenType::Plus) {
+                Node::new_binop(TokenType::Plus, lhs, self.operand(Self::mul))
             } else if self.consume(TokenType::Minus) {
-                lhs = Node::new_binop(TokenType::Minus, lhs, self.mul());
+                Node::new_binop(TokenType::Minus, lhs, self.operand(Self::mul))
             } else {
+                self.depth = depth;
                 return lhs;
-            }
+            };
+            node.token = Some(t.clone());
+            lhs = node;
         }
     }
 
     fn shift(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.add();
         loop {
-            if self.consume(TokenType::SHL) {
-                lhs = Node::new_binop(TokenType::SHL, lhs, self.add());
+            let t = self.peek();
+            let mut node = if self.consume(TokenType::SHL) {
+                Node::new_binop(TokenType::SHL, lhs, self.operand(Self::add))
             } else if self.consume(TokenType::SHR) {
-                lhs = Node::new_binop(TokenType::SHR, lhs, self.add());
+                Node::new_binop(TokenType::SHR, lhs, self.operand(Self::add))
             } else {
+                self.depth = depth;
                 return lhs;
-            }
+            };
+            node.token = Some(t.clone());
+            lhs = node;
         }
     }
 
     fn relational(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.shift();
         loop {
-            if self.consume(TokenType::LeftAngleBracket) {
-                lhs = Node::new_binop(TokenType::LeftAngleBracket, lhs, self.shift());
+            let t = self.peek();
+            let mut node = if self.consume(TokenType::LeftAngleBracket) {
+                Node::new_binop(TokenType::LeftAngleBracket, lhs, self.operand(Self::shift))
             } else if self.consume(TokenType::RightAngleBracket) {
-                lhs = Node::new_binop(TokenType::LeftAngleBracket, self.shift(), lhs);
+                Node::new_binop(TokenType::LeftAngleBracket, self.operand(Self::shift), lhs)
             } else if self.consume(TokenType::LE) {
-                lhs = Node::new_binop(TokenType::LE, lhs, self.shift())
+                Node::new_binop(TokenType::LE, lhs, self.operand(Self::shift))
             } else if self.consume(TokenType::GE) {
-                lhs = Node::new_binop(TokenType::LE, self.shift(), lhs);
+                Node::new_binop(TokenType::LE, self.operand(Self::shift), lhs)
             } else {
+                self.depth = depth;
                 return lhs;
-            }
+            };
+            node.token = Some(t.clone());
+            lhs = node;
         }
     }
 
     fn equality(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.relational();
         loop {
-            if self.consume(TokenType::EQ) {
-                lhs = Node::new_binop(TokenType::EQ, lhs, self.relational());
+            let t = self.peek();
+            let mut node = if self.consume(TokenType::EQ) {
+                Node::new_binop(TokenType::EQ, lhs, self.operand(Self::relational))
             } else if self.consume(TokenType::NE) {
-                lhs = Node::new_binop(TokenType::NE, lhs, self.relational());
+                Node::new_binop(TokenType::NE, lhs, self.operand(Self::relational))
             } else {
+                self.depth = depth;
                 return lhs;
-            }
+            };
+            node.token = Some(t.clone());
+            lhs = node;
         }
     }
 
     fn bit_and(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.equality();
         while self.consume(TokenType::And) {
-            lhs = Node::new_binop(TokenType::And, lhs, self.equality());
+            lhs = Node::new_binop(TokenType::And, lhs, self.operand(Self::equality));
         }
+        self.depth = depth;
         lhs
     }
 
     fn bit_xor(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.bit_and();
         while self.consume(TokenType::Hat) {
-            lhs = Node::new_binop(TokenType::Hat, lhs, self.bit_and());
+            lhs = Node::new_binop(TokenType::Hat, lhs, self.operand(Self::bit_and));
         }
+        self.depth = depth;
         lhs
     }
 
     fn bit_or(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.bit_xor();
         while self.consume(TokenType::VerticalBar) {
-            lhs = Node::new_binop(TokenType::VerticalBar, lhs, self.bit_xor());
+            lhs = Node::new_binop(TokenType::VerticalBar, lhs, self.operand(Self::bit_xor));
         }
+        self.depth = depth;
         lhs
     }
 
     fn logand(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.bit_or();
         while self.consume(TokenType::Logand) {
-            lhs = Node::new_binop(TokenType::Logand, lhs, self.logand());
+            lhs = Node::new_binop(TokenType::Logand, lhs, self.operand(Self::logand));
         }
+        self.depth = depth;
         lhs
     }
 
     fn logor(&mut self) -> Node {
+        let depth = self.depth;
         let mut lhs = self.logand();
         while self.consume(TokenType::Logor) {
-            lhs = Node::new_binop(TokenType::Logor, lhs, self.logand());
+            lhs = Node::new_binop(TokenType::Logor, lhs, self.operand(Self::logand));
         }
+        self.depth = depth;
         lhs
     }
 
@@ -557,9 +1447,15 @@ impl<'a> Parser<'a> {
         if !self.consume(TokenType::Question) {
             return cond;
         }
-        let then = self.expr();
+        // GNU extension: `a ?: b` yields a, evaluated only once, if it
+        // is nonzero. The omitted operand is represented as Null.
+        let then = if self.peek().ty == TokenType::Colon {
+            Node::new(NodeType::Null)
+        } else {
+            self.nested(Self::expr)
+        };
         self.expect(TokenType::Colon);
-        let els = self.conditional();
+        let els = self.nested(Self::conditional);
         Node::new(NodeType::Ternary(
             Box::new(cond),
             Box::new(then),
@@ -578,9 +1474,16 @@ impl<'a> Parser<'a> {
 
     fn assign(&mut self) -> Node {
         let lhs = self.conditional();
-        if let Some(op) = Self::assign_op(&self.tokens[self.pos].ty) {
+        let t = self.peek();
+        if let Some(op) = Self::assign_op(&t.ty) {
             self.pos += 1;
-            Node::new_binop(op.clone(), lhs, self.assign())
+            let rhs = self.nested(Self::assign);
+            if *op == TokenType::DivEQ || *op == TokenType::ModEQ {
+                Self::check_divisor(t, &rhs);
+            }
+            let mut node = Node::new_binop(op.clone(), lhs, rhs);
+            node.token = Some(t.clone());
+            node
         } else {
             lhs
         }
@@ -591,50 +1494,184 @@ impl<'a> Parser<'a> {
         if !self.consume(TokenType::Comma) {
             return lhs;
         }
-        Node::new_binop(TokenType::Comma, lhs, self.expr())
+        Node::new_binop(TokenType::Comma, lhs, self.nested(Self::expr))
     }
 
-    fn ctype(&mut self) -> Type {
-        let t = &self.tokens[self.pos];
-        if let Some(mut ty) = self.decl_specifiers() {
-            while self.consume(TokenType::Mul) {
-                ty = Type::ptr_to(Box::new(ty));
-            }
-            ty
+    // A type name with an optional array part, e.g. `int *[3]` or `int (*)[3]`.
+    fn type_name(&mut self) -> Type {
+        let ty = self.ctype();
+        self.abstract_declarator(ty)
+    }
+
+    // Like direct_decl(), but without a name.
+    fn abstract_declarator(&mut self, ty: Type) -> Type {
+        if self.peek().ty != TokenType::LeftParen || self.tokens[self.pos + 1].ty != TokenType::Mul
+        {
+            return self.read_array(Box::new(ty));
+        }
+
+        self.pos += 1;
+        let mut inner = Type::default();
+        while self.consume(TokenType::Mul) {
+            inner = Type::ptr_to(Box::new(inner));
+            self.pointer_qualifiers(&mut inner);
+        }
+        let inner = self.abstract_declarator(inner);
+        self.expect(TokenType::RightParen);
+        let ty = if self.consume(TokenType::LeftParen) {
+            self.func_type(ty)
         } else {
-            t.bad_token("typename expected");
+            self.read_array(Box::new(ty))
+        };
+        replace_placeholder(inner, ty)
+    }
+
+    // Like decl_specifiers(), but a missing type is an error.
+    fn type_specifier(&mut self) -> Type {
+        let t = self.peek();
+        self.decl_specifiers()
+            .unwrap_or_else(|| t.bad_token("typename expected"))
+    }
+
+    fn ctype(&mut self) -> Type {
+        let ty = self.type_specifier();
+        self.pointer(ty)
+    }
+
+    // Reads the `*`s of a declarator, e.g. `* restrict *`.
+    fn pointer(&mut self, mut ty: Type) -> Type {
+        while self.consume(TokenType::Mul) {
+            ty = Type::ptr_to(Box::new(ty));
+            self.pointer_qualifiers(&mut ty);
         }
+        ty
     }
 
     fn read_array(&mut self, mut ty: Box<Type>) -> Type {
-        let mut v: Vec<usize> = vec![];
+        let mut v: Vec<Option<usize>> = vec![];
         while self.consume(TokenType::LeftBracket) {
             if self.consume(TokenType::RightBracket) {
-                v.push(0); // temporary value
+                v.push(None);
                 continue;
             }
 
+            // The length may be any constant expression, such as
+            // `BUFSZ * 2` where BUFSZ is an enumerator.
+            let t = self.peek();
             let len = self.expr();
-            if let NodeType::Num(n) = len.op {
-                v.push(n as usize);
-                self.expect(TokenType::RightBracket);
-            } else {
-                panic!("number expected");
+            match const_eval(t, &len) {
+                Some(n) if n >= 0 => v.push(Some(n as usize)),
+                Some(_) => t.bad_token("array size is negative"),
+                None => t.bad_token("array size must be a constant expression"),
             }
+            self.expect(TokenType::RightBracket);
         }
 
-        v.reverse();
-        for val in v {
-            ty = Box::new(Type::ary_of(ty, val));
+        // An omitted length is 0 until an initializer sets it.
+        self.unsized_array = v.first() == Some(&None);
+        for len in v.into_iter().rev() {
+            ty = Box::new(Type::ary_of(ty, len.unwrap_or(0)));
         }
         *ty
     }
 
+    // A compound literal such as `(struct point){1, 2}` is an unnamed
+    // local variable initialized with the list.
+    fn compound_literal(&mut self, ty: Type) -> Node {
+        let name = format!(".compound_literal{}", self.ncomplit);
+        self.ncomplit += 1;
+
+        let ident = Node::new(NodeType::Ident(name.clone()));
+        let init = self.init_rval(ident.clone(), &ty);
+        let mut var = Node::new(NodeType::Vardef(name, None, Scope::Local(0)));
+        var.ty = Box::new(ty);
+        Self::set_init_len(&mut var, &init);
+        let zero = Self::zero_fill(&ident, &var.ty);
+        Node::new(NodeType::CompLit(
+            Box::new(Node::new(NodeType::VecStmt(vec![var, zero, init]))),
+            Box::new(ident),
+        ))
+    }
+
+    // What a brace initializer leaves out of an array or struct is zero,
+    // as in a static one, so the object is cleared before the listed
+    // elements are stored.
+    fn zero_fill(ident: &Node, ty: &Type) -> Node {
+        if !std::matches!(ty.ty, Ctype::Ary(..) | Ctype::Struct(_)) {
+            return Node::new(NodeType::Null);
+        }
+        let args = vec![
+            new_expr!(NodeType::Addr, ident.clone()),
+            Node::new_int(0),
+            Node::new(NodeType::Num(ty.size as i64)),
+        ];
+        let call = Node::new(NodeType::Call("__builtin_memset".into(), args));
+        Node::new(NodeType::ExprStmt(Box::new(call)))
+    }
+
+    // `int x[] = {1, 2, 3}` takes its length from the initializer.
+    fn set_init_len(var: &mut Node, init: &Node) {
+        if let (Ctype::Ary(ref ary_of, 0), NodeType::VecStmt(ref init)) = (&var.ty.ty, &init.op) {
+            let ary_of = ary_of.clone();
+            *var.ty = Type::ary_of(ary_of, init.len());
+        }
+    }
+
+    // Reads the rest of a brace-enclosed initializer for `ident`.
+    fn init_rval(&mut self, ident: Node, ty: &Type) -> Node {
+        match ty.ty {
+            Ctype::Ary(..) => self.array_init_rval(ident),
+            Ctype::Struct(ref members) => self.struct_init_rval(ident, members),
+            _ => {
+                let val = self.assign();
+                self.consume(TokenType::Comma);
+                self.expect(TokenType::RightBrace);
+                let assign = Node::new_binop(TokenType::Equal, ident, val);
+                Node::new(NodeType::VecStmt(vec![Node::new(NodeType::ExprStmt(
+                    Box::new(assign),
+                ))]))
+            }
+        }
+    }
+
+    fn struct_init_rval(&mut self, ident: Node, members: &[Node]) -> Node {
+        let mut init = vec![];
+        for m in members {
+            if self.consume(TokenType::RightBrace) {
+                return Node::new(NodeType::VecStmt(init));
+            }
+
+            let name = match m.op {
+                NodeType::Vardef(ref name, _, _) => name.clone(),
+                _ => unreachable!(),
+            };
+            let node = Node::new(NodeType::Dot(Box::new(ident.clone()), name, 0));
+            let val = self.assign();
+            init.push(Node::new(NodeType::ExprStmt(Box::new(Node::new_binop(
+                TokenType::Equal,
+                node,
+                val,
+            )))));
+            if !self.consume(TokenType::Comma) {
+                break;
+            }
+        }
+        let t = self.peek();
+        if !self.consume(TokenType::RightBrace) {
+            t.bad_token("excess elements in struct initializer");
+        }
+        Node::new(NodeType::VecStmt(init))
+    }
+
     fn array_init_rval(&mut self, ident: Node) -> Node {
         let mut init = vec![];
         let mut i = 0;
         loop {
-            let val = self.primary();
+            if self.consume(TokenType::RightBrace) {
+                break;
+            }
+
+            let val = self.assign();
             let node = new_expr!(
                 NodeType::Deref,
                 Node::new_binop(TokenType::Plus, ident.clone(), Node::new(NodeType::Num(i)))
@@ -644,83 +1681,199 @@ impl<'a> Parser<'a> {
                 node,
                 val,
             )))));
+            i += 1;
             if !self.consume(TokenType::Comma) {
+                self.expect(TokenType::RightBrace);
                 break;
             }
-            i += 1;
         }
-        self.expect(TokenType::RightBrace);
         Node::new(NodeType::VecStmt(init))
     }
 
-    fn update_ptr_to(&mut self, src: &mut Box<Type>, dst: Box<Type>) {
-        match src.ty {
-            Ctype::Ptr(ref mut ptr_to) => self.update_ptr_to(ptr_to, dst),
-            _ => *src = dst,
-        }
-    }
-
     fn direct_decl(&mut self, ty: Box<Type>) -> Node {
-        let t = &self.tokens[self.pos];
+        let t = self.peek();
         let mut placeholder = Box::new(Type::default());
         let mut node;
+        let mut is_nested = false;
 
         if let TokenType::Ident(_) = t.ty {
             node = Node::new(NodeType::Vardef(self.ident(), None, Scope::Local(0)));
         } else if self.consume(TokenType::LeftParen) {
             node = self.declarator(&mut placeholder);
             self.expect(TokenType::RightParen);
+            is_nested = true;
+        } else if t.ty == TokenType::Restrict {
+            t.bad_token("restrict requires a pointer type");
         } else {
             t.bad_token("bad direct-declarator");
         }
 
-        // Read the second half of type name (e.g. `[3][5]`).
-        let ty = self.read_array(ty);
-        self.update_ptr_to(&mut node.ty, Box::new(ty));
+        // Read the second half of type name, e.g. `[3][5]`, or the
+        // parameters of a function pointer, e.g. `(int)` in `(*fp)(int)`.
+        let ty = if is_nested && self.consume(TokenType::LeftParen) {
+            self.func_type(*ty)
+        } else {
+            self.read_array(ty)
+        };
+        node.ty = Box::new(replace_placeholder(*node.ty, ty));
+        self.attributes(&mut node.ty);
+        node
+    }
 
-        // Read an initializer.
-        let init: Option<Box<Node>>;
-        if self.consume(TokenType::Equal) {
-            // Assign a value when initializing an array.
-            if let TokenType::Ident(ref name) = t.ty {
-                if self.consume(TokenType::LeftBrace) {
-                    let mut stmts = vec![];
-                    let mut ary_declaration =
-                        Node::new(NodeType::Vardef(name.clone(), None, Scope::Local(0)));
-                    ary_declaration.ty = node.ty;
-                    stmts.push(ary_declaration);
-                    let init_ary = self.array_init_rval(Node::new(NodeType::Ident(name.clone())));
-                    stmts.push(init_ary);
-                    return Node::new(NodeType::VecStmt(stmts));
-                }
-            }
+    // Reads the initializer of a local variable, if any.
+    fn local_init(&mut self, mut node: Node) -> Node {
+        if !self.consume(TokenType::Equal) {
+            return node;
+        }
+        let name = match node.op {
+            NodeType::Vardef(ref name, _, _) => name.clone(),
+            _ => unreachable!(),
+        };
+
+        // Assign a value when initializing an array.
+        if self.consume(TokenType::LeftBrace) {
+            let mut ary_declaration =
+                Node::new(NodeType::Vardef(name.clone(), None, Scope::Local(0)));
+            let ident = Node::new(NodeType::Ident(name));
+            let init_ary = self.init_rval(ident.clone(), &node.ty);
+            ary_declaration.ty = node.ty;
+            Self::set_init_len(&mut ary_declaration, &init_ary);
+            let zero = Self::zero_fill(&ident, &ary_declaration.ty);
+            return Node::new(NodeType::VecStmt(vec![ary_declaration, zero, init_ary]));
+        }
+        if let Ctype::Ary(..) = node.ty.ty {
+            self.peek()
+                .bad_token("array initializer must be enclosed in braces");
+        }
 
-            init = Some(Box::new(self.assign()));
-            match node.op {
-                NodeType::Vardef(_, ref mut init2, _) => *init2 = init,
-                _ => unreachable!(),
-            }
+        let init = Some(Box::new(self.assign()));
+        match node.op {
+            NodeType::Vardef(_, ref mut init2, _) => *init2 = init,
+            _ => unreachable!(),
         }
         node
     }
 
+    // `restrict` is only a hint to the optimizer, so it is skipped.
+    // `int *const p` makes the pointer itself const.
+    fn pointer_qualifiers(&mut self, ty: &mut Type) {
+        loop {
+            if self.consume(TokenType::Const) {
+                ty.is_const = true;
+            } else if self.consume(TokenType::Volatile) {
+                ty.is_volatile = true;
+            } else if !self.consume(TokenType::Restrict) {
+                return;
+            }
+        }
+    }
+
     fn declarator(&mut self, ty: &mut Type) -> Node {
         while self.consume(TokenType::Mul) {
             *ty = Type::ptr_to(Box::new(ty.clone()));
+            self.pointer_qualifiers(ty);
         }
         self.direct_decl(Box::new(ty.clone()))
     }
 
     fn declaration(&mut self) -> Node {
-        let mut ty = self.decl_specifiers().unwrap();
+        let mut ty = self.type_specifier();
+        if self.consume(TokenType::Semicolon) {
+            return Node::new(NodeType::Null);
+        }
         let node = self.declarator(&mut ty);
+        let node = self.local_init(node);
+        if self.peek().ty == TokenType::LeftParen {
+            let start = self.pos;
+            self.pos += 1;
+            self.skip_parens();
+            if self.peek().ty == TokenType::LeftBrace {
+                self.tokens[start].bad_token("nested function definitions are not allowed");
+            }
+            self.pos = start;
+        }
         self.expect(TokenType::Semicolon);
         node
     }
 
-    fn param_declaration(&mut self) -> Node {
-        let mut ty = self.decl_specifiers().unwrap();
+    // Struct member, optionally a bit-field (e.g. `unsigned a : 3;`).
+    fn member_declaration(&mut self) -> Node {
+        let mut ty = self.type_specifier();
         let mut node = self.declarator(&mut ty);
+        if self.consume(TokenType::Colon) {
+            let t = self.peek();
+            let width = const_eval(t, &self.conditional())
+                .unwrap_or_else(|| t.bad_token("constant expression expected"));
+            if !matches!(node.ty.ty, Ctype::Int) || width < 0 || width as usize > node.ty.size * 8 {
+                t.bad_token("invalid bit-field width");
+            }
+            node.ty.bitfield = Some((0, width as usize));
+        }
+        self.expect(TokenType::Semicolon);
+        node
+    }
+
+    // Returns true if the declarator that follows has a name, which is
+    // optional for a parameter.
+    fn is_named_declarator(&self) -> bool {
+        use self::TokenType::*;
+        let mut i = self.pos;
+        while std::matches!(
+            self.tokens[i].ty,
+            Mul | LeftParen | Restrict | Const | Volatile
+        ) {
+            i += 1;
+        }
+        std::matches!(self.tokens[i].ty, Ident(_))
+    }
+
+    // Reads a parameter list after its `(`. The second value is true for
+    // `(void)`, which takes no arguments, while the parameters of `()`
+    // are unspecified. The third is true if the list ends in `...`.
+    fn params(&mut self) -> (Vec<Node>, bool, bool) {
+        let mut args = vec![];
+        let mut is_variadic = false;
+        let is_void = self.peek().ty == TokenType::Void
+            && self.tokens[self.pos + 1].ty == TokenType::RightParen;
+        if is_void {
+            self.pos += 2;
+        } else if !self.consume(TokenType::RightParen) {
+            args.push(self.param_declaration());
+            while self.consume(TokenType::Comma) {
+                if self.consume(TokenType::Ellipsis) {
+                    is_variadic = true;
+                    break;
+                }
+                args.push(self.param_declaration());
+            }
+            self.expect(TokenType::RightParen);
+        }
+        (args, is_void, is_variadic)
+    }
+
+    // The type of a function returning `ret`, read from its parameter
+    // list after the `(`.
+    fn func_type(&mut self, ret: Type) -> Type {
+        let (args, is_void, is_variadic) = self.params();
+        let params = if is_void || !args.is_empty() {
+            Some(args.iter().map(|arg| (*arg.ty).clone()).collect())
+        } else {
+            None
+        };
+        Type::new(Ctype::Func(Box::new(ret), params, is_variadic), 0)
+    }
+
+    // The name may be omitted, as in `int f(int, char *);`.
+    fn param_declaration(&mut self) -> Node {
+        let mut ty = self.type_specifier();
+        let mut node = if self.is_named_declarator() {
+            self.declarator(&mut ty)
+        } else {
+            let ty = self.pointer(ty);
+            let mut node = Node::new(NodeType::Vardef(String::new(), None, Scope::Local(0)));
+            node.ty = Box::new(self.abstract_declarator(ty));
+            node
+        };
         if let Ctype::Ary(ary_of, _) = node.ty.ty {
             node.ty = Box::new(Type::ptr_to(ary_of));
         }
@@ -734,20 +1887,77 @@ impl<'a> Parser<'a> {
         node
     }
 
+    // The variables declared in the first clause are scoped to the loop.
+    fn for_stmt(&mut self) -> Node {
+        self.expect(TokenType::LeftParen);
+
+        let init: Box<Node> = if self.is_typename(self.peek()) {
+            let node = self.declaration();
+            self.declare(&node);
+            self.add_cleanup(&node);
+            Box::new(node)
+        } else if self.consume(TokenType::Semicolon) {
+            Box::new(Node::new(NodeType::Null))
+        } else {
+            Box::new(self.expr_stmt())
+        };
+
+        let cond;
+        if !self.consume(TokenType::Semicolon) {
+            cond = Box::new(self.expr());
+            self.expect(TokenType::Semicolon);
+        } else {
+            cond = Box::new(Node::new(NodeType::Null))
+        }
+
+        let inc;
+        if !self.consume(TokenType::RightParen) {
+            inc = Box::new(new_expr!(NodeType::ExprStmt, self.expr()));
+            self.expect(TokenType::RightParen);
+        } else {
+            inc = Box::new(Node::new(NodeType::Null))
+        }
+
+        let body = Box::new(self.loop_body());
+        let cleanups = mem::take(&mut self.env.cleanups);
+        if cleanups.is_empty() {
+            return Node::new(NodeType::For(init, cond, inc, body));
+        }
+
+        // A variable declared in init is cleaned up when the loop exits,
+        // as if the loop were `{ init; for (; cond; inc) body }`. A break
+        // leaves only the body, so it exits through the cleanups too.
+        let null = Box::new(Node::new(NodeType::Null));
+        let mut stmts = vec![*init, Node::new(NodeType::For(null, cond, inc, body))];
+        stmts.extend(cleanups);
+        Node::new(NodeType::CompStmt(stmts))
+    }
+
     fn stmt(&mut self) -> Node {
-        let t = &self.tokens[self.pos];
-        self.pos += 1;
+        let t = self.peek();
+        let mut node = self.nested(Self::stmt_body);
+        // For warnings about the statement as a whole.
+        if node.token.is_none() {
+            node.token = Some(t.clone());
+        }
+        node
+    }
+
+    fn stmt_body(&mut self) -> Node {
+        while self.consume(TokenType::Extension) {}
+        let t = self.next();
 
         match t.ty {
             TokenType::Typedef => {
                 let node = self.declaration();
                 if let NodeType::Vardef(name, _, _) = node.op {
                     self.env.typedefs.insert(name, *node.ty);
-                    return Node::new(NodeType::Null);
+                    Node::new(NodeType::Null)
                 } else {
                     unreachable!();
                 }
             }
+            TokenType::Static => self.static_local(),
             TokenType::If => {
                 let mut els = None;
                 self.expect(TokenType::LeftParen);
@@ -759,47 +1969,18 @@ impl<'a> Parser<'a> {
                 }
                 Node::new(NodeType::If(Box::new(cond), Box::new(then), els))
             }
-            TokenType::For => {
-                self.expect(TokenType::LeftParen);
-
-                let init: Box<Node> = if self.is_typename(&self.tokens[self.pos]) {
-                    Box::new(self.declaration())
-                } else if self.consume(TokenType::Semicolon) {
-                    Box::new(Node::new(NodeType::Null))
-                } else {
-                    Box::new(self.expr_stmt())
-                };
-
-                let cond;
-                if !self.consume(TokenType::Semicolon) {
-                    cond = Box::new(self.expr());
-                    self.expect(TokenType::Semicolon);
-                } else {
-                    cond = Box::new(Node::new(NodeType::Null))
-                }
-
-                let inc;
-                if !self.consume(TokenType::RightParen) {
-                    inc = Box::new(new_expr!(NodeType::ExprStmt, self.expr()));
-                    self.expect(TokenType::RightParen);
-                } else {
-                    inc = Box::new(Node::new(NodeType::Null))
-                }
-
-                let body = Box::new(self.stmt());
-                Node::new(NodeType::For(init, cond, inc, body))
-            }
+            TokenType::For => self.in_new_scope(Self::for_stmt),
             TokenType::While => {
                 self.expect(TokenType::LeftParen);
                 let init = Box::new(Node::new(NodeType::Null));
                 let inc = Box::new(Node::new(NodeType::Null));
                 let cond = Box::new(self.expr());
                 self.expect(TokenType::RightParen);
-                let body = Box::new(self.stmt());
+                let body = Box::new(self.loop_body());
                 Node::new(NodeType::For(init, cond, inc, body))
             }
             TokenType::Do => {
-                let body = Box::new(self.stmt());
+                let body = Box::new(self.loop_body());
                 self.expect(TokenType::While);
                 self.expect(TokenType::LeftParen);
                 let cond = Box::new(self.expr());
@@ -809,89 +1990,316 @@ impl<'a> Parser<'a> {
             }
             TokenType::Break => {
                 self.expect(TokenType::Semicolon);
-                Node::new(NodeType::Break)
+                self.loop_exit(Node::new(NodeType::Break))
             }
-            TokenType::Return => {
-                let expr = self.expr();
+            TokenType::Continue => {
                 self.expect(TokenType::Semicolon);
-                Node::new(NodeType::Return(Box::new(expr)))
+                self.loop_exit(Node::new(NodeType::Continue))
             }
-            TokenType::LeftBrace => {
-                let mut stmts = vec![];
-                while !self.consume(TokenType::RightBrace) {
-                    stmts.push(self.stmt());
+            TokenType::Goto => {
+                if self.has_cleanups() {
+                    t.bad_token("goto in the scope of a variable with a cleanup");
                 }
-                Node::new(NodeType::CompStmt(stmts))
+                let node = if self.consume(TokenType::Mul) {
+                    new_expr!(NodeType::GotoPtr, self.expr())
+                } else {
+                    Node::new(NodeType::Goto(self.ident()))
+                };
+                self.expect(TokenType::Semicolon);
+                node
             }
+            TokenType::Ident(ref name) if self.consume(TokenType::Colon) => {
+                Node::new(NodeType::Label(name.clone(), Box::new(self.stmt())))
+            }
+            TokenType::Return => {
+                let expr = self.expr();
+                self.expect(TokenType::Semicolon);
+                self.return_stmt(expr)
+            }
+            TokenType::LeftBrace => self.compound_stmt(),
             TokenType::Semicolon => Node::new(NodeType::Null),
             _ => {
                 self.pos -= 1;
-                if self.is_typename(&self.tokens[self.pos]) {
-                    return self.declaration();
+                if self.is_typename(self.peek()) {
+                    let node = self.declaration();
+                    self.declare(&node);
+                    self.add_cleanup(&node);
+                    return node;
                 }
                 self.expr_stmt()
             }
         }
     }
 
-    fn compound_stmt(&mut self) -> Node {
-        let mut stmts = vec![];
+    // A static local is read like a global, and sema gives it a unique
+    // label. Its initializer is applied once, not on each execution of
+    // the declaration. Kept out of stmt_body(), whose frame size bounds
+    // how deeply statements can nest.
+    #[inline(never)]
+    fn static_local(&mut self) -> Node {
+        let base = self.type_specifier();
+        let mut nodes = vec![];
+        loop {
+            let node = self.declarator(&mut base.clone());
+            let name = match node.op {
+                NodeType::Vardef(name, _, _) => name,
+                _ => unreachable!(),
+            };
+            nodes.extend(self.global_init(*node.ty, name, false, false, true));
+            if !self.consume(TokenType::Comma) {
+                break;
+            }
+        }
+        self.expect(TokenType::Semicolon);
+        Node::new(NodeType::VecStmt(nodes))
+    }
 
-        let new_env = Env::new(Some(Box::new(self.env.clone())));
-        self.env = new_env;
-        while !self.consume(TokenType::RightBrace) {
-            stmts.push(self.stmt());
+    // Reads the initializer of a scalar at `offset` in a global. One that
+    // is not an integer constant is left to sema as zeros in the data.
+    fn scalar_init(&mut self, ty: &Type, parts: &mut Vec<InitPart>, offset: usize) -> String {
+        let t = self.peek();
+        let node = self.assign();
+        match const_eval(t, &node) {
+            Some(val) => int_data(val, ty),
+            None => {
+                parts.push((offset, ty.clone(), node));
+                "\0".repeat(ty.size)
+            }
         }
-        let next = self.env.next.clone();
-        self.env = *next.unwrap();
-        Node::new(NodeType::CompStmt(stmts))
     }
 
-    fn toplevel(&mut self) -> Option<Node> {
+    // Reads the initializer of a global array, e.g. `{1, 2, 3}` or `"abc"`,
+    // and returns its bytes. The missing elements are zero, and those
+    // that are not integer constants go to `parts`. The length of
+    // `int a[] = {...}` is set from it.
+    fn global_array_init(
+        &mut self,
+        ty: &mut Type,
+        parts: &mut Vec<InitPart>,
+        base: usize,
+    ) -> String {
+        let (elem, len) = match ty.ty {
+            Ctype::Ary(ref elem, len) => ((**elem).clone(), len),
+            _ => unreachable!(),
+        };
+
+        let t = self.peek();
+        let mut data = String::new();
+        let mut count = 0;
+        match t.ty {
+            TokenType::Str(ref s, l) if std::matches!(elem.ty, Ctype::Char) => {
+                self.pos += 1;
+                data.push_str(s);
+                count = if len == 0 || l <= len { l } else { l - 1 };
+            }
+            TokenType::LeftBrace => {
+                self.pos += 1;
+                while !self.consume(TokenType::RightBrace) {
+                    let offset = base + data.chars().count();
+                    if let Ctype::Ary(..) = elem.ty {
+                        data.push_str(&self.global_array_init(&mut elem.clone(), parts, offset));
+                    } else {
+                        data.push_str(&self.scalar_init(&elem, parts, offset));
+                    }
+                    count += 1;
+                    if !self.consume(TokenType::Comma) {
+                        self.expect(TokenType::RightBrace);
+                        break;
+                    }
+                }
+            }
+            _ => t.bad_token("array initializer must be enclosed in braces"),
+        }
+
+        if len == 0 {
+            *ty = Type::ary_of(Box::new(elem), count);
+        } else if count > len {
+            t.bad_token("excess elements in array initializer");
+        }
+        data.extend((data.chars().count()..ty.size).map(|_| '\0'));
+        data.chars().take(ty.size).collect()
+    }
+
+    fn compound_stmt(&mut self) -> Node {
+        self.in_new_scope(|p| {
+            let mut stmts = vec![];
+            while !p.consume(TokenType::RightBrace) {
+                stmts.push(p.stmt());
+            }
+            let cleanups = mem::take(&mut p.env.cleanups);
+            stmts.extend(cleanups.into_iter().rev());
+            Node::new(NodeType::CompStmt(stmts))
+        })
+    }
+
+    fn toplevel(&mut self) -> Vec<Node> {
+        while self.consume(TokenType::Extension) {}
         let is_typedef = self.consume(TokenType::Typedef);
         let is_extern = self.consume(TokenType::Extern);
 
-        let mut ty = self.ctype();
-        let t = &self.tokens[self.pos];
-        let name: String;
-        if let TokenType::Ident(ref name2) = t.ty {
-            name = name2.clone();
-        } else {
-            t.bad_token("function or variable name expected");
+        // A static function or variable is not visible to other files.
+        // `attrs` collects the attributes of a function, which may also
+        // follow its parameter list.
+        let mut is_inline = false;
+        let mut is_static = false;
+        let mut attrs = Type::default();
+        loop {
+            if self.consume(TokenType::Inline) {
+                is_inline = true;
+            } else if self.consume(TokenType::Noreturn) {
+                attrs.is_noreturn = true;
+            } else if self.peek().ty == TokenType::Attribute {
+                self.attributes(&mut attrs);
+            } else if self.consume(TokenType::Static) {
+                is_static = true;
+            } else {
+                break;
+            }
+        }
+
+        let base = self.type_specifier();
+        let mut ty = self.pointer(base.clone());
+        if self.consume(TokenType::Semicolon) {
+            return vec![];
         }
-        self.pos += 1;
 
         // Function
-        if self.consume(TokenType::LeftParen) {
-            let mut args = vec![];
-            if !self.consume(TokenType::RightParen) {
-                args.push(self.param_declaration());
-                while self.consume(TokenType::Comma) {
-                    args.push(self.param_declaration());
+        let t = self.peek();
+        if std::matches!(t.ty, TokenType::Ident(_))
+            && self.tokens[self.pos + 1].ty == TokenType::LeftParen
+        {
+            let name = self.global_name();
+            self.pos += 1;
+            let (args, is_void, is_variadic) = self.params();
+            let params: Vec<Type> = args.iter().map(|arg| (*arg.ty).clone()).collect();
+            self.attributes(&mut attrs);
+            if is_static {
+                self.statics.insert(name.clone());
+            }
+
+            // Every declaration that lists the parameters must agree with
+            // the previous ones on their number.
+            let is_specified =
+                is_void || !params.is_empty() || self.peek().ty == TokenType::LeftBrace;
+            if let Some(Ctype::Func(_, Some(prev), prev_variadic)) =
+                self.env.vars.get(&name).map(|ty| &ty.ty)
+            {
+                if is_specified && (prev.len() != params.len() || *prev_variadic != is_variadic) {
+                    t.bad_token(&format!("conflicting types for {}", name));
                 }
-                self.expect(TokenType::RightParen);
             }
 
             if self.consume(TokenType::Semicolon) {
+                let params = if is_void || !params.is_empty() {
+                    Some(params)
+                } else {
+                    None
+                };
                 let mut node = Node::new(NodeType::Decl(name));
-                node.ty = Box::new(Type::new(Ctype::Func(Box::new(ty)), 0));
-                return Some(node);
+                node.ty = Box::new(Type::new(Ctype::Func(Box::new(ty), params, is_variadic), 0));
+                node.ty.is_noreturn = attrs.is_noreturn;
+                self.declare(&node);
+                return vec![node];
             }
 
-            let t = &self.tokens[self.pos];
+            let name_token = t.clone();
+            let t = self.peek();
             self.expect(TokenType::LeftBrace);
             if is_typedef {
                 t.bad_token("typedef {} has function definition");
             }
-            let body = self.compound_stmt();
-
-            let mut node = Node::new(NodeType::Func(name, args, Box::new(body), 0));
-            node.ty = Box::new(Type::new(Ctype::Func(Box::new(ty)), 0));
-            return Some(node);
+            let mut func_ty = Type::new(
+                Ctype::Func(Box::new(ty.clone()), Some(params), is_variadic),
+                0,
+            );
+            func_ty.is_noreturn = attrs.is_noreturn;
+            self.env.vars.insert(name.clone(), func_ty.clone());
+            let body = self.in_new_scope(|p| {
+                p.env.ret_ty = Some(ty);
+                args.iter().for_each(|arg| p.declare(arg));
+                p.compound_stmt()
+            });
+
+            let is_static = self.statics.contains(&name);
+            let mut node = Node::new(NodeType::Func(
+                name,
+                args,
+                Box::new(body),
+                0,
+                is_inline,
+                is_static,
+            ));
+            node.ty = Box::new(func_ty);
+            node.token = Some(name_token);
+            return vec![node];
         }
 
-        ty = self.read_array(Box::new(ty));
+        // Variables, e.g. `int a = 1, *b = &a;` or `int (*fp)(int);`
+        let mut nodes = vec![];
+        loop {
+            nodes.extend(self.global_var(ty, is_typedef, is_extern, is_static));
+            if !self.consume(TokenType::Comma) {
+                break;
+            }
+            ty = self.pointer(base.clone());
+        }
         self.expect(TokenType::Semicolon);
+        nodes
+    }
+
+    fn global_name(&mut self) -> String {
+        let t = self.next();
+        match t.ty {
+            TokenType::Ident(ref name) => name.clone(),
+            _ => t.bad_token("function or variable name expected"),
+        }
+    }
+
+    // Reads the rest of a global variable declarator after its `*`s,
+    // with its initializer.
+    fn global_var(
+        &mut self,
+        ty: Type,
+        is_typedef: bool,
+        is_extern: bool,
+        is_static: bool,
+    ) -> Option<Node> {
+        let node = self.direct_decl(Box::new(ty));
+        let name = match node.op {
+            NodeType::Vardef(name, _, _) => name,
+            _ => unreachable!(),
+        };
+        self.global_init(*node.ty, name, is_typedef, is_extern, is_static)
+    }
+
+    // Reads the initializer of a global or static variable. A typedef
+    // only defines a name.
+    fn global_init(
+        &mut self,
+        mut ty: Type,
+        name: String,
+        is_typedef: bool,
+        is_extern: bool,
+        is_static: bool,
+    ) -> Option<Node> {
+        // A constant initializer becomes the bytes of the variable. The
+        // other parts, such as addresses, are resolved in sema.
+        let mut init = None;
+        let mut data = String::new();
+        if !is_typedef && self.consume(TokenType::Equal) {
+            let t = self.peek();
+            let mut parts = vec![];
+            match ty.ty {
+                Ctype::Ary(..) => data = self.global_array_init(&mut ty, &mut parts, 0),
+                Ctype::Int | Ctype::Char | Ctype::Bool | Ctype::Ptr(_) => {
+                    data = self.scalar_init(&ty, &mut parts, 0)
+                }
+                _ => t.bad_token("invalid initializer"),
+            }
+            if !parts.is_empty() {
+                init = Some(Box::new(Node::new(NodeType::GlobalInit(parts))));
+            }
+        }
 
         if is_typedef {
             self.env.typedefs.insert(name.clone(), ty.clone());
@@ -899,16 +2307,14 @@ impl<'a> Parser<'a> {
         }
 
         // Global variable
-        let mut node = Node::new(NodeType::Vardef(
-            name,
-            None,
-            if is_extern {
-                Scope::Global(String::new(), 0, true)
-            } else {
-                Scope::Global(String::new(), ty.size, false)
-            },
-        ));
+        let scope = if is_extern && data.is_empty() && init.is_none() {
+            Scope::Global(String::new(), 0, true, is_static)
+        } else {
+            Scope::Global(data, ty.size, false, is_static)
+        };
+        let mut node = Node::new(NodeType::Vardef(name, init, scope));
         node.ty = Box::new(ty);
+        self.declare(&node);
         Some(node)
     }
 }
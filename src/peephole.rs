@@ -0,0 +1,99 @@
+use crate::cfg::Cfg;
+use crate::gen_ir::{Function, IROp, IR};
+
+use std::collections::HashMap;
+
+// Peephole optimizations on the IR.
+//
+// Multiplication and division by a constant power of two are replaced
+// with shifts:
+//
+//   MOV r2, 8            MOV r2, 8
+//   MUL r1, r2     =>    SHL r1, 3
+//
+// The constant is tracked from its MOV within the same basic block. The
+// MOV itself becomes dead and is removed by the CSE pass that follows.
+
+// Returns k if x is 2^k for k > 0.
+fn log2(x: i64) -> Option<usize> {
+    if x > 1 && x.count_ones() == 1 {
+        Some(x.trailing_zeros() as usize)
+    } else {
+        None
+    }
+}
+
+fn reg_imm(op: IROp, lhs: usize, imm: usize) -> IR {
+    IR {
+        op,
+        lhs: Some(lhs),
+        rhs: Some(imm),
+    }
+}
+
+fn strength_reduce(ir: &mut Vec<IR>, start: usize, end: usize) {
+    use self::IROp::*;
+    let mut consts: HashMap<usize, i64> = HashMap::new();
+    let mut i = start;
+    let mut end = end;
+
+    while i < end {
+        let (lhs, rhs) = (ir[i].lhs, ir[i].rhs);
+        let rhs_const = rhs.and_then(|r| consts.get(&r)).cloned().and_then(log2);
+        match ir[i].op {
+            Imm => {
+                consts.insert(lhs.unwrap(), rhs.unwrap() as i64);
+                i += 1;
+                continue;
+            }
+            Mul if rhs_const.is_some() => {
+                ir[i] = reg_imm(ShlImm, lhs.unwrap(), rhs_const.unwrap());
+            }
+            // c * x is computed as x << k into c's register.
+            Mul if lhs
+                .and_then(|r| consts.get(&r))
+                .cloned()
+                .and_then(log2)
+                .is_some() =>
+            {
+                let k = log2(consts[&lhs.unwrap()]).unwrap();
+                ir[i] = IR { op: Mov, lhs, rhs };
+                ir.insert(i + 1, reg_imm(ShlImm, lhs.unwrap(), k));
+                i += 1;
+                end += 1;
+            }
+            Div(size) if rhs_const.is_some() => {
+                ir[i] = reg_imm(DivPow2(size), lhs.unwrap(), rhs_const.unwrap());
+            }
+            UDiv(size) if rhs_const.is_some() => {
+                ir[i] = reg_imm(UDivPow2(size), lhs.unwrap(), rhs_const.unwrap());
+            }
+            _ => (),
+        }
+
+        // Anything else that writes a register makes it non-constant.
+        match ir[i].op {
+            Store(..) | StoreArg(_) | Memcpy(_) | Memset(_) | Jmp | JmpReg | If(_) | Unless(_)
+            | Return | Label | ArgArea | StackArg | Nop => (),
+            _ => {
+                consts.remove(&lhs.unwrap());
+            }
+        }
+        i += 1;
+    }
+}
+
+fn optimize(f: &mut Function) {
+    // Rewriting a block may insert instructions, so the blocks are
+    // visited from the last one.
+    let cfg = Cfg::new(f);
+    for bb in cfg.blocks.iter().rev() {
+        strength_reduce(&mut f.ir, bb.start, bb.end);
+    }
+}
+
+pub fn peephole(fns: &mut Vec<Function>) {
+    for f in fns {
+        optimize(f);
+    }
+}
@@ -1,35 +1,29 @@
 // C preprocessor
 
-use crate::token::{tokenize, Token};
+use crate::token::{error, tokenize, Token};
 use crate::TokenType;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
 use std::mem;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn preprocess(tokens: Vec<Token>, ctx: &mut Preprocessor) -> Vec<Token> {
     ctx.preprocess_impl(tokens)
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Env {
     input: Vec<Token>,
     output: Vec<Token>,
     pos: usize,
+    conds: Vec<Token>, // The open #ifdef and #ifndef directives
     next: Option<Box<Env>>,
 }
 
-impl Default for Env {
-    fn default() -> Env {
-        Env {
-            input: vec![],
-            output: vec![],
-            pos: 0,
-            next: None,
-        }
-    }
-}
-
 impl Env {
     pub fn new(input: Vec<Token>, next: Option<Box<Env>>) -> Self {
         Env {
@@ -130,13 +124,28 @@ impl Macro {
 pub struct Preprocessor {
     macros: HashMap<String, Macro>,
     pub env: Box<Env>,
+    // Files that contained `#pragma once`
+    once: HashSet<PathBuf>,
+    // __DATE__ and __TIME__
+    date: String,
+    time: String,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Preprocessor {
     pub fn new() -> Self {
+        let (date, time) = timestamp();
         Preprocessor {
             macros: HashMap::new(),
             env: Box::new(Env::new(vec![], None)),
+            once: HashSet::new(),
+            date,
+            time,
         }
     }
 
@@ -145,7 +154,7 @@ impl Preprocessor {
             return None;
         }
         let pos = self.env.pos;
-        let t = Some(mem::replace(&mut self.env.input[pos], Token::default()));
+        let t = Some(std::mem::take(&mut self.env.input[pos]));
         self.env.pos += 1;
         t
     }
@@ -155,7 +164,7 @@ impl Preprocessor {
     }
 
     fn get(&mut self, ty: TokenType, msg: &str) -> Token {
-        let t = self.next().expect(msg);
+        let t = self.next().unwrap_or_else(|| error(msg));
         if t.ty != ty {
             t.bad_token(msg);
         }
@@ -163,7 +172,7 @@ impl Preprocessor {
     }
 
     fn ident(&mut self, msg: &str) -> String {
-        let t = self.next().expect(msg);
+        let t = self.next().unwrap_or_else(|| error(msg));
         match t.ty {
             TokenType::Ident(s) | TokenType::Str(s, _) => s,
             _ => t.bad_token(msg),
@@ -200,15 +209,13 @@ impl Preprocessor {
     fn read_one_arg(&mut self) -> Vec<Token> {
         let mut v = vec![];
         let msg = "unclosed macro argument";
-        let start = self.peek().expect(msg).clone();
+        let start = self.peek().unwrap_or_else(|| error(msg)).clone();
         let mut level = 0;
 
         while !self.eof() {
-            let t = self.peek().expect(msg).clone();
-            if level == 0 {
-                if t.ty == TokenType::RightParen || t.ty == TokenType::Comma {
-                    return v;
-                }
+            let t = self.peek().unwrap_or_else(|| error(msg)).clone();
+            if level == 0 && (t.ty == TokenType::RightParen || t.ty == TokenType::Comma) {
+                return v;
             }
 
             self.next();
@@ -248,23 +255,27 @@ impl Preprocessor {
         Token::new(TokenType::Str(sb, len), 0, filename, buf)
     }
 
-    fn add_special_macro(&mut self, t: &Token) -> bool {
-        if t.is_ident("__LINE__") {
-            self.env.output.push(Token::new(
-                TokenType::Num(t.get_line_number() as i32),
-                0,
-                t.filename.clone(),
-                t.buf.clone(),
-            ));
-            true
-        } else {
-            false
-        }
+    // `start` is the token the expansion started from, so __LINE__ in a
+    // macro body expands to the line of the macro invocation.
+    fn add_special_macro(&mut self, t: &Token, start: &Token) -> bool {
+        let ty = match t.ty {
+            TokenType::Ident(ref name) if name == "__LINE__" => {
+                TokenType::Num(start.get_line_number() as i64, 4, false)
+            }
+            TokenType::Ident(ref name) if name == "__FILE__" => string(&start.filename),
+            TokenType::Ident(ref name) if name == "__DATE__" => string(&self.date),
+            TokenType::Ident(ref name) if name == "__TIME__" => string(&self.time),
+            _ => return false,
+        };
+        self.env
+            .output
+            .push(Token::new(ty, 0, t.filename.clone(), t.buf.clone()));
+        true
     }
 
-    fn apply_objlike(&mut self, tokens: Vec<Token>) {
+    fn apply_objlike(&mut self, tokens: Vec<Token>, start: &Token) {
         for t in tokens {
-            if self.add_special_macro(&t) {
+            if self.add_special_macro(&t, start) {
                 continue;
             } else {
                 self.env.output.push(t);
@@ -280,7 +291,7 @@ impl Preprocessor {
         }
 
         for t in tokens {
-            if self.add_special_macro(&t) {
+            if self.add_special_macro(&t, start) {
                 continue;
             }
 
@@ -301,7 +312,7 @@ impl Preprocessor {
 
     fn apply(&mut self, m: Macro, start: &Token) {
         match m.ty {
-            MacroType::Objlike => self.apply_objlike(m.tokens),
+            MacroType::Objlike => self.apply_objlike(m.tokens, start),
             MacroType::Funclike(ref params) => self.apply_funclike(m.tokens, params, start),
         }
     }
@@ -336,16 +347,92 @@ impl Preprocessor {
 
     fn include(&mut self) {
         let path = self.ident("string expected");
-        let t = self.next().expect("newline expected");
+        let t = self.next().unwrap_or_else(|| error("newline expected"));
         if t.ty != TokenType::NewLine {
             t.bad_token("newline expected");
         }
+        if self.once.contains(&canonical_path(&path)) {
+            return;
+        }
         let mut v = tokenize(path, self);
+        // The included tokens continue this file, so drop their Eof.
+        v.pop();
         self.env.output.append(&mut v);
     }
 
+    // The rest of the line as written, e.g. the message of `#error`.
+    fn line_text(&mut self) -> String {
+        let tokens = self.read_until_eol();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => first.buf[first.start..last.end].iter().collect(),
+            _ => String::new(),
+        }
+    }
+
+    // Skips the tokens of a group whose condition is false, up to its
+    // #else or #endif. Returns true if it stopped at an #else, whose
+    // group is then read instead.
+    fn skip_group(&mut self, start: &Token) -> bool {
+        let mut depth = 0;
+        while let Some(t) = self.next() {
+            if t.ty == TokenType::Eof {
+                start.bad_token("unterminated conditional directive");
+            }
+            if t.ty != TokenType::HashMark {
+                continue;
+            }
+            match self.next() {
+                Some(ref t) if t.is_ident("ifdef") || t.is_ident("ifndef") => depth += 1,
+                Some(ref t) if t.is_ident("endif") && depth > 0 => depth -= 1,
+                Some(ref t) if t.is_ident("endif") => {
+                    self.read_until_eol();
+                    return false;
+                }
+                Some(ref t) if t.ty == TokenType::Else && depth == 0 => {
+                    self.read_until_eol();
+                    return true;
+                }
+                _ => (),
+            }
+        }
+        start.bad_token("unterminated conditional directive");
+    }
+
+    fn ifdef(&mut self, t: Token, expect_defined: bool) {
+        let name = self.ident("macro name expected");
+        self.read_until_eol();
+        if self.macros.contains_key(&name) == expect_defined || self.skip_group(&t) {
+            self.env.conds.push(t);
+        }
+    }
+
+    // The group before an #else was read, so the one after it is skipped.
+    fn else_group(&mut self, t: &Token) {
+        match self.env.conds.pop() {
+            Some(start) if !self.skip_group(&start) => (),
+            Some(_) => t.bad_token("#else after #else"),
+            None => t.bad_token("#else without #ifdef"),
+        }
+    }
+
+    fn endif(&mut self, t: &Token) {
+        if self.env.conds.pop().is_none() {
+            t.bad_token("#endif without #ifdef");
+        }
+        self.read_until_eol();
+    }
+
+    fn pragma(&mut self, t: &Token) {
+        let tokens = self.read_until_eol();
+        if tokens.len() == 1 && tokens[0].is_ident("once") {
+            self.once.insert(canonical_path(&t.filename));
+            return;
+        }
+        t.warn("unknown pragma ignored");
+    }
+
     fn preprocess_impl(&mut self, tokens: Vec<Token>) -> Vec<Token> {
-        self.env = Box::new(Env::new(tokens, Some(self.env.clone())));
+        *self.env = Env::new(tokens, Some(self.env.clone()));
 
         while !self.eof() {
             let t = self.next().unwrap();
@@ -356,6 +443,9 @@ impl Preprocessor {
                 macro_name = None;
             }
             if let Some(name) = macro_name {
+                if self.add_special_macro(&t, &t) {
+                    continue;
+                }
                 if let Some(m) = self.macros.get(&name).cloned() {
                     self.apply(m, &t);
                 } else {
@@ -369,19 +459,86 @@ impl Preprocessor {
                 continue;
             }
 
+            if self.consume(TokenType::Else) {
+                self.else_group(&t);
+                continue;
+            }
+
             let ident = self.ident("identifier expected");
             if &*ident == "define" {
                 self.define();
             } else if &*ident == "include" {
                 self.include();
+            } else if &*ident == "pragma" {
+                self.pragma(&t);
+            } else if &*ident == "ifdef" {
+                self.ifdef(t, true);
+            } else if &*ident == "ifndef" {
+                self.ifdef(t, false);
+            } else if &*ident == "endif" {
+                self.endif(&t);
+            } else if &*ident == "error" {
+                let msg = self.line_text();
+                t.bad_token(&format!("#error {}", msg));
+            } else if &*ident == "warning" {
+                let msg = self.line_text();
+                t.warn(&format!("#warning {}", msg));
             } else {
                 t.bad_token("unknown directive");
             }
         }
 
+        if let Some(t) = self.env.conds.last() {
+            t.bad_token("unterminated conditional directive");
+        }
+
         let mut output = vec![];
         mem::swap(&mut self.env.output, &mut output);
         self.env = self.env.next.take().unwrap();
         output
     }
 }
+
+fn string(s: &str) -> TokenType {
+    TokenType::Str(s.into(), s.len() + 1)
+}
+
+// The time of the compilation in UTC as __DATE__ ("Jan  2 2006") and
+// __TIME__ ("15:04:05"). Like gcc, SOURCE_DATE_EPOCH overrides it so
+// that builds can be reproducible.
+fn timestamp() -> (String, String) {
+    let secs = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        });
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Converts days since 1970-01-01 to a civil date, counting from
+    // 0000-03-01 so that the leap day ends each 400-year era.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let doe = days % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = (mp + 2) % 12;
+    let year = era * 400 + yoe + (month < 2) as u64;
+
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let date = format!("{} {:2} {}", MONTHS[month as usize], day, year);
+    let time = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+    (date, time)
+}
+
+// Different spellings of the same file, such as "a.h" and "./a.h", map
+// to the same path.
+fn canonical_path(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
@@ -2,8 +2,6 @@ use crate::gen_ir::{Function, IROp, IRType, IR};
 use crate::irdump::IRInfo;
 use crate::REGS_N;
 
-use std::sync::Mutex;
-
 // Quoted from 9cc
 // > Register allocator.
 //
@@ -17,91 +15,100 @@ use std::sync::Mutex;
 // > practically we don't have to think about the case in which
 // > registers are exhausted and need to be spilled to memory.
 
-lazy_static! {
-    static ref USED: Mutex<[bool; REGS_N]> = Mutex::new([false; REGS_N]);
-    static ref REG_MAP: Mutex<[Option<usize>; 8192]> = Mutex::new([None; 8192]);
-}
-
-fn used_get(i: usize) -> bool {
-    USED.lock().unwrap()[i]
+struct RegAlloc {
+    used: [bool; REGS_N],
+    reg_map: Vec<Option<usize>>,
 }
 
-fn used_set(i: usize, val: bool) {
-    USED.lock().unwrap()[i] = val;
-}
-
-fn reg_map_get(i: usize) -> Option<usize> {
-    REG_MAP.lock().unwrap().get(i).cloned().unwrap()
-}
-
-fn reg_map_set(i: usize, val: usize) {
-    REG_MAP.lock().unwrap()[i] = Some(val);
-}
-
-fn alloc(ir_reg: usize) -> usize {
-    if REG_MAP.lock().unwrap().len() <= ir_reg {
-        panic!("program too big");
+impl RegAlloc {
+    fn new() -> Self {
+        RegAlloc {
+            used: [false; REGS_N],
+            reg_map: vec![],
+        }
     }
 
-    if let Some(r) = reg_map_get(ir_reg) {
-        assert!(used_get(r));
-        return r;
-    }
+    fn alloc(&mut self, ir_reg: usize) -> usize {
+        if self.reg_map.len() <= ir_reg {
+            self.reg_map.resize(ir_reg + 1, None);
+        }
+
+        if let Some(r) = self.reg_map[ir_reg] {
+            assert!(self.used[r]);
+            return r;
+        }
 
-    for i in 0..REGS_N {
-        if used_get(i) {
-            continue;
+        for i in 0..REGS_N {
+            if self.used[i] {
+                continue;
+            }
+            self.reg_map[ir_reg] = Some(i);
+            self.used[i] = true;
+            return i;
         }
-        reg_map_set(ir_reg, i);
-        used_set(i, true);
-        return i;
+        panic!("register exhauseted: {}", ir_reg);
     }
-    panic!("register exhauseted: {}", ir_reg);
-}
 
-fn visit(irv: &mut Vec<IR>) {
-    use self::IRType::*;
+    fn visit(&mut self, irv: &mut Vec<IR>) {
+        use self::IRType::*;
 
-    for item in irv {
-        let mut ir = item.clone();
-        let info = &IRInfo::from(&ir.op);
+        for item in irv {
+            let mut ir = item.clone();
+            let info = &IRInfo::from(&ir.op);
 
-        match info.ty {
-            Reg | RegImm | RegLabel | LabelAddr => ir.lhs = Some(alloc(ir.lhs.unwrap())),
-            Mem | RegReg => {
-                ir.lhs = Some(alloc(ir.lhs.unwrap()));
-                ir.rhs = Some(alloc(ir.rhs.unwrap()));
-            }
-            Call => {
-                ir.lhs = Some(alloc(ir.lhs.unwrap()));
-                match ir.op {
-                    IROp::Call(name, nargs, args) => {
-                        let mut args_new: [usize; 6] = [0; 6];
-                        for i in 0..nargs {
-                            args_new[i] = alloc(args[i]);
+            match info.ty {
+                Reg | RegImm | RegLabel | LabelAddr => ir.lhs = Some(self.alloc(ir.lhs.unwrap())),
+                Mem | RegReg => {
+                    ir.lhs = Some(self.alloc(ir.lhs.unwrap()));
+                    ir.rhs = Some(self.alloc(ir.rhs.unwrap()));
+                }
+                Call => {
+                    ir.lhs = Some(self.alloc(ir.lhs.unwrap()));
+                    match ir.op {
+                        IROp::Call(name, nargs, args) => {
+                            let mut args_new: [usize; 6] = [0; 6];
+                            for i in 0..nargs.min(6) {
+                                args_new[i] = self.alloc(args[i]);
+                            }
+                            ir.op = IROp::Call(name, nargs, args_new);
+                        }
+                        IROp::TailCall(name, nargs, args) => {
+                            let mut args_new: [usize; 6] = [0; 6];
+                            for i in 0..nargs.min(6) {
+                                args_new[i] = self.alloc(args[i]);
+                            }
+                            ir.op = IROp::TailCall(name, nargs, args_new);
+                        }
+                        IROp::CallPtr(nargs, args) => {
+                            let mut args_new: [usize; 6] = [0; 6];
+                            for i in 0..nargs.min(6) {
+                                args_new[i] = self.alloc(args[i]);
+                            }
+                            ir.op = IROp::CallPtr(nargs, args_new);
+                            ir.rhs = Some(self.alloc(ir.rhs.unwrap()));
                         }
-                        ir.op = IROp::Call(name, nargs, args_new);
+                        _ => unreachable!(),
                     }
-                    _ => unreachable!(),
                 }
+                _ => (),
             }
-            _ => (),
-        }
 
-        if ir.op == IROp::Kill {
-            let lhs = ir.lhs.unwrap();
-            assert!(used_get(lhs));
-            used_set(lhs, false);
-            ir.op = IROp::Nop;
+            if ir.op == IROp::Kill {
+                let lhs = ir.lhs.unwrap();
+                assert!(self.used[lhs]);
+                self.used[lhs] = false;
+                ir.op = IROp::Nop;
+            }
+            *item = ir;
         }
-        *item = ir;
     }
 }
 
 pub fn alloc_regs(fns: &mut Vec<Function>) {
+    let mut ra = RegAlloc::new();
     for f in fns {
-        *USED.lock().unwrap() = [false; REGS_N];
+        ra.used = [false; REGS_N];
 
-        visit(&mut f.ir);
+        ra.visit(&mut f.ir);
     }
 }
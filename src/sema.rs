@@ -1,11 +1,12 @@
 use crate::matches;
-use crate::parse::{Node, NodeType};
+use crate::parse::{eval, int_data, Node, NodeType};
+use crate::token::error;
+use crate::typing::{self, arith_conv, binop_type, is_integer};
 use crate::util::roundup;
-use crate::{Ctype, Scope, TokenType, Type, Var};
+use crate::{Ctype, Scope, Token, TokenType, Type, Var};
 
 use std::collections::HashMap;
 use std::mem;
-use std::sync::Mutex;
 
 // Quoted from 9cc
 // > Semantics analyzer. This pass plays a few important roles as shown
@@ -32,12 +33,8 @@ fn swap(p: &mut Node, q: &mut Node) {
     mem::swap(p, q);
 }
 
-lazy_static! {
-    static ref GLOBALS: Mutex<Vec<Var>> = Mutex::new(vec![]);
-    static ref ENV: Mutex<Env> = Mutex::new(Env::new(None));
-    static ref STRLABEL: Mutex<usize> = Mutex::new(0);
-    static ref STACKSIZE: Mutex<usize> = Mutex::new(0);
-}
+// Functions whose bodies are larger than this many nodes are not inlined.
+const INLINE_LIMIT: usize = 64;
 
 #[derive(Debug, Clone)]
 struct Env {
@@ -54,347 +51,1116 @@ impl Env {
     }
 }
 
-fn into_new_range<T: Sized>(param: T, f: Box<dyn Fn(T) -> T>) -> T {
-    let env = ENV.lock().unwrap().clone();
-    *ENV.lock().unwrap() = Env::new(Some(Box::new(env)));
-    let ret = f(param);
-    // Rollback
-    let env = ENV.lock().unwrap().clone();
-    *ENV.lock().unwrap() = *env.next.unwrap();
-    ret
+struct Sema {
+    globals: Vec<Var>,
+    env: Env,
+    strlabel: usize,
+    nstatic: usize, // Static locals, which are named .L.<name>.<n>
+    stacksize: usize,
+    inline_fns: HashMap<String, (Vec<Node>, Node)>,
+    inlining: Vec<String>,
+    inline_label: usize,
+    func_name: String,       // The function being walked, for __func__
+    in_init: bool,           // Walking the assignments of an initializer
+    addr_of_subscript: bool, // Walking `a[i]` in `&a[i]`, which may be a[len]
 }
 
-fn find_var(name: &str) -> Option<Var> {
-    let env = ENV.lock().unwrap().clone();
-    let mut next: &Option<Box<Env>> = &Some(Box::new(env));
-    loop {
-        if let Some(ref e) = next {
-            let var = e.vars.get(name);
-            if var.is_some() {
-                return var.cloned();
-            }
-            next = &e.next;
-        } else {
-            return None;
+fn maybe_decay(base: Node, decay: bool) -> Node {
+    if !decay || !std::matches!(base.ty.ty, Ctype::Ary(..) | Ctype::Func(..)) {
+        return base;
+    }
+    let ty = Box::new(typing::decay(&base.ty));
+    let mut node = Node::new(NodeType::Addr(Box::new(base)));
+    node.ty = ty;
+    node
+}
+
+// The left operand of an assignment does not decay, and neither does
+// that of + or -, whose array length is checked against the index.
+fn decays_lhs(op: &TokenType) -> bool {
+    use self::TokenType::*;
+    !std::matches!(
+        op,
+        Plus | Minus
+            | Equal
+            | AddEQ
+            | SubEQ
+            | MulEQ
+            | DivEQ
+            | ModEQ
+            | ShlEQ
+            | ShrEQ
+            | BitandEQ
+            | XorEQ
+            | BitorEQ
+    )
+}
+
+// `a[i]` is read as `*(a + i)`, with the `[` token on the addition.
+fn is_subscript(node: &Node) -> bool {
+    match node.op {
+        NodeType::Deref(ref addr) => {
+            std::matches!(addr.op, NodeType::BinOp(TokenType::Plus, ..))
+                && std::matches!(addr.token, Some(ref t) if t.ty == TokenType::LeftBracket)
         }
+        _ => false,
     }
 }
 
-fn maybe_decay(base: Node, decay: bool) -> Node {
-    if !decay {
-        return base;
+fn is_func_ptr(ty: &Type) -> bool {
+    match ty.ty {
+        Ctype::Ptr(ref ptr_to) => matches!(ptr_to.ty, Ctype::Func(..)),
+        _ => false,
     }
+}
 
-    if let Ctype::Ary(ary_of, _) = base.ty.ty.clone() {
-        let mut node = Node::new(NodeType::Addr(Box::new(base)));
-        node.ty = Box::new(Type::ptr_to(ary_of.clone()));
-        node
-    } else {
-        base
+// A variadic function takes at least as many arguments as it has
+// parameters. `name` is empty for a call through a pointer.
+fn check_arg_count(
+    node: &Node,
+    name: &str,
+    params: &Option<Vec<Type>>,
+    is_variadic: bool,
+    nargs: usize,
+) {
+    if let Some(params) = params {
+        if nargs < params.len() || (!is_variadic && nargs > params.len()) {
+            let callee = if name.is_empty() {
+                "".to_string()
+            } else {
+                format!(" to {}", name)
+            };
+            let at_least = if is_variadic { "at least " } else { "" };
+            let msg = format!(
+                "wrong number of arguments{}: {}{} expected, but got {}",
+                callee,
+                at_least,
+                params.len(),
+                nargs
+            );
+            error_at(&node.token, &msg);
+        }
     }
 }
 
-fn check_lval(node: &Node) {
+// `token` is that of the operator applied to node.
+fn check_lval(node: &Node, token: &Option<Token>) {
     let op = &node.op;
     if !matches!(op, NodeType::Lvar(_))
         && !matches!(op, NodeType::Gvar(_, _, _))
         && !matches!(op, NodeType::Deref(_))
         && !matches!(op, NodeType::Dot(_, _, _))
+        && !matches!(op, NodeType::CompLit(_, _))
     {
-        panic!("not an lvalue: {:?}", node.op);
+        error_at(token, &format!("not an lvalue: {:?}", node.op));
     }
 }
 
-fn walk(mut node: Node, decay: bool) -> Node {
-    use self::NodeType::*;
-    let op = node.op.clone();
-    match op {
-        Num(_) | Null | Break => (),
-        Str(data, len) => {
-            // Quoted from 9cc
-            // > A string literal is converted to a reference to an anonymous
-            // > global variable of type char array.
-            let name = format!(".L.str{}", *STRLABEL.lock().unwrap());
-            *STRLABEL.lock().unwrap() += 1;
-            let var = Var::new_global(node.ty.clone(), name, data, len, false);
-            let name = var.name.clone();
-            GLOBALS.lock().unwrap().push(var);
-
-            let mut ret = Node::new(NodeType::Gvar(name, "".into(), len));
-            ret.ty = node.ty;
-            return maybe_decay(ret, decay);
+// Converts an integer operand to the type `ty` it is computed at, so
+// that the operator sees the common type of both operands. A narrower
+// `ty` is left to the store that truncates the value.
+fn convert(node: Node, ty: &Type) -> Node {
+    if !is_integer(&node.ty)
+        || !is_integer(ty)
+        || node.ty.size > ty.size
+        || (node.ty.size == ty.size && node.ty.is_unsigned == ty.is_unsigned)
+    {
+        return node;
+    }
+    let mut cast = new_cast(node);
+    cast.ty = Box::new(Type {
+        bitfield: None,
+        ..ty.clone()
+    });
+    cast
+}
+
+fn new_cast(node: Node) -> Node {
+    Node::new(NodeType::Cast(Box::new(node)))
+}
+
+// Returns the operands of an arithmetic operator converted to their
+// common type, and the type.
+fn usual_arith_conv(lhs: Node, rhs: Node) -> (Box<Node>, Box<Node>, Box<Type>) {
+    let ty = arith_conv(&lhs.ty, &rhs.ty);
+    (Box::new(convert(lhs, &ty)), Box::new(convert(rhs, &ty)), ty)
+}
+
+// Pointer arithmetic scales by the size of the pointee, which must be
+// known. void * is allowed with a warning, as in GCC.
+fn check_ptr_arith(node: &Node, ptr: &Type) {
+    if let Ctype::Ptr(ref ptr_to) = ptr.ty {
+        if ptr_to.is_incomplete() {
+            error_at(&node.token, "arithmetic on pointer to incomplete type");
         }
-        Ident(ref name) => {
-            if let Some(var) = find_var(name) {
-                match var.scope {
-                    Scope::Local(offset) => {
-                        let mut ret = Node::new(NodeType::Lvar(Scope::Local(offset)));
-                        ret.ty = var.ty.clone();
-                        return maybe_decay(ret, decay);
-                    }
-                    Scope::Global(ref data, len, _) => {
-                        let mut ret =
-                            Node::new(NodeType::Gvar(var.name.clone(), data.clone(), len));
-                        ret.ty = var.ty.clone();
-                        return maybe_decay(ret, decay);
-                    }
-                }
-            } else {
-                panic!("undefined variable: {}", name);
+        if let Ctype::Void = ptr_to.ty {
+            warn_at(node, "pointer of type 'void *' used in arithmetic");
+        }
+    }
+}
+
+// Warns of a constant subscript outside an array of known length `len`.
+// Only `a[i]` is checked, since `a + len` is a valid pointer, and so is
+// `&a[len]` if `past_end` is set.
+fn check_index(node: &Node, len: usize, index: &Node, past_end: bool) {
+    let t = match node.token {
+        Some(ref t) if t.ty == TokenType::LeftBracket && len > 0 => t,
+        _ => return,
+    };
+    match eval(index) {
+        Some(i) if i < 0 => t.warn(&format!(
+            "array index {} is before the beginning of the array",
+            i
+        )),
+        Some(i) if i as usize > len || (i as usize == len && !past_end) => {
+            t.warn(&format!("array index {} is past the end of the array", i))
+        }
+        _ => (),
+    }
+}
+
+// `p - q` is the number of elements between two pointers, so the byte
+// difference is divided by the element size.
+fn ptr_diff(lhs: Node, rhs: Node) -> Node {
+    let size = match lhs.ty.ty {
+        Ctype::Ptr(ref ptr_to) => ptr_to.size.max(1),
+        _ => unreachable!(),
+    };
+    let mut diff = Node::new_binop(TokenType::Minus, lhs, rhs);
+    diff.ty = Box::new(Type::ptrdiff_t());
+    let mut node = Node::new_binop(TokenType::Div, diff, Node::new_int(size as i32));
+    node.ty = Box::new(Type::ptrdiff_t());
+    node
+}
+
+// A comparison where the signed operand is converted to unsigned, so that
+// e.g. -1 < 1u is false. `-1L < 1u` is signed, since long holds every
+// unsigned int. Nonnegative constants are exempt, as in gcc.
+fn is_sign_mismatch(lhs: &Node, rhs: &Node) -> bool {
+    if !is_integer(&lhs.ty) || !is_integer(&rhs.ty) || !arith_conv(&lhs.ty, &rhs.ty).is_unsigned {
+        return false;
+    }
+
+    let signed = if lhs.ty.is_unsigned { rhs } else { lhs };
+    if signed.ty.is_unsigned {
+        return false;
+    }
+    match signed.op {
+        NodeType::Num(val) => val < 0,
+        _ => true,
+    }
+}
+
+// Evaluates an address constant, which is the address of a global plus
+// a constant, as (label, addend).
+fn addr_const(node: &Node) -> Option<(String, i64)> {
+    match node.op {
+        NodeType::Addr(ref expr) => lval_addr(expr),
+        NodeType::BinOp(ref op, ref lhs, ref rhs) if matches!(node.ty.ty, Ctype::Ptr(_)) => {
+            let (label, addend) = addr_const(lhs)?;
+            match op {
+                TokenType::Plus => Some((label, addend + eval(rhs)?)),
+                TokenType::Minus => Some((label, addend - eval(rhs)?)),
+                _ => None,
             }
         }
-        Vardef(name, init_may, _) => {
-            let stacksize = *STACKSIZE.lock().unwrap();
-            *STACKSIZE.lock().unwrap() = roundup(stacksize, node.ty.align);
-            *STACKSIZE.lock().unwrap() += node.ty.size;
-            let offset = *STACKSIZE.lock().unwrap();
+        _ => None,
+    }
+}
 
-            ENV.lock().unwrap().vars.insert(
-                name.clone(),
-                Var::new(node.ty.clone(), name.clone(), Scope::Local(offset)),
-            );
+// The address of an lvalue in a global, such as `a[2]` or `s.b`.
+fn lval_addr(node: &Node) -> Option<(String, i64)> {
+    match node.op {
+        NodeType::Gvar(ref name, ..) => Some((name.clone(), 0)),
+        NodeType::Deref(ref expr) => addr_const(expr),
+        NodeType::Dot(ref expr, _, offset) => {
+            let (label, addend) = lval_addr(expr)?;
+            Some((label, addend + offset as i64))
+        }
+        _ => None,
+    }
+}
+
+// Reports an error at `token`, the token of the node being checked.
+// Without one, the error has no position.
+fn error_at(token: &Option<Token>, msg: &str) -> ! {
+    match token {
+        Some(t) => t.bad_token(msg),
+        None => error(msg),
+    }
+}
+
+fn warn_at(node: &Node, msg: &str) {
+    if let Some(ref t) = node.token {
+        t.warn(msg);
+    }
+}
 
-            let mut init = None;
-            if let Some(init2) = init_may {
-                init = Some(Box::new(walk(*init2, true)));
+// Folds a shift of constants at the width of its type. The count must
+// be less than the width.
+fn fold_shift(op: &TokenType, val: i64, count: u32, ty: Box<Type>) -> Node {
+    let val = match (op, ty.size == 8, ty.is_unsigned) {
+        (TokenType::SHL, true, _) => val << count,
+        (TokenType::SHL, false, true) => i64::from((val as u32) << count),
+        (TokenType::SHL, false, false) => i64::from((val as i32) << count),
+        (_, true, true) => (val as u64 >> count) as i64,
+        (_, true, false) => val >> count,
+        (_, false, true) => i64::from(val as u32 >> count),
+        (_, false, false) => i64::from(val as i32 >> count),
+    };
+    let mut node = Node::new(NodeType::Num(val));
+    node.ty = ty;
+    node
+}
+
+// A pointer compared with an integer other than the null pointer constant.
+fn is_ptr_int_mismatch(lhs: &Node, rhs: &Node) -> bool {
+    let int = match (&lhs.ty.ty, &rhs.ty.ty) {
+        (Ctype::Ptr(_), _) if is_integer(&rhs.ty) => rhs,
+        (_, Ctype::Ptr(_)) if is_integer(&lhs.ty) => lhs,
+        _ => return false,
+    };
+    match int.op {
+        NodeType::Num(val) => val != 0,
+        _ => true,
+    }
+}
+
+// Returns true if a loop condition is always true, e.g. `for (;;)`.
+fn is_always_true(cond: &Node) -> bool {
+    match cond.op {
+        NodeType::Null => true,
+        NodeType::Num(val) => val != 0,
+        _ => false,
+    }
+}
+
+// Returns true if `node` has a `break` out of the loop it is the body
+// of. A `break` in a nested loop leaves that loop instead.
+fn has_break(node: &Node) -> bool {
+    use self::NodeType::*;
+    match node.op {
+        Break => true,
+        CompStmt(ref stmts) => stmts.iter().any(has_break),
+        If(_, ref then, ref els) => has_break(then) || els.as_ref().is_some_and(|e| has_break(e)),
+        Label(_, ref stmt) => has_break(stmt),
+        _ => false,
+    }
+}
+
+// The left-hand side of an assignment, or the operand of ++ or --.
+fn check_assignable(node: &Node, token: &Option<Token>) {
+    check_lval(node, token);
+    match node.ty.ty {
+        Ctype::Ary(..) => error_at(token, "array is not assignable"),
+        Ctype::Func(..) => error_at(token, "function is not assignable"),
+        _ if node.ty.is_const => error_at(token, "assignment of read-only location"),
+        _ => (),
+    }
+}
+
+// Calls f on node and on every node below it.
+fn visit(node: &Node, f: &mut dyn FnMut(&Node)) {
+    use self::NodeType::*;
+    f(node);
+    match node.op {
+        Vardef(_, Some(ref expr), _)
+        | Addr(ref expr)
+        | Deref(ref expr)
+        | Dot(ref expr, _, _)
+        | Exclamation(ref expr)
+        | Neg(ref expr)
+        | Cast(ref expr)
+        | PostInc(ref expr)
+        | PostDec(ref expr)
+        | Return(ref expr)
+        | Sizeof(ref expr)
+        | Alignof(ref expr)
+        | ExprStmt(ref expr)
+        | StmtExpr(ref expr)
+        | GotoPtr(ref expr)
+        | Label(_, ref expr) => visit(expr, f),
+        BinOp(_, ref lhs, ref rhs) | DoWhile(ref lhs, ref rhs) | CompLit(ref lhs, ref rhs) => {
+            visit(lhs, f);
+            visit(rhs, f);
+        }
+        If(ref cond, ref then, ref els) => {
+            visit(cond, f);
+            visit(then, f);
+            if let Some(els) = els {
+                visit(els, f);
             }
-            node.op = Vardef(name, init, Scope::Local(offset));
         }
-        If(mut cond, mut then, els_may) => {
-            cond = Box::new(walk(*cond, true));
-            then = Box::new(walk(*then, true));
-            let mut new_els = None;
-            if let Some(els) = els_may {
-                new_els = Some(Box::new(walk(*els, true)));
-            }
-            node.op = If(cond, then, new_els);
+        Ternary(ref cond, ref then, ref els) => {
+            visit(cond, f);
+            visit(then, f);
+            visit(els, f);
         }
-        Ternary(mut cond, mut then, mut els) => {
-            cond = Box::new(walk(*cond, true));
-            then = Box::new(walk(*then, true));
-            els = Box::new(walk(*els, true));
-            node.ty = then.ty.clone();
-            node.op = Ternary(cond, then, els);
+        For(ref init, ref cond, ref inc, ref body) => {
+            visit(init, f);
+            visit(cond, f);
+            visit(inc, f);
+            visit(body, f);
         }
-        For(init, cond, inc, body) => {
-            let f = |(init, cond, inc, body)| -> (Node, Node, Node, Node) {
-                (
-                    walk(init, true),
-                    walk(cond, true),
-                    walk(inc, true),
-                    walk(body, true),
-                )
-            };
-            let (init, cond, inc, body) = into_new_range((*init, *cond, *inc, *body), Box::new(f));
-            node.op = For(
-                Box::new(init),
-                Box::new(cond),
-                Box::new(inc),
-                Box::new(body),
-            );
+        Call(_, ref stmts) | CompStmt(ref stmts) | VecStmt(ref stmts) => {
+            for stmt in stmts {
+                visit(stmt, f);
+            }
+        }
+        CallPtr(ref callee, ref args) => {
+            visit(callee, f);
+            for arg in args {
+                visit(arg, f);
+            }
         }
-        DoWhile(body, cond) => {
-            node.op = DoWhile(Box::new(walk(*body, true)), Box::new(walk(*cond, true)));
+        _ => (),
+    }
+}
+
+fn vardef_name(node: &Node) -> String {
+    match node.op {
+        NodeType::Vardef(ref name, _, _) => name.clone(),
+        _ => unreachable!(),
+    }
+}
+
+impl Sema {
+    fn new() -> Self {
+        Sema {
+            globals: vec![],
+            env: Env::new(None),
+            strlabel: 0,
+            nstatic: 0,
+            stacksize: 0,
+            inline_fns: HashMap::new(),
+            inlining: vec![],
+            inline_label: 0,
+            func_name: String::new(),
+            in_init: false,
+            addr_of_subscript: false,
         }
-        Dot(mut expr, name, _) => {
-            expr = Box::new(walk(*expr, true));
-            let offset;
-            if let Ctype::Struct(ref members) = expr.ty.ty {
-                if members.is_empty() {
-                    panic!("incomplete type");
+    }
+
+    fn in_new_scope<T>(&mut self, param: T, f: impl FnOnce(&mut Self, T) -> T) -> T {
+        let env = mem::replace(&mut self.env, Env::new(None));
+        self.env = Env::new(Some(Box::new(env)));
+        let ret = f(self, param);
+        // Rollback
+        self.env = *self.env.next.take().unwrap();
+        ret
+    }
+
+    fn find_var(&self, name: &str) -> Option<Var> {
+        let mut env = Some(&self.env);
+        while let Some(e) = env {
+            if let Some(var) = e.vars.get(name) {
+                return Some(var.clone());
+            }
+            env = e.next.as_deref();
+        }
+        None
+    }
+
+    // Returns a statement expression that computes `name(args)` with the
+    // body of an inline function, or None if the call cannot be inlined.
+    //
+    // The arguments are evaluated into fresh temporaries first, and the
+    // parameters are then initialized from those, so an argument can never
+    // be captured by a parameter of the same name. The body itself is not
+    // renamed; instead, a call is not inlined if a name the body refers to
+    // but does not declare is shadowed by a local variable at the call site.
+    fn inline_call(&mut self, name: &str, args: &[Node]) -> Option<Node> {
+        if self.inlining.iter().any(|f| f == name) {
+            return None;
+        }
+        let (params, body) = self.inline_fns.get(name)?.clone();
+        if params.len() != args.len() {
+            return None;
+        }
+
+        // Labels, static locals and __func__ belong to the function they
+        // are written in, so a body that uses them cannot be copied into
+        // another function.
+        let mut is_fn_specific = false;
+        let mut size = 0;
+        let mut uses = vec![];
+        let mut defs: Vec<String> = params.iter().map(vardef_name).collect();
+        visit(&body, &mut |node| {
+            size += 1;
+            match node.op {
+                NodeType::Ident(ref name) | NodeType::Call(ref name, _) => uses.push(name.clone()),
+                NodeType::Vardef(_, _, Scope::Global(..)) | NodeType::Label(..) => {
+                    is_fn_specific = true
                 }
-                let m_may = members.iter().find(|m| {
-                    if let NodeType::Vardef(ref m_name, _, _) = m.op {
-                        if m_name != &name {
-                            return false;
-                        }
-                        return true;
-                    }
-                    false
-                });
+                NodeType::Vardef(ref name, _, _) => defs.push(name.clone()),
+                _ => (),
+            }
+        });
+        if size > INLINE_LIMIT || is_fn_specific || uses.iter().any(|name| name == "__func__") {
+            return None;
+        }
+        for name in uses.iter().filter(|name| !defs.contains(name)) {
+            if let Some(Var {
+                scope: Scope::Local(_),
+                ..
+            }) = self.find_var(name)
+            {
+                return None;
+            }
+        }
 
-                if let Some(m) = m_may {
-                    if let NodeType::Vardef(_, _, Scope::Local(offset2)) = m.op {
-                        node.ty = m.ty.clone();
-                        offset = offset2;
-                    } else {
-                        unreachable!()
+        let mut temps = vec![];
+        let mut inits = vec![];
+        for (param, arg) in params.iter().zip(args) {
+            let tmp = format!(".inline.tmp{}", self.inline_label);
+            self.inline_label += 1;
+
+            let mut var = Node::new(NodeType::Vardef(
+                tmp.clone(),
+                Some(Box::new(arg.clone())),
+                Scope::Local(0),
+            ));
+            var.ty = param.ty.clone();
+            temps.push(var);
+
+            let mut var = Node::new(NodeType::Vardef(
+                vardef_name(param),
+                Some(Box::new(Node::new(NodeType::Ident(tmp)))),
+                Scope::Local(0),
+            ));
+            var.ty = param.ty.clone();
+            inits.push(var);
+        }
+        inits.push(body);
+        temps.push(Node::new(NodeType::CompStmt(inits)));
+        Some(Node::new(NodeType::StmtExpr(Box::new(Node::new(
+            NodeType::CompStmt(temps),
+        )))))
+    }
+
+    // Returns true if control may reach the end of `node`. A statement
+    // after a return, goto or call to a noreturn function is reachable
+    // only through a label.
+    fn may_fall_through(&self, node: &Node) -> bool {
+        use self::NodeType::*;
+        match node.op {
+            Return(_) | Continue | Goto(_) | GotoPtr(_) => false,
+            ExprStmt(ref expr) => match expr.op {
+                Call(ref name, _) => !self.find_var(name).is_some_and(|var| var.ty.is_noreturn),
+                _ => true,
+            },
+            CompStmt(ref stmts) => {
+                let mut reachable = true;
+                for stmt in stmts {
+                    if let Label(..) = stmt.op {
+                        reachable = true;
+                    }
+                    if reachable && !self.may_fall_through(stmt) {
+                        reachable = false;
                     }
-                } else {
-                    panic!("member missing: {}", name);
                 }
-            } else {
-                panic!("struct expected before '.'");
+                reachable
             }
+            Label(_, ref stmt) => self.may_fall_through(stmt),
+            If(_, ref then, Some(ref els)) => {
+                self.may_fall_through(then) || self.may_fall_through(els)
+            }
+            For(_, ref cond, _, ref body) => !is_always_true(cond) || has_break(body),
+            DoWhile(ref body, ref cond) => !is_always_true(cond) || has_break(body),
+            _ => true,
+        }
+    }
+
+    // Fills in the parts of a global's initializer that the parser left:
+    // constants that need types, such as `sizeof x`, and addresses, such
+    // as `&a[2]` or `"abc"`, which the linker fills in.
+    fn global_init(&mut self, var: &mut Var, init: Node) {
+        let parts = match init.op {
+            NodeType::GlobalInit(parts) => parts,
+            _ => unreachable!(),
+        };
+        let mut data: Vec<char> = match var.scope {
+            Scope::Global(ref data, ..) => data.chars().collect(),
+            _ => unreachable!(),
+        };
+        for (offset, ty, node) in parts {
+            let node = self.walk(node, true);
+            if let Some(val) = eval(&node) {
+                let bytes = int_data(val, &ty);
+                data.splice(offset..offset + ty.size, bytes.chars());
+                continue;
+            }
+            let is_ptr = matches!(ty.ty, Ctype::Ptr(_));
+            match addr_const(&node) {
+                Some((label, 0)) if is_ptr => var.relocs.push((offset, label)),
+                Some((label, addend)) if is_ptr => {
+                    var.relocs.push((offset, format!("{}{:+}", label, addend)))
+                }
+                _ => error_at(&node.token, "initializer element is not constant"),
+            }
+        }
+        if let Scope::Global(ref mut old, ..) = var.scope {
+            *old = data.into_iter().collect();
+        }
+    }
 
-            node.op = NodeType::Dot(expr, name, offset);
-            return maybe_decay(node, decay);
+    // A static local becomes a global with a unique label, which its
+    // name refers to in the rest of the block. Kept out of walk(), whose
+    // frame size bounds how deeply statements can nest.
+    #[inline(never)]
+    fn static_local(&mut self, node: Node) {
+        let (name, init, data, len) = match node.op {
+            NodeType::Vardef(name, init, Scope::Global(data, len, ..)) => (name, init, data, len),
+            _ => unreachable!(),
+        };
+        let label = format!(".L.{}.{}", name, self.nstatic);
+        self.nstatic += 1;
+        let mut var = Var::new_global(node.ty, label, data, len, false, true);
+        if let Some(init) = init {
+            self.global_init(&mut var, *init);
         }
-        BinOp(token_type, mut lhs, mut rhs) => {
-            use self::TokenType::*;
-            match token_type {
-                Plus | Minus => {
-                    lhs = Box::new(walk(*lhs, true));
-                    rhs = Box::new(walk(*rhs, true));
-
-                    if matches!(rhs.ty.ty, Ctype::Ptr(_)) {
-                        swap(&mut lhs, &mut rhs);
+        self.globals.push(var.clone());
+        self.env.vars.insert(name, var);
+    }
+
+    // Types a call through a function pointer, which is also how `f(x)`
+    // is called if `f` is a variable rather than a function.
+    #[inline(never)]
+    fn call_ptr(&mut self, mut node: Node, callee: Node, args: Vec<Node>) -> Node {
+        let callee = self.walk(callee, true);
+        let returning = match callee.ty.ty {
+            Ctype::Ptr(ref ptr_to) => match ptr_to.ty {
+                Ctype::Func(ref returning, ref params, is_variadic) => {
+                    check_arg_count(&node, "", params, is_variadic, args.len());
+                    returning.clone()
+                }
+                _ => error_at(&node.token, "called object is not a function"),
+            },
+            _ => error_at(&node.token, "called object is not a function"),
+        };
+        let args = args.into_iter().map(|arg| self.walk(arg, true)).collect();
+        node.op = NodeType::CallPtr(Box::new(callee), args);
+        node.ty = returning;
+        node
+    }
+
+    // Types a binary operator whose operands have been walked. This is
+    // the largest case of walk(), and is a function of its own to keep
+    // its frame off the stack while the operands are walked.
+    #[inline(never)]
+    fn binop(
+        &mut self,
+        mut node: Node,
+        token_type: TokenType,
+        mut lhs: Box<Node>,
+        mut rhs: Box<Node>,
+        past_end: bool,
+    ) -> Node {
+        use self::NodeType::*;
+        use self::TokenType::*;
+        node.ty = binop_type(&token_type, &lhs.ty, &rhs.ty);
+        match token_type {
+            Plus | Minus => {
+                let len = match lhs.ty.ty {
+                    Ctype::Ary(_, len) => len,
+                    _ => 0,
+                };
+                *lhs = maybe_decay(*lhs, true);
+                check_index(&node, len, &rhs, past_end);
+
+                let is_ptr = |node: &Node| matches!(node.ty.ty, Ctype::Ptr(_));
+                if token_type == Plus && is_ptr(&rhs) {
+                    swap(&mut lhs, &mut rhs);
+                }
+                check_ptr_arith(&node, &lhs.ty);
+                if token_type == Minus && is_ptr(&lhs) && is_ptr(&rhs) {
+                    return ptr_diff(*lhs, *rhs);
+                }
+                if is_ptr(&rhs) {
+                    let op = if token_type == Plus { '+' } else { '-' };
+                    error_at(&node.token, &format!("invalid operands to binary {}", op));
+                }
+
+                if is_ptr(&lhs) {
+                    let offset = convert(*rhs, &Type::ptrdiff_t());
+                    rhs = Box::new(Node::scale_ptr(offset, &lhs.ty));
+                } else {
+                    let (l, r, _) = usual_arith_conv(*lhs, *rhs);
+                    lhs = l;
+                    rhs = r;
+                }
+                node.op = BinOp(token_type, lhs, rhs);
+            }
+            AddEQ | SubEQ => {
+                check_assignable(&lhs, &node.token);
+                check_ptr_arith(&node, &lhs.ty);
+
+                if matches!(lhs.ty.ty, Ctype::Ptr(_)) {
+                    let offset = convert(*rhs, &Type::ptrdiff_t());
+                    rhs = Box::new(Node::scale_ptr(offset, &lhs.ty));
+                } else {
+                    rhs = Box::new(convert(*rhs, &lhs.ty));
+                }
+                node.op = BinOp(token_type, lhs, rhs);
+            }
+            Equal => {
+                // A const variable may still be initialized.
+                if !self.in_init {
+                    check_assignable(&lhs, &node.token);
+                }
+
+                // The value of `b = 5` is the value stored to b, which
+                // is 1 if b is a _Bool.
+                if let Ctype::Bool = lhs.ty.ty {
+                    *rhs = Node::new_binop(NE, *rhs, Node::new_int(0));
+                } else {
+                    *rhs = convert(*rhs, &lhs.ty);
+                }
+                node.op = BinOp(token_type, lhs, rhs);
+            }
+            MulEQ | DivEQ | ModEQ | ShlEQ | ShrEQ | BitandEQ | XorEQ | BitorEQ => {
+                check_assignable(&lhs, &node.token);
+                // The operation is done at the common type, and the
+                // store converts the result back to the type of lhs.
+                if !std::matches!(token_type, ShlEQ | ShrEQ) {
+                    let ty = arith_conv(&lhs.ty, &rhs.ty);
+                    rhs = Box::new(convert(*rhs, &ty));
+                }
+                node.op = BinOp(token_type, lhs, rhs);
+            }
+            Comma => node.op = BinOp(token_type, lhs, rhs),
+            EQ | NE | LE | LeftAngleBracket | Logand | Logor => {
+                // `a < b < c` is `(a < b) < c`, which is rarely what was
+                // meant, with or without the parentheses.
+                let is_compare = |node: &Node| {
+                    std::matches!(node.op, BinOp(EQ | NE | LE | LeftAngleBracket, ..))
+                };
+                if !std::matches!(token_type, Logand | Logor)
+                    && (is_compare(&lhs) || is_compare(&rhs))
+                {
+                    warn_at(
+                        &node,
+                        "comparisons like 'X<=Y<=Z' do not have their mathematical meaning",
+                    );
+                }
+                if let Some(ref t) = node.token {
+                    if is_sign_mismatch(&lhs, &rhs) {
+                        t.warn("comparison between signed and unsigned integers");
                     }
-                    if matches!(rhs.ty.ty, Ctype::Ptr(_)) {
-                        panic!("'pointer {:?} pointer' is not defined", node.op)
+                    if is_ptr_int_mismatch(&lhs, &rhs) {
+                        t.warn("comparison between pointer and integer");
                     }
-
-                    if matches!(lhs.ty.ty, Ctype::Ptr(_)) {
-                        rhs = Box::new(Node::scale_ptr(rhs, &lhs.ty));
+                }
+                if !std::matches!(token_type, Logand | Logor) {
+                    let (l, r, _) = usual_arith_conv(*lhs, *rhs);
+                    lhs = l;
+                    rhs = r;
+                }
+                node.op = BinOp(token_type, lhs, rhs);
+            }
+            SHL | SHR => {
+                if let Some(count) = eval(&rhs) {
+                    let dir = if token_type == SHL { "left" } else { "right" };
+                    if count < 0 {
+                        warn_at(&node, &format!("{} shift count is negative", dir));
+                    } else if count as usize >= node.ty.size * 8 {
+                        warn_at(&node, &format!("{} shift count >= width of type", dir));
+                    } else if let Some(val) = eval(&lhs) {
+                        return fold_shift(&token_type, val, count as u32, node.ty);
                     }
+                }
+                node.op = BinOp(token_type, lhs, rhs);
+            }
+            _ => {
+                let (lhs, rhs, _) = usual_arith_conv(*lhs, *rhs);
+                node.op = BinOp(token_type, lhs, rhs);
+            }
+        }
+        node
+    }
+
+    fn walk(&mut self, mut node: Node, decay: bool) -> Node {
+        use self::NodeType::*;
+        let op = node.op.clone();
+        match op {
+            Num(_) | Null | Break | Continue | Goto(_) => (),
+            LabelAddr(_) => node.ty = Box::new(Type::ptr_to(Box::new(Type::void_ty()))),
+            GotoPtr(expr) => node.op = GotoPtr(Box::new(self.walk(*expr, true))),
+            Label(name, stmt) => node.op = Label(name, Box::new(self.walk(*stmt, true))),
+            Str(data, len) => {
+                // Quoted from 9cc
+                // > A string literal is converted to a reference to an anonymous
+                // > global variable of type char array.
+                let name = format!(".L.str{}", self.strlabel);
+                self.strlabel += 1;
+                let var = Var::new_global(node.ty.clone(), name, data, len, false, true);
+                let name = var.name.clone();
+                self.globals.push(var);
 
-                    node.op = BinOp(token_type, lhs.clone(), rhs);
-                    node.ty = lhs.ty;
+                let mut ret = Node::new(NodeType::Gvar(name, "".into(), len));
+                ret.ty = node.ty;
+                return maybe_decay(ret, decay);
+            }
+            Ident(ref name) => {
+                if let Some(var) = self.find_var(name) {
+                    match var.scope {
+                        Scope::Local(offset) => {
+                            let mut ret = Node::new(NodeType::Lvar(Scope::Local(offset)));
+                            ret.ty = var.ty.clone();
+                            return maybe_decay(ret, decay);
+                        }
+                        Scope::Global(ref data, len, ..) => {
+                            let mut ret =
+                                Node::new(NodeType::Gvar(var.name.clone(), data.clone(), len));
+                            ret.ty = var.ty.clone();
+                            return maybe_decay(ret, decay);
+                        }
+                    }
+                } else if name == "__func__" && !self.func_name.is_empty() {
+                    // As if each function began with
+                    // `static const char __func__[] = "function-name";`
+                    let len = self.func_name.len() + 1;
+                    let mut s = Node::new(Str(self.func_name.clone(), len));
+                    s.ty = Box::new(Type::ary_of(Box::new(Type::char_ty()), len));
+                    return self.walk(s, decay);
+                } else {
+                    error_at(&node.token, &format!("undeclared identifier: {}", name));
                 }
-                AddEQ | SubEQ => {
-                    lhs = Box::new(walk(*lhs, false));
-                    check_lval(&*lhs);
-                    rhs = Box::new(walk(*rhs, true));
+            }
+            Vardef(_, _, Scope::Global(..)) => {
+                self.static_local(node);
+                return Node::new(Null);
+            }
+            Vardef(name, init_may, _) => {
+                if node.ty.is_incomplete() {
+                    error_at(
+                        &node.token,
+                        &format!("variable has incomplete type: {}", name),
+                    );
+                }
+
+                // A variable lives at rbp-offset, so the offset itself (not the
+                // end of the previous variable) must be a multiple of the alignment.
+                let stacksize = self.stacksize;
+                self.stacksize = roundup(stacksize + node.ty.size, node.ty.align);
+                let offset = self.stacksize;
+
+                self.env.vars.insert(
+                    name.clone(),
+                    Var::new(node.ty.clone(), name.clone(), Scope::Local(offset)),
+                );
 
-                    if matches!(lhs.ty.ty, Ctype::Ptr(_)) {
-                        rhs = Box::new(Node::scale_ptr(rhs, &lhs.ty));
+                let mut init = None;
+                if let Some(init2) = init_may {
+                    init = Some(Box::new(convert(self.walk(*init2, true), &node.ty)));
+                }
+                node.op = Vardef(name, init, Scope::Local(offset));
+            }
+            If(mut cond, mut then, els_may) => {
+                cond = Box::new(self.walk(*cond, true));
+                then = Box::new(self.walk(*then, true));
+                let mut new_els = None;
+                if let Some(els) = els_may {
+                    new_els = Some(Box::new(self.walk(*els, true)));
+                }
+                node.op = If(cond, then, new_els);
+            }
+            Ternary(mut cond, mut then, mut els) => {
+                cond = Box::new(self.walk(*cond, true));
+                then = Box::new(self.walk(*then, true));
+                els = Box::new(self.walk(*els, true));
+                // The branches are converted to a common type, where a null
+                // pointer constant takes the type of a pointer in the other.
+                node.ty = match (&then.op, &els.ty.ty) {
+                    (Null, _) => arith_conv(&cond.ty, &els.ty),
+                    (Num(0), Ctype::Ptr(_)) => els.ty.clone(),
+                    _ => {
+                        let (t, e, ty) = usual_arith_conv(*then, *els);
+                        then = t;
+                        els = e;
+                        ty
+                    }
+                };
+                node.op = Ternary(cond, then, els);
+            }
+            For(init, cond, inc, body) => {
+                let f = |sema: &mut Self, (init, cond, inc, body)| {
+                    (
+                        sema.walk(init, true),
+                        sema.walk(cond, true),
+                        sema.walk(inc, true),
+                        sema.walk(body, true),
+                    )
+                };
+                let (init, cond, inc, body) = self.in_new_scope((*init, *cond, *inc, *body), f);
+                node.op = For(
+                    Box::new(init),
+                    Box::new(cond),
+                    Box::new(inc),
+                    Box::new(body),
+                );
+            }
+            DoWhile(body, cond) => {
+                node.op = DoWhile(
+                    Box::new(self.walk(*body, true)),
+                    Box::new(self.walk(*cond, true)),
+                );
+            }
+            Dot(mut expr, name, _) => {
+                expr = Box::new(self.walk(*expr, true));
+                let offset;
+                if let Ctype::Struct(ref members) = expr.ty.ty {
+                    if members.is_empty() {
+                        error_at(&node.token, "incomplete type");
+                    }
+                    if let Some((ty, offset2)) = expr.ty.member(&name) {
+                        node.ty = Box::new(ty);
+                        offset = offset2;
+                    } else {
+                        error_at(&node.token, &format!("member missing: {}", name));
                     }
-                    node.op = BinOp(token_type, lhs.clone(), rhs);
-                    node.ty = lhs.ty;
+                } else {
+                    error_at(&node.token, "struct expected before '.'");
+                }
+
+                node.op = NodeType::Dot(expr, name, offset);
+                return maybe_decay(node, decay);
+            }
+            BinOp(token_type, lhs, rhs) => {
+                let past_end = mem::take(&mut self.addr_of_subscript);
+                let lhs = Box::new(self.walk(*lhs, decays_lhs(&token_type)));
+                let rhs = Box::new(self.walk(*rhs, true));
+                return self.binop(node, token_type, lhs, rhs, past_end);
+            }
+            PostInc(mut expr) => {
+                expr = Box::new(self.walk(*expr, false));
+                check_assignable(&expr, &node.token);
+                check_ptr_arith(&node, &expr.ty);
+                node.ty = expr.ty.clone();
+                node.op = PostInc(expr);
+            }
+            PostDec(mut expr) => {
+                expr = Box::new(self.walk(*expr, false));
+                check_assignable(&expr, &node.token);
+                check_ptr_arith(&node, &expr.ty);
+                node.ty = expr.ty.clone();
+                node.op = PostDec(expr);
+            }
+            Neg(mut expr) => {
+                expr = Box::new(self.walk(*expr, true));
+                node.ty = expr.ty.clone();
+                node.op = Neg(expr);
+            }
+            Exclamation(mut expr) => {
+                expr = Box::new(self.walk(*expr, true));
+                node.ty = Box::new(Type::int_ty());
+                node.op = Exclamation(expr);
+            }
+            Addr(mut expr) => {
+                // &arr is a pointer to the whole array, so the operand must
+                // not decay to a pointer to its first element.
+                self.addr_of_subscript = is_subscript(&expr);
+                expr = Box::new(self.walk(*expr, false));
+                check_lval(&expr, &node.token);
+                if expr.ty.bitfield.is_some() {
+                    error_at(&node.token, "cannot take address of bit-field");
                 }
-                Equal | MulEQ | DivEQ | ModEQ | ShlEQ | ShrEQ | BitandEQ | XorEQ | BitorEQ => {
-                    lhs = Box::new(walk(*lhs, false));
-                    check_lval(&*lhs);
-                    node.op = BinOp(token_type, lhs.clone(), Box::new(walk(*rhs, true)));
-                    node.ty = lhs.ty;
+                node.ty = Box::new(Type::ptr_to(expr.ty.clone()));
+                // &*x is x, so &a[i] is a + i without a load.
+                node.op = match expr.op {
+                    Deref(inner) => inner.op,
+                    _ => Addr(expr),
+                };
+            }
+            Deref(mut expr) => {
+                expr = Box::new(self.walk(*expr, true));
+                match expr.ty.ty {
+                    Ctype::Ptr(ref ptr_to) => node.ty = ptr_to.clone(),
+                    Ctype::Void => error_at(&node.token, "cannot dereference void pointer"),
+                    _ => error_at(&node.token, "operand must be a pointer"),
                 }
-                _ => {
-                    lhs = Box::new(walk(*lhs, true));
-                    rhs = Box::new(walk(*rhs, true));
-                    node.op = BinOp(token_type, lhs.clone(), rhs);
-                    node.ty = lhs.ty;
+                node.op = Deref(expr);
+                return maybe_decay(node, decay);
+            }
+            Return(expr) => node.op = Return(Box::new(self.walk(*expr, true))),
+            ExprStmt(expr) => node.op = ExprStmt(Box::new(self.walk(*expr, true))),
+            Sizeof(mut expr) => {
+                *expr = self.walk(*expr, false);
+                if expr.ty.is_incomplete() {
+                    error_at(
+                        &node.token,
+                        "invalid application of sizeof to incomplete type",
+                    );
                 }
+                node = Node::new(NodeType::Num(expr.ty.size as i64));
+                node.ty = Box::new(Type::size_t());
             }
-        }
-        PostInc(mut expr) => {
-            expr = Box::new(walk(*expr, true));
-            node.ty = expr.ty.clone();
-            node.op = PostInc(expr);
-        }
-        PostDec(mut expr) => {
-            expr = Box::new(walk(*expr, true));
-            node.ty = expr.ty.clone();
-            node.op = PostDec(expr);
-        }
-        Neg(mut expr) => {
-            expr = Box::new(walk(*expr, true));
-            node.ty = expr.ty.clone();
-            node.op = Neg(expr);
-        }
-        Exclamation(mut expr) => {
-            expr = Box::new(walk(*expr, true));
-            node.ty = expr.ty.clone();
-            node.op = Exclamation(expr);
-        }
-        Addr(mut expr) => {
-            expr = Box::new(walk(*expr, true));
-            check_lval(&*expr);
-            node.ty = Box::new(Type::ptr_to(expr.ty.clone()));
-            node.op = Addr(expr);
-        }
-        Deref(mut expr) => {
-            expr = Box::new(walk(*expr, true));
-            match expr.ty.ty {
-                Ctype::Ptr(ref ptr_to) => node.ty = ptr_to.clone(),
-                Ctype::Void => panic!("cannot dereference void pointer"),
-                _ => panic!("operand must be a pointer"),
-            }
-            node.op = Deref(expr);
-            return maybe_decay(node, decay);
-        }
-        Return(expr) => node.op = Return(Box::new(walk(*expr, true))),
-        ExprStmt(expr) => node.op = ExprStmt(Box::new(walk(*expr, true))),
-        Sizeof(mut expr) => {
-            expr = Box::new(walk(*expr, false));
-            node = Node::new_int(expr.ty.size as i32)
-        }
-        Alignof(mut expr) => {
-            expr = Box::new(walk(*expr, false));
-            node = Node::new_int(expr.ty.align as i32)
-        }
-        Call(name, mut args) => {
-            if let Some(var) = find_var(&name) {
-                if let Ctype::Func(returning) = var.ty.ty {
+            Alignof(mut expr) => {
+                *expr = self.walk(*expr, false);
+                if expr.ty.is_incomplete() {
+                    error_at(
+                        &node.token,
+                        "invalid application of _Alignof to incomplete type",
+                    );
+                }
+                node = Node::new(NodeType::Num(expr.ty.align as i64));
+                node.ty = Box::new(Type::size_t());
+            }
+            Call(name, mut args) => {
+                let var = self.find_var(&name).unwrap_or_else(|| {
+                    error_at(&node.token, &format!("undefined function: {}", name))
+                });
+                if is_func_ptr(&var.ty) {
+                    return self.call_ptr(node, Node::new(Ident(name)), args);
+                }
+                if let Ctype::Func(returning, params, is_variadic) = var.ty.ty {
+                    check_arg_count(&node, &name, &params, is_variadic, args.len());
                     node.ty = returning;
                 } else {
-                    eprint!("bad function: {}", name);
+                    error_at(&node.token, &format!("{} is not a function", name));
                 }
-            } else {
-                eprint!("bad function: {}", name);
+
+                if let Some(expr) = self.inline_call(&name, &args) {
+                    self.inlining.push(name);
+                    let mut expr = self.walk(expr, true);
+                    self.inlining.pop();
+                    expr.ty = node.ty;
+                    return expr;
+                }
+
+                args = args.into_iter().map(|arg| self.walk(arg, true)).collect();
+                node.op = Call(name, args);
+            }
+            CallPtr(callee, args) => return self.call_ptr(node, *callee, args),
+            CompStmt(mut stmts) => {
+                let f = |sema: &mut Self, stmts: Vec<Node>| -> Vec<Node> {
+                    stmts
+                        .into_iter()
+                        .map(|stmt| sema.walk(stmt, true))
+                        .collect()
+                };
+                stmts = self.in_new_scope(stmts, f);
+                node.op = CompStmt(stmts);
+            }
+            VecStmt(mut stmts) => {
+                let in_init = mem::replace(&mut self.in_init, true);
+                stmts = stmts
+                    .into_iter()
+                    .map(|stmt| self.walk(stmt, true))
+                    .collect();
+                self.in_init = in_init;
+                node.op = VecStmt(stmts);
+            }
+            CompLit(init, var) => {
+                let init = self.walk(*init, true);
+                let var = self.walk(*var, decay);
+                node.ty = var.ty.clone();
+                node.op = CompLit(Box::new(init), Box::new(var));
+            }
+            Cast(expr) => node.op = Cast(Box::new(self.walk(*expr, true))),
+            StmtExpr(body) => {
+                node.op = StmtExpr(Box::new(self.walk(*body, true)));
+                node.ty = Box::new(Type::int_ty())
             }
+            _ => panic!("unknown node type"),
+        };
+        node
+    }
+}
 
-            args = args.into_iter().map(|arg| walk(arg, true)).collect();
-            node.op = Call(name, args);
-        }
-        CompStmt(mut stmts) => {
-            let f = |stmts: Vec<Node>| -> Vec<Node> {
-                stmts.into_iter().map(|stmt| walk(stmt, true)).collect()
-            };
-            stmts = into_new_range(stmts, Box::new(f));
-            node.op = CompStmt(stmts);
-        }
-        VecStmt(mut stmts) => {
-            stmts = stmts.into_iter().map(|stmt| walk(stmt, true)).collect();
-            node.op = VecStmt(stmts);
-        }
-        StmtExpr(body) => {
-            node.op = StmtExpr(Box::new(walk(*body, true)));
-            node.ty = Box::new(Type::int_ty())
-        }
-        _ => panic!("unknown node type"),
-    };
-    node
+// The functions the compiler provides, as (name, type).
+fn builtins() -> Vec<(&'static str, Type)> {
+    let void_ptr = || Type::ptr_to(Box::new(Type::void_ty()));
+    let func = |params| Type::new(Ctype::Func(Box::new(void_ptr()), Some(params), false), 0);
+    vec![
+        (
+            "__builtin_memcpy",
+            func(vec![void_ptr(), void_ptr(), Type::size_t()]),
+        ),
+        (
+            "__builtin_memset",
+            func(vec![void_ptr(), Type::int_ty(), Type::size_t()]),
+        ),
+    ]
 }
 
-pub fn sema(nodes: Vec<Node>) -> (Vec<Node>, Vec<Var>) {
+// If `inline` is set, calls to small `inline` functions are replaced
+// with their bodies.
+pub fn sema(nodes: Vec<Node>, inline: bool) -> (Vec<Node>, Vec<Var>) {
     let mut new_nodes = vec![];
+    let mut sema = Sema::new();
 
-    for mut node in nodes {
-        if let NodeType::Vardef(name, _, Scope::Global(data, len, is_extern)) = node.op {
-            let var = Var::new_global(node.ty, name.clone(), data, len, is_extern);
-            GLOBALS.lock().unwrap().push(var.clone());
-            ENV.lock().unwrap().vars.insert(name, var);
-            continue;
-        }
+    // Compiler builtins are declared implicitly.
+    for (name, ty) in builtins() {
+        let var = Var::new_global(Box::new(ty), name.into(), "".into(), 0, false, false);
+        sema.env.vars.insert(name.into(), var);
+    }
 
-        let var;
+    // Register every function signature first, so that a call can be
+    // typed even if its callee is defined later in the file.
+    for node in &nodes {
         match &node.op {
-            NodeType::Func(name, _, _, _) | NodeType::Decl(name) => {
-                var = Var::new_global(node.ty.clone(), name.clone(), "".into(), 0, false);
-                ENV.lock().unwrap().vars.insert(name.clone(), var);
+            NodeType::Func(name, ..) | NodeType::Decl(name) => {
+                // A function is noreturn if any of its declarations says so.
+                let mut var =
+                    Var::new_global(node.ty.clone(), name.clone(), "".into(), 0, false, false);
+                if let Some(prev) = sema.env.vars.get(name) {
+                    var.ty.is_noreturn |= prev.ty.is_noreturn;
+                }
+                sema.env.vars.insert(name.clone(), var);
+                if let NodeType::Func(_, args, body, _, true, _) = &node.op {
+                    if inline {
+                        sema.inline_fns
+                            .insert(name.clone(), (args.clone(), (**body).clone()));
+                    }
+                }
             }
+            NodeType::Vardef(_, _, Scope::Global(..)) => (),
             _ => unreachable!(),
         }
+    }
 
-        if matches!(node.op, NodeType::Decl(_)) {
+    for mut node in nodes {
+        if let NodeType::Vardef(name, init, Scope::Global(data, len, is_extern, is_static)) =
+            node.op
+        {
+            let mut var = Var::new_global(node.ty, name.clone(), data, len, is_extern, is_static);
+            sema.env.vars.insert(name.clone(), var.clone());
+            if let Some(init) = init {
+                sema.global_init(&mut var, *init);
+            }
+            sema.globals.push(var.clone());
+            sema.env.vars.insert(name, var);
             continue;
         }
 
-        if let NodeType::Func(name, args, body, _) = node.op {
+        if let NodeType::Func(name, args, body, _, is_inline, is_static) = node.op {
+            sema.func_name = name.clone();
             let mut args2 = vec![];
             for arg in args {
-                args2.push(walk(arg, true));
+                args2.push(sema.walk(arg, true));
+            }
+            let body2 = sema.walk(*body, true);
+            if let Ctype::Func(ref ret, ..) = node.ty.ty {
+                let is_void = std::matches!(ret.ty, Ctype::Void);
+                if !is_void && name != "main" && sema.may_fall_through(&body2) {
+                    if let Some(ref t) = node.token {
+                        t.warn("control reaches end of non-void function");
+                    }
+                }
             }
-            let body2 = walk(*body, true);
             node.op = NodeType::Func(
                 name.clone(),
                 args2,
                 Box::new(body2),
-                *STACKSIZE.lock().unwrap(),
+                sema.stacksize,
+                is_inline,
+                is_static,
             );
-            *STACKSIZE.lock().unwrap() = 0;
+            sema.stacksize = 0;
+            sema.func_name.clear();
             new_nodes.push(node);
         }
     }
-    (new_nodes, GLOBALS.lock().unwrap().clone())
+    (new_nodes, sema.globals.clone())
 }
@@ -0,0 +1,89 @@
+// A listing of the symbols a translation unit defines and references,
+// in the manner of `nm -S`:
+//
+//            T main
+//   00000004 D x
+//   00000008 B p
+//            U printf
+//
+// T is a function, D an initialized global, R a const one and B a
+// zero-filled one. Static ones are in lower case. U is a symbol that
+// must be defined elsewhere. The size of a function is not known until
+// it is assembled, so only globals have one.
+
+use crate::gen_ir::{Function, IROp};
+use crate::gen_x86::{section, Section};
+use crate::{Scope, Var};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: char,
+    pub size: Option<usize>,
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.size {
+            Some(size) => write!(f, "{:08x} {} {}", size, self.kind, self.name),
+            None => write!(f, "         {} {}", self.kind, self.name),
+        }
+    }
+}
+
+impl Symbol {
+    fn new(name: &str, kind: char, size: Option<usize>) -> Self {
+        Symbol {
+            name: name.into(),
+            kind,
+            size,
+        }
+    }
+}
+
+// Returns the symbols sorted by name. String literals have local labels
+// and are not listed.
+pub fn symbols(fns: &[Function], globals: &[Var]) -> Vec<Symbol> {
+    let mut syms = BTreeMap::new();
+    for f in fns {
+        let kind = if f.is_static { 't' } else { 'T' };
+        syms.insert(&f.name, Symbol::new(&f.name, kind, None));
+    }
+    for var in globals.iter().filter(|var| !var.name.starts_with('.')) {
+        let is_static = match var.scope {
+            Scope::Global(_, _, false, is_static) => is_static,
+            _ => continue,
+        };
+        let kind = match section(var) {
+            Section::Data => 'D',
+            Section::Rodata => 'R',
+            Section::Bss => 'B',
+        };
+        let kind = if is_static {
+            kind.to_ascii_lowercase()
+        } else {
+            kind
+        };
+        syms.insert(&var.name, Symbol::new(&var.name, kind, Some(var.ty.size)));
+    }
+
+    for ir in fns.iter().flat_map(|f| &f.ir) {
+        let name = match ir.op {
+            IROp::Call(ref name, ..) | IROp::TailCall(ref name, ..) => name,
+            IROp::LabelAddr(ref name) if !name.starts_with('.') => name,
+            _ => continue,
+        };
+        syms.entry(name)
+            .or_insert_with(|| Symbol::new(name, 'U', None));
+    }
+    syms.into_values().collect()
+}
+
+pub fn dump_symbols(syms: &[Symbol]) {
+    for sym in syms {
+        println!("{}", sym);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::gen_ir::{Function, IROp, IRType};
+use crate::irdump::IRInfo;
+
+use std::collections::HashSet;
+
+// Tail call optimization for self-recursive functions.
+//
+// A call to the enclosing function whose result is returned directly
+// is replaced with a jump to the beginning of the function body, so
+// that deep tail recursion runs in constant stack space:
+//
+//   r7 = fact(r3, r5)
+//   KILL r3
+//   KILL r5
+//   RET r7
+//
+// becomes
+//
+//   TAILCALL fact(r3, r5)
+//   KILL r3
+//   KILL r5
+
+// Returns true if the address of a local variable or parameter may be
+// kept beyond the access it was computed for, e.g. passed to a call or
+// stored. A jump would then reuse a frame that is still referenced. An
+// address that is only loaded from, stored to or offset is safe.
+fn addr_escapes(f: &Function) -> bool {
+    use self::IROp::*;
+    let mut addrs = HashSet::new();
+    for ir in &f.ir {
+        let ty = IRInfo::from(&ir.op).ty;
+        let lhs = ir.lhs.filter(|_| {
+            !std::matches!(
+                ty,
+                IRType::Noarg | IRType::Imm | IRType::Jmp | IRType::Label | IRType::StoreArg
+            )
+        });
+        let rhs = ir
+            .rhs
+            .filter(|_| std::matches!(ty, IRType::RegReg | IRType::Mem));
+        let is_addr = |r: Option<usize>| r.is_some_and(|r| addrs.contains(&r));
+
+        match ir.op {
+            Bprel => {
+                addrs.insert(ir.lhs.unwrap());
+            }
+            Kill | Load(..) | LoadSx(..) => {
+                addrs.remove(&ir.lhs.unwrap());
+            }
+            Mov | Add if is_addr(rhs) => {
+                addrs.insert(ir.lhs.unwrap());
+            }
+            Mov => {
+                addrs.remove(&ir.lhs.unwrap());
+            }
+            Store(..) | Memset(_) | Add | Sub if !is_addr(rhs) => (),
+            AddImm | SubImm | Memcpy(_) => (),
+            Call(_, nargs, ref args) | TailCall(_, nargs, ref args) | CallPtr(nargs, ref args)
+                if args[..nargs.min(6)].iter().any(|r| addrs.contains(r)) =>
+            {
+                return true
+            }
+            Call(..) | TailCall(..) | CallPtr(..) => (),
+            _ if is_addr(lhs) || is_addr(rhs) => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+fn optimize(f: &mut Function) {
+    if addr_escapes(f) {
+        return;
+    }
+    for i in 0..f.ir.len() {
+        let (nargs, args) = match f.ir[i].op {
+            IROp::Call(ref name, nargs, args) if *name == f.name && nargs <= 6 => (nargs, args),
+            _ => continue,
+        };
+
+        // Only KILLs may stand between the call and the return.
+        let ret = f.ir[i + 1..]
+            .iter()
+            .position(|ir| ir.op != IROp::Kill)
+            .map(|pos| i + 1 + pos);
+        let ret = match ret {
+            Some(j) if f.ir[j].op == IROp::Return && f.ir[j].lhs == f.ir[i].lhs => j,
+            _ => continue,
+        };
+
+        f.ir[i].op = IROp::TailCall(f.name.clone(), nargs, args);
+        f.ir[ret].op = IROp::Nop;
+    }
+}
+
+pub fn tail_call(fns: &mut Vec<Function>) {
+    for f in fns {
+        optimize(f);
+    }
+}
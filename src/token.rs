@@ -1,15 +1,35 @@
 use crate::preprocess;
 use crate::CharacterType;
+use crate::CompileError;
 use crate::TokenType;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::panic;
 use std::rc::Rc;
 
+thread_local! {
+    // The warnings reported on this thread since the last take_warnings().
+    static WARNINGS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+// Returns the warnings reported on this thread, and forgets them.
+pub fn take_warnings() -> Vec<String> {
+    WARNINGS.with(|w| w.take())
+}
+
 pub fn tokenize(path: String, ctx: &mut preprocess::Preprocessor) -> Vec<Token> {
-    let mut tokenizer = Tokenizer::new(Rc::new(path));
+    let src = Tokenizer::read_file(&path);
+    tokenize_str(path, &src, ctx)
+}
+
+// Tokenizes `src` as if it were the contents of the file `path`. The
+// result always ends with an Eof token.
+pub fn tokenize_str(path: String, src: &str, ctx: &mut preprocess::Preprocessor) -> Vec<Token> {
+    let mut tokenizer = Tokenizer::new(Rc::new(path), src);
     tokenizer.canonicalize_newline();
     tokenizer.remove_backslash_newline();
     tokenizer.scan(&keyword_map());
@@ -17,25 +37,68 @@ pub fn tokenize(path: String, ctx: &mut preprocess::Preprocessor) -> Vec<Token>
     tokenizer.tokens = preprocess::preprocess(tokenizer.tokens, ctx);
     tokenizer.strip_newlines_tokens();
     tokenizer.join_string_literals();
+
+    tokenizer.pos = tokenizer.p.len();
+    let eof = tokenizer.new_token(TokenType::Eof);
+    tokenizer.tokens.push(eof);
     tokenizer.tokens
 }
 
+// Prints the token stream one token per line, for debugging the lexer.
+pub fn dump_tokens(tokens: &[Token]) {
+    for t in tokens {
+        let (line, col) = t.position();
+        println!("{}:{}:{}\t{:?}", t.filename, line, col, t.ty);
+    }
+}
+
 fn keyword_map() -> HashMap<String, TokenType> {
     let mut map = HashMap::new();
     map.insert("_Alignof".into(), TokenType::Alignof);
+    map.insert("_Bool".into(), TokenType::Bool);
+    map.insert("_Noreturn".into(), TokenType::Noreturn);
+    map.insert("__attribute".into(), TokenType::Attribute);
+    map.insert("__attribute__".into(), TokenType::Attribute);
+    map.insert("__builtin_offsetof".into(), TokenType::Offsetof);
+    map.insert("__extension__".into(), TokenType::Extension);
+    map.insert("__inline".into(), TokenType::Inline);
+    map.insert("__inline__".into(), TokenType::Inline);
+    map.insert("__restrict".into(), TokenType::Restrict);
+    map.insert("__typeof".into(), TokenType::Typeof);
+    map.insert("__typeof__".into(), TokenType::Typeof);
+    map.insert("__restrict__".into(), TokenType::Restrict);
+    map.insert("__const".into(), TokenType::Const);
+    map.insert("__const__".into(), TokenType::Const);
+    map.insert("__volatile".into(), TokenType::Volatile);
+    map.insert("__volatile__".into(), TokenType::Volatile);
+    map.insert("auto".into(), TokenType::Auto);
     map.insert("break".into(), TokenType::Break);
     map.insert("char".into(), TokenType::Char);
+    map.insert("const".into(), TokenType::Const);
+    map.insert("continue".into(), TokenType::Continue);
     map.insert("void".into(), TokenType::Void);
     map.insert("do".into(), TokenType::Do);
     map.insert("else".into(), TokenType::Else);
+    map.insert("enum".into(), TokenType::Enum);
     map.insert("extern".into(), TokenType::Extern);
     map.insert("for".into(), TokenType::For);
+    map.insert("goto".into(), TokenType::Goto);
     map.insert("if".into(), TokenType::If);
+    map.insert("inline".into(), TokenType::Inline);
     map.insert("int".into(), TokenType::Int);
+    map.insert("long".into(), TokenType::Long);
+    map.insert("register".into(), TokenType::Register);
+    map.insert("restrict".into(), TokenType::Restrict);
     map.insert("return".into(), TokenType::Return);
+    map.insert("signed".into(), TokenType::Signed);
+    map.insert("__signed__".into(), TokenType::Signed);
     map.insert("sizeof".into(), TokenType::Sizeof);
+    map.insert("static".into(), TokenType::Static);
     map.insert("struct".into(), TokenType::Struct);
     map.insert("typedef".into(), TokenType::Typedef);
+    map.insert("typeof".into(), TokenType::Typeof);
+    map.insert("unsigned".into(), TokenType::Unsigned);
+    map.insert("volatile".into(), TokenType::Volatile);
     map.insert("while".into(), TokenType::While);
     map
 }
@@ -78,17 +141,36 @@ impl Token {
         }
     }
 
+    // Whatever was expected, running out of input is reported as such.
     pub fn bad_token(&self, msg: &str) -> ! {
-        print_line(&*self.buf, &*self.filename, self.start);
-        panic!("{}", msg);
+        let msg = if self.ty == TokenType::Eof {
+            "unexpected end of file"
+        } else {
+            msg
+        };
+        error_at(&self.buf, &self.filename, self.start, msg)
     }
 
     pub fn tokstr(&self) -> String {
         self.buf[self.start..self.end].iter().collect()
     }
 
+    // Returns the 1-based line and column of the token.
+    pub fn position(&self) -> (usize, usize) {
+        let before = &self.buf[..self.start];
+        let line = before.iter().filter(|c| *c == &'\n').count() + 1;
+        let col = before.iter().rev().take_while(|c| *c != &'\n').count() + 1;
+        (line, col)
+    }
+
+    pub fn warn(&self, msg: &str) {
+        let (line, col) = self.position();
+        let warning = format!("{}:{}:{}: warning: {}", self.filename, line, col, msg);
+        WARNINGS.with(|w| w.borrow_mut().push(warning));
+    }
+
     pub fn get_line_number(&self) -> usize {
-        self.buf[..self.end].iter().filter(|c| *c == &'\n').count()
+        self.position().0
     }
 
     pub fn is_ident(&self, s: &str) -> bool {
@@ -113,6 +195,7 @@ impl Symbol {
 
 lazy_static! {
     static ref SYMBOLS: Vec<Symbol> = [
+        Symbol::new("...", TokenType::Ellipsis),
         Symbol::new("<<=", TokenType::ShlEQ),
         Symbol::new(">>=", TokenType::ShrEQ),
         Symbol::new("!=", TokenType::NE),
@@ -149,9 +232,9 @@ struct Tokenizer {
 }
 
 impl Tokenizer {
-    fn new(filename: Rc<String>) -> Self {
+    fn new(filename: Rc<String>, src: &str) -> Self {
         Tokenizer {
-            p: Rc::new(Self::read_file(&filename).chars().collect()),
+            p: Rc::new(src.chars().collect()),
             filename,
             pos: 0,
             tokens: vec![],
@@ -161,14 +244,15 @@ impl Tokenizer {
     fn read_file(filename: &str) -> String {
         let mut input = String::new();
         let mut fp = io::stdin();
-        if filename != &"-".to_string() {
-            let mut fp = File::open(filename).expect("file not found");
+        if filename != "-" {
+            let mut fp = File::open(filename)
+                .unwrap_or_else(|_| error(&format!("{}: file not found", filename)));
             fp.read_to_string(&mut input)
-                .expect("something went wrong reading the file");
+                .unwrap_or_else(|_| error(&format!("{}: cannot read the file", filename)));
             return input;
         }
         fp.read_to_string(&mut input)
-            .expect("something went wrong reading the file");
+            .expect("cannot read the standard input");
         input
     }
 
@@ -203,9 +287,14 @@ impl Tokenizer {
                     self.tokens.push(t);
                 }
                 CharacterType::Whitespace => self.pos += 1,
-                CharacterType::Alphabetic => self.ident(&keywords),
+                CharacterType::Alphabetic => self.ident(keywords),
                 CharacterType::Digit => self.number(),
 
+                CharacterType::NonAlphabetic('.')
+                    if self.get_character(1) == Some(CharacterType::Digit) =>
+                {
+                    self.float_number()
+                }
                 CharacterType::NonAlphabetic('\'') => self.char_literal(),
                 CharacterType::NonAlphabetic('\"') => self.string_literal(),
                 CharacterType::NonAlphabetic('/') => match self.p.get(self.pos + 1) {
@@ -264,7 +353,7 @@ impl Tokenizer {
     }
 
     fn line_comment(&mut self) {
-        while self.p.get(self.pos) != Some(&'\n') {
+        while self.pos < self.p.len() && self.p[self.pos] != '\n' {
             self.pos += 1;
         }
     }
@@ -301,13 +390,19 @@ impl Tokenizer {
     fn char_literal(&mut self) {
         self.pos += 1;
         let result: char;
-        let c = self.p.get(self.pos).expect("premature end of input");
+        let c = self
+            .p
+            .get(self.pos)
+            .unwrap_or_else(|| self.bad_position("premature end of input"));
         if c != &'\\' {
             result = *c;
             self.pos += 1;
         } else {
             self.pos += 1;
-            let c2 = self.p.get(self.pos).unwrap();
+            let c2 = self
+                .p
+                .get(self.pos)
+                .unwrap_or_else(|| self.bad_position("premature end of input"));
             result = if let Some(esc) = Self::escaped(*c2) {
                 esc
             } else {
@@ -317,10 +412,10 @@ impl Tokenizer {
         }
 
         if self.p.get(self.pos) != Some(&'\'') {
-            panic!("unclosed character literal");
+            self.bad_position("unclosed character literal");
         }
 
-        let mut t = self.new_token(TokenType::Num(result as u8 as i32));
+        let mut t = self.new_token(TokenType::Num(result as u8 as i64, 4, false));
         self.pos += 1;
         t.end = self.pos + 1;
         self.tokens.push(t);
@@ -331,11 +426,15 @@ impl Tokenizer {
         let mut sb = String::new();
         let mut len = 0;
         loop {
-            let mut c2 = self.p.get(self.pos + len).expect("PREMATURE end of input");
+            let mut c2 = self
+                .p
+                .get(self.pos + len)
+                .unwrap_or_else(|| self.bad_position("premature end of input"));
             if c2 == &'"' {
                 len += 1;
                 self.pos += len;
-                let mut t = self.new_token(TokenType::Str(sb, len));
+                // The size includes the terminating NUL.
+                let mut t = self.new_token(TokenType::Str(sb.clone(), sb.len() + 1));
                 t.start = self.pos - len - 1;
                 t.end = self.pos + 1;
                 self.tokens.push(t);
@@ -344,16 +443,19 @@ impl Tokenizer {
 
             if c2 != &'\\' {
                 len += 1;
-                sb.push(c2.clone());
+                sb.push(*c2);
                 continue;
             }
 
             len += 1;
-            c2 = self.p.get(self.pos + len).unwrap();
+            c2 = self
+                .p
+                .get(self.pos + len)
+                .unwrap_or_else(|| self.bad_position("premature end of input"));
             if let Some(esc) = Self::escaped(*c2) {
                 sb.push(esc);
             } else {
-                sb.push(c2.clone());
+                sb.push(*c2);
             }
             len += 1;
         }
@@ -370,6 +472,13 @@ impl Tokenizer {
         }
 
         let name: String = self.p[self.pos..self.pos + len].iter().collect();
+        match (name.as_str(), self.p.get(self.pos + len)) {
+            ("u8", Some('"')) | ("L", Some('"')) | ("L", Some('\'')) => {
+                return self.prefixed_literal(len);
+            }
+            _ => (),
+        }
+
         let mut t;
         if let Some(keyword) = keywords.get(&name) {
             t = self.new_token(keyword.clone());
@@ -381,7 +490,29 @@ impl Tokenizer {
         self.tokens.push(t);
     }
 
+    // A string or character literal with an encoding prefix. u8"..." is
+    // an ordinary byte string. Wide literals are not supported yet, so
+    // L"..." and L'x' are read as if they had no prefix.
+    fn prefixed_literal(&mut self, prefix_len: usize) {
+        let start = self.pos;
+        self.pos += prefix_len;
+        if self.p[self.pos] == '\'' {
+            self.char_literal();
+        } else {
+            self.string_literal();
+        }
+
+        let t = self.tokens.last_mut().unwrap();
+        t.start = start;
+        if self.p[start] == 'L' {
+            t.warn("wide character literals are not fully supported; treated as narrow");
+        }
+    }
+
     fn number(&mut self) {
+        if self.is_float() {
+            return self.float_number();
+        }
         match self.p.get(self.pos..self.pos + 2) {
             Some(&['0', 'x']) | Some(&['0', 'X']) => {
                 self.pos += 2;
@@ -394,19 +525,175 @@ impl Tokenizer {
         }
     }
 
+    fn is_hex_prefix(&self) -> bool {
+        matches!(
+            self.p.get(self.pos..self.pos + 2),
+            Some(&['0', 'x']) | Some(&['0', 'X'])
+        )
+    }
+
+    // A number is floating if its digits are followed by a '.' or an
+    // exponent ('e' for decimal, 'p' for hexadecimal).
+    fn is_float(&self) -> bool {
+        let (base, exp, start) = if self.is_hex_prefix() {
+            (16, 'p', self.pos + 2)
+        } else {
+            (10, 'e', self.pos)
+        };
+        let rest = self.p[start..].iter().find(|c| !c.is_digit(base));
+        match rest {
+            Some('.') => true,
+            Some(c) => c.to_ascii_lowercase() == exp,
+            None => false,
+        }
+    }
+
+    fn digits(&mut self, base: u32) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.p.get(self.pos) {
+            if !c.is_digit(base) {
+                break;
+            }
+            s.push(*c);
+            self.pos += 1;
+        }
+        s
+    }
+
+    // Reads a decimal floating constant such as 3.14, .5, 1e10 or
+    // 2.5E-3, or a hexadecimal one such as 0x1.8p3. A trailing f/F or
+    // l/L suffix is accepted; both are treated as double.
+    fn float_number(&mut self) {
+        let mut t = self.new_token(TokenType::FloatNum(0.0));
+        let is_hex = self.is_hex_prefix();
+        let base = if is_hex {
+            self.pos += 2;
+            16
+        } else {
+            10
+        };
+
+        let int_part = self.digits(base);
+        let mut frac_part = String::new();
+        if self.p.get(self.pos) == Some(&'.') {
+            self.pos += 1;
+            frac_part = self.digits(base);
+        }
+        if int_part.is_empty() && frac_part.is_empty() {
+            self.bad_position("malformed floating constant");
+        }
+
+        let mut exp = 0;
+        let exp_char = if is_hex { 'p' } else { 'e' };
+        match self.p.get(self.pos) {
+            Some(c) if c.to_ascii_lowercase() == exp_char => {
+                self.pos += 1;
+                let sign = match self.p.get(self.pos) {
+                    Some('+') => 1,
+                    Some('-') => -1,
+                    _ => 0,
+                };
+                if sign != 0 {
+                    self.pos += 1;
+                }
+                let digits = self.digits(10);
+                if digits.is_empty() {
+                    self.bad_position("exponent has no digits");
+                }
+                exp = digits.parse::<i32>().unwrap_or(i32::MAX) * if sign < 0 { -1 } else { 1 };
+            }
+            _ if is_hex => self.bad_position("hexadecimal floating constant requires an exponent"),
+            _ => (),
+        }
+
+        let val = if is_hex {
+            let mut mantissa = 0.0;
+            for c in int_part.chars().chain(frac_part.chars()) {
+                mantissa = mantissa * 16.0 + c.to_digit(16).unwrap() as f64;
+            }
+            // Beyond 2^±2200 every mantissa over- or underflows anyway.
+            // The scaling is in two steps, so that a large mantissa times
+            // a tiny power of two doesn't underflow before the product.
+            let exp = exp
+                .saturating_sub(4 * frac_part.len() as i32)
+                .clamp(-2200, 2200);
+            if mantissa == 0.0 {
+                0.0
+            } else {
+                mantissa * 2f64.powi(exp / 2) * 2f64.powi(exp - exp / 2)
+            }
+        } else {
+            format!("0{}.{}e{}", int_part, frac_part, exp)
+                .parse::<f64>()
+                .unwrap()
+        };
+
+        if let Some('f') | Some('F') | Some('l') | Some('L') = self.p.get(self.pos) {
+            self.pos += 1;
+        }
+
+        t.ty = TokenType::FloatNum(val);
+        t.end = self.pos;
+        self.tokens.push(t);
+    }
+
     fn parse_number(&mut self, base: u32) {
-        let mut sum: i32 = 0;
+        let mut sum: u64 = 0;
         let mut len = 0;
         for c in self.p[self.pos..].iter() {
             if let Some(val) = c.to_digit(base) {
-                sum = sum * base as i32 + val as i32;
+                sum = sum
+                    .checked_mul(base as u64)
+                    .and_then(|sum| sum.checked_add(val as u64))
+                    .unwrap_or_else(|| self.bad_position("integer constant is too large"));
                 len += 1;
             } else {
                 break;
             }
         }
-        let mut t = self.new_token(TokenType::Num(sum as i32));
+        let mut t = self.new_token(TokenType::Num(0, 4, false));
+        if base == 16 {
+            t.start -= 2; // Include the "0x" prefix.
+        }
         self.pos += len;
+
+        // Suffixes: u and l or ll in either order and either case.
+        let (mut is_unsigned, mut is_long) = (false, false);
+        loop {
+            match self.p.get(self.pos) {
+                Some('u') | Some('U') if !is_unsigned => is_unsigned = true,
+                Some(&c) if (c == 'l' || c == 'L') && !is_long => {
+                    is_long = true;
+                    if self.p.get(self.pos + 1) == Some(&c) {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+
+        // The type is the first of int, unsigned int, long and unsigned
+        // long that holds the value. A decimal constant without a `u`
+        // skips unsigned int, and is unsigned long only if no long can
+        // hold it.
+        let fits = |size, unsigned| match (size, unsigned) {
+            (4, false) => sum <= i32::MAX as u64,
+            (4, true) => sum <= u32::MAX as u64,
+            (_, false) => sum <= i64::MAX as u64,
+            _ => true,
+        };
+        let (size, is_unsigned) = [(4, false), (4, true), (8, false), (8, true)]
+            .iter()
+            .cloned()
+            .find(|&(size, unsigned)| {
+                (size == 8 || !is_long)
+                    && (unsigned || !is_unsigned)
+                    && (!unsigned || is_unsigned || base != 10 || size == 8)
+                    && fits(size, unsigned)
+            })
+            .unwrap();
+        t.ty = TokenType::Num(sum as i64, size, is_unsigned);
         t.end = self.pos;
         self.tokens.push(t);
     }
@@ -414,8 +701,7 @@ impl Tokenizer {
     fn canonicalize_newline(&mut self) {
         let mut pos = 0;
         while pos < self.p.len() {
-            if self.p[pos] == '\r' && self.p[pos + 1] == '\n' {
-                Rc::get_mut(&mut self.p).unwrap().remove(pos);
+            if self.p[pos] == '\r' && self.p.get(pos + 1) == Some(&'\n') {
                 Rc::get_mut(&mut self.p).unwrap().remove(pos);
             }
             pos += 1;
@@ -429,11 +715,12 @@ impl Tokenizer {
         let mut pos = 0;
         let mut cnt = 0;
         while pos < self.p.len() {
-            if self.p[pos] == '\\' && self.p[pos + 1] == '\n' {
+            if self.p[pos] == '\\' && self.p.get(pos + 1) == Some(&'\n') {
+                // The next line may itself start with a backslash-newline,
+                // so pos is not advanced.
                 cnt += 1;
                 Rc::get_mut(&mut self.p).unwrap().remove(pos);
                 Rc::get_mut(&mut self.p).unwrap().remove(pos);
-                pos += 1;
             } else if self.p[pos] == '\n' {
                 for _ in 0..cnt {
                     Rc::get_mut(&mut self.p).unwrap().insert(pos, '\n');
@@ -469,7 +756,8 @@ impl Tokenizer {
                 {
                     let new = self.append(last_str, t_str, last.start);
                     v.pop();
-                    v.push(new);
+                    v.push(new.clone());
+                    last_may = Some(new);
                     continue;
                 }
             }
@@ -489,47 +777,37 @@ impl Tokenizer {
             .collect()
     }
 
-    fn bad_position(&self, msg: &'static str) {
-        print_line(&self.p, &self.filename, self.pos);
-        panic!("{}", msg);
+    fn bad_position(&self, msg: &'static str) -> ! {
+        error_at(&self.p, &self.filename, self.pos, msg)
     }
 }
 
-// Finds a line pointed by a given pointer from the input file
-// to print it out.
-fn print_line(buf: &[char], path: &str, pos: usize) {
-    let mut p = 0;
-    let mut start = 0;
-    let mut line = 0;
-    let mut col = 0;
-    for c in buf.iter() {
-        if c == &'\n' {
-            start = pos + 1;
-            line += 1;
-            col = 0;
-            p += 1;
-            continue;
-        }
-
-        if p != pos {
-            col += 1;
-            p += 1;
-            continue;
-        }
-
-        print!("error at {}:{}:{}\n\n", path, line + 1, col);
-        break;
-    }
+// Aborts compilation with an error at a given position of the input,
+// showing the line it is on:
+//
+//   error at foo.c:1:11
+//
+//   int x = 1 $ 2;
+//             ^
+fn error_at(buf: &[char], path: &str, pos: usize, msg: &str) -> ! {
+    let before = &buf[..pos.min(buf.len())];
+    let line = before.iter().filter(|c| *c == &'\n').count() + 1;
+    let col = before.iter().rev().take_while(|c| *c != &'\n').count();
+    let start = pos.min(buf.len()) - col;
+    let text: String = buf[start..].iter().take_while(|c| *c != &'\n').collect();
+
+    let context = format!(
+        "error at {}:{}:{}\n\n{}\n{}^\n",
+        path,
+        line,
+        col + 1,
+        text,
+        " ".repeat(col)
+    );
+    panic::panic_any(CompileError::new(msg.to_string(), context));
+}
 
-    for p in buf[start..].iter() {
-        if p == &'\n' {
-            break;
-        }
-        print!("{}", p);
-    }
-    println!();
-    for _ in 0..col - 1 {
-        print!(" ");
-    }
-    print!("^\n\n");
+// Aborts compilation with an error that has no position in the input.
+pub fn error(msg: &str) -> ! {
+    panic::panic_any(CompileError::new(msg.to_string(), String::new()));
 }
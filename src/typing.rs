@@ -0,0 +1,76 @@
+use crate::{Ctype, TokenType, Type};
+
+// The typing rules that need only the types of the operands. sema types
+// the tree with them, and the parser types the operand of `typeof` with
+// them, since it has to know that type before sema runs.
+
+pub fn is_integer(ty: &Type) -> bool {
+    std::matches!(ty.ty, Ctype::Int | Ctype::Char | Ctype::Bool)
+}
+
+// The usual arithmetic conversions. Integers narrower than int are
+// promoted to int, and then the wider operand's type is used, or the
+// unsigned one if both are equally wide. With a pointer operand, the
+// type of lhs is used as is.
+pub fn arith_conv(lhs: &Type, rhs: &Type) -> Box<Type> {
+    if !is_integer(lhs) || !is_integer(rhs) {
+        return Box::new(lhs.clone());
+    }
+
+    let promote = |ty: &Type| {
+        if ty.size < 4 {
+            Type::int_ty()
+        } else {
+            Type {
+                bitfield: None,
+                ..ty.clone()
+            }
+        }
+    };
+    let (lhs, rhs) = (promote(lhs), promote(rhs));
+    if lhs.size > rhs.size || (lhs.size == rhs.size && lhs.is_unsigned) {
+        Box::new(lhs)
+    } else {
+        Box::new(rhs)
+    }
+}
+
+// An array decays to a pointer to its first element, and a function
+// to a pointer to itself.
+pub fn decay(ty: &Type) -> Type {
+    match ty.ty {
+        Ctype::Ary(ref ary_of, _) => Type::ptr_to(ary_of.clone()),
+        Ctype::Func(..) => Type::ptr_to(Box::new(ty.clone())),
+        _ => ty.clone(),
+    }
+}
+
+fn is_assign(op: &TokenType) -> bool {
+    use self::TokenType::*;
+    std::matches!(
+        op,
+        Equal | AddEQ | SubEQ | MulEQ | DivEQ | ModEQ | ShlEQ | ShrEQ | BitandEQ | XorEQ | BitorEQ
+    )
+}
+
+// The type of `lhs op rhs`. An assignment has the type of its left
+// operand, which does not decay.
+pub fn binop_type(op: &TokenType, lhs: &Type, rhs: &Type) -> Box<Type> {
+    use self::TokenType::*;
+    if is_assign(op) {
+        return Box::new(lhs.clone());
+    }
+
+    let (lhs, rhs) = (decay(lhs), decay(rhs));
+    let is_ptr = |ty: &Type| std::matches!(ty.ty, Ctype::Ptr(_));
+    match op {
+        Minus if is_ptr(&lhs) && is_ptr(&rhs) => Box::new(Type::ptrdiff_t()),
+        Plus | Minus if is_ptr(&lhs) => Box::new(lhs),
+        Plus if is_ptr(&rhs) => Box::new(rhs),
+        Comma => Box::new(rhs),
+        EQ | NE | LE | LeftAngleBracket | Logand | Logor => Box::new(Type::int_ty()),
+        // The result has the type of the promoted left operand.
+        SHL | SHR => arith_conv(&lhs, &lhs),
+        _ => arith_conv(&lhs, &rhs),
+    }
+}
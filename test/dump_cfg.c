@@ -0,0 +1,13 @@
+int max(int a, int b) {
+  int m;
+  if (a < b)
+    m = b;
+  else
+    m = a;
+  return m;
+}
+
+int dead(int x) {
+  return x;
+  x = 1;
+}
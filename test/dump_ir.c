@@ -0,0 +1,10 @@
+int sum(int n) {
+  int s = 0;
+  for (int i = 1; i <= n; i++)
+    s = s + i / 2;
+  return s;
+}
+
+int twice(int *a, int i) {
+  return a[i] + a[i];
+}
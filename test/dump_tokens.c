@@ -0,0 +1,6 @@
+int main() {
+  char *s = "hi";
+  return 0x1F + 3.14 + .5 + 1e10 + 2.5E-3f + 0x1.8p3;
+  0x1.1p-99999999999 + 0x1p99999999999 + 0x0p99999999999 + 0x100000000p-1090;
+}
+char *u = u8"abc";
@@ -0,0 +1,2 @@
+// array initializer must be enclosed in braces
+int main() { int a[2] = 1; return 0; }
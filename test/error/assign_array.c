@@ -0,0 +1,2 @@
+// array is not assignable
+int main() { int a[2]; int b[2]; a = b; return 0; }
@@ -0,0 +1,3 @@
+// cannot take address of bit-field
+struct { int a : 3; } x;
+int main() { int *p = &x.a; return 0; }
@@ -0,0 +1,2 @@
+// called object is not a function
+int main() { int x = 1; return (x + 1)(2); }
@@ -0,0 +1,2 @@
+// invalid combination of type specifiers
+int main() { char long x; return 0; }
@@ -0,0 +1,2 @@
+// comma operator in constant expression
+int main() { int a[(1, 2)]; return sizeof(a); }
@@ -0,0 +1,2 @@
+// comma operator in constant expression
+int main() { int a[0 ? 3 : (4, 5)]; return sizeof(a); }
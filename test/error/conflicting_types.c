@@ -0,0 +1,3 @@
+// conflicting types for f
+int f(int);
+int f(int a, int b) { return a + b; }
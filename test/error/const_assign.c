@@ -0,0 +1,3 @@
+// assignment of read-only location
+const int x = 1;
+int main() { x = 2; return x; }
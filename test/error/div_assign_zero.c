@@ -0,0 +1,2 @@
+// division by zero
+int main() { int x=1; x /= 0; return x; }
@@ -0,0 +1,7 @@
+// duplicate label 'again'
+int main() {
+again:
+  ;
+again:
+  return 0;
+}
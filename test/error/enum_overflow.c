@@ -0,0 +1,2 @@
+// enumerator value is out of range of int
+enum { A = 2147483647, B };
@@ -0,0 +1,2 @@
+// constant expression expected
+enum { A = 1 << 40 };
@@ -0,0 +1,4 @@
+// unexpected end of file
+int main() {
+  if (1) {
+    return 0;
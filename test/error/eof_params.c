@@ -0,0 +1,2 @@
+// unexpected end of file
+int f(
\ No newline at end of file
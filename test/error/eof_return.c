@@ -0,0 +1,2 @@
+// unexpected end of file
+int main() { return 0
\ No newline at end of file
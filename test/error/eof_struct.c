@@ -0,0 +1,2 @@
+// unexpected end of file
+struct point { int x;
@@ -0,0 +1,8 @@
+// #error unsupported target
+#ifdef __unix__
+#error this target is not inactive
+#endif
+#ifndef __r9cc__
+#error unsupported target
+#endif
+int main() { return 0; }
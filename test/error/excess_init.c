@@ -0,0 +1,3 @@
+// excess elements in array initializer
+int a[2] = {1, 2, 3};
+int main() { return a[0]; }
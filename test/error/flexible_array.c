@@ -0,0 +1,2 @@
+// flexible array member not at end of struct
+struct s { int n; int a[][2]; int x; };
@@ -0,0 +1,2 @@
+// floating-point constants are not supported
+int main() { return 1e10; }
@@ -0,0 +1,2 @@
+// floating-point constants are not supported
+int main() { return 3.14; }
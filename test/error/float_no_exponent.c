@@ -0,0 +1,2 @@
+// exponent has no digits
+int main() { return 1e; }
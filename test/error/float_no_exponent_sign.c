@@ -0,0 +1,2 @@
+// exponent has no digits
+int main() { return 2.5E-; }
@@ -0,0 +1,2 @@
+// undeclared identifier: i
+int f() { for (int i = 0; i < 3; i++); return i; }
@@ -0,0 +1,4 @@
+// initializer element is not constant
+int x;
+int y = x;
+int main() { return y; }
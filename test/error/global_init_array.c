@@ -0,0 +1,3 @@
+// array initializer must be enclosed in braces
+int a[2] = 1;
+int main() { return a[0]; }
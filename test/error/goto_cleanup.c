@@ -0,0 +1,3 @@
+// goto in the scope of a variable with a cleanup
+void f(int *p) {}
+int main() { int a __attribute__((cleanup(f))) = 1; goto out; out: return 0; }
@@ -0,0 +1,2 @@
+// floating-point constants are not supported
+int main() { return 0X1.8P3L; }
@@ -0,0 +1,2 @@
+// hexadecimal floating constant requires an exponent
+int main() { return 0x1.8; }
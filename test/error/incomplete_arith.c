@@ -0,0 +1,3 @@
+// arithmetic on pointer to incomplete type
+struct s;
+int main() { struct s *p = 0; p = p + 1; return 0; }
@@ -0,0 +1,3 @@
+// variable has incomplete type: x
+struct s;
+int main() { struct s x; return 0; }
@@ -0,0 +1,2 @@
+// invalid combination of type specifiers
+int main() { int _Bool x; return 0; }
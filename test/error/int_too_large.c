@@ -0,0 +1,2 @@
+// integer constant is too large
+int main() { return 18446744073709551616; }
@@ -0,0 +1,2 @@
+// division by zero
+int main() { return 5 % (2-2); }
@@ -0,0 +1,2 @@
+// array size is negative
+int a[1 - 2];
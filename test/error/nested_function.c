@@ -0,0 +1,5 @@
+// nested function definitions are not allowed
+int main() {
+  int f() { return 1; }
+  return f();
+}
@@ -0,0 +1,3 @@
+// x is not a function
+int x;
+int f() { return x(); }
@@ -0,0 +1,3 @@
+// no such member
+struct s { int a; };
+int f() { return __builtin_offsetof(struct s, b); }
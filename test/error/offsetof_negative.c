@@ -0,0 +1,3 @@
+// negative array index in offsetof
+struct s { int a[4]; };
+int f() { return __builtin_offsetof(struct s, a[-1]); }
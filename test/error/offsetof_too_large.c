@@ -0,0 +1,3 @@
+// offsetof is too large
+struct s { int a[4]; };
+int f() { return __builtin_offsetof(struct s, a[4611686018427387904]); }
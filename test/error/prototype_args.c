@@ -0,0 +1,4 @@
+// wrong number of arguments to f: 1 expected, but got 2
+extern int f(int);
+int main() { return f(1, 2); }
+int f(int x) { return x; }
@@ -0,0 +1,5 @@
+// invalid operands to binary -
+int main() {
+  int a[2];
+  return 1 - a;
+}
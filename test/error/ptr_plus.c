@@ -0,0 +1,6 @@
+// invalid operands to binary +
+int main() {
+  int a[2];
+  int *p = a;
+  return p + a;
+}
@@ -0,0 +1,2 @@
+// restrict requires a pointer type
+int f() { int restrict x; return 0; }
@@ -0,0 +1,2 @@
+// invalid combination of type specifiers
+int main() { signed unsigned x; return 0; }
@@ -0,0 +1,3 @@
+// invalid application of sizeof to incomplete type
+struct s;
+int main() { struct s *p = 0; return sizeof(*p); }
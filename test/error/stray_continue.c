@@ -0,0 +1,2 @@
+// stray 'continue' statement
+int main() { continue; return 0; }
@@ -0,0 +1,3 @@
+// struct used as a value
+struct s { int x; };
+int main() { struct s a; return a + 1; }
@@ -0,0 +1,3 @@
+// wrong number of arguments to f: 2 expected, but got 1
+int f(int a, int b) { return a + b; }
+int main() { return f(1); }
@@ -0,0 +1,5 @@
+// undeclared identifier: y
+int x;
+int main() {
+  return x + y;
+}
@@ -0,0 +1,2 @@
+// undefined function: g
+int f() { return g(); }
@@ -0,0 +1,5 @@
+// label 'out' used but not defined
+int main() {
+  goto out;
+  return 0;
+}
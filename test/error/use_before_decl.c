@@ -0,0 +1,6 @@
+// undeclared identifier: x
+int main() {
+  x = 1;
+  int x;
+  return x;
+}
@@ -0,0 +1,3 @@
+// wrong number of arguments to f: at least 1 expected, but got 0
+int f(int n, ...);
+int main() { return f(); }
@@ -0,0 +1,2 @@
+// array size must be a constant expression
+int f(int n) { int a[n]; return 0; }
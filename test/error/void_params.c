@@ -0,0 +1,3 @@
+// wrong number of arguments to f: 0 expected, but got 1
+int f(void);
+int main() { return f(1); }
@@ -1,3 +1,22 @@
 // This file is compiled by gcc.
 
  int global_arr[1] = {5};
+
+int is_aligned(void *p, int align) { return (long)p % align == 0; }
+
+int sprintf(char *buf, const char *fmt, ...);
+
+// printf of a double uses SSE instructions that fault on a misaligned
+// stack, and rbp is 16-byte aligned only if rsp was at the call.
+int sse_callee(void) {
+  char buf[16];
+  int n = sprintf(buf, "%.1f", 1.5);
+  return n + ((long)__builtin_frame_address(0) % 16 == 0);
+}
+
+int gcc_digits8(int a, int b, int c, int d, int e, int f, int g, int h) {
+  return ((((((a * 10 + b) * 10 + c) * 10 + d) * 10 + e) * 10 + f) * 10 + g) * 10 + h;
+}
+
+int digits8(int a, int b, int c, int d, int e, int f, char g, int h);
+int gcc_calls_digits8(void) { return digits8(9, 8, 7, 6, 5, 4, 3, 2); }
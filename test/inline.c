@@ -0,0 +1,19 @@
+// Compiled with -finline: every call below must be inlined, so the
+// generated assembly must not contain a call instruction.
+static inline int square(int x) { return x * x; }
+inline int sum(int a, int b) { int x = a; return x + b; }
+
+int main() {
+  int a = 3;
+  int b = 4;
+  int x = 10;
+  if (sum(square(a), square(b)) != 25)
+    return 1;
+  if (square(sum(a, b)) != 49)
+    return 2;
+  if (sum(x, a) != 13)
+    return 3;
+  if (square(a++) != 9 || a != 4)
+    return 4;
+  return 0;
+}
@@ -0,0 +1,4 @@
+#pragma once
+// Included twice from test.c; a second copy of this definition would
+// fail to assemble.
+int once_defined() { return 7; }
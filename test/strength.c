@@ -0,0 +1,24 @@
+// Compiled with the Makefile: multiplication and division by powers
+// of two must be emitted as shifts, without mul or idiv.
+int main() {
+  int x = 5;
+  int n = -7;
+  unsigned u = -1;
+  if (x * 8 != 40)
+    return 1;
+  if (8 * x != 40)
+    return 2;
+  if (x / 4 != 1)
+    return 3;
+  if (n / 4 != -1)
+    return 4;
+  if (n / 2 != -3)
+    return 5;
+  if ((n - 1) / 4 != -2)
+    return 6;
+  if (u / 2 != 2147483647)
+    return 7;
+  if (n * 1024 != -7168)
+    return 8;
+  return 0;
+}
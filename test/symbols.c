@@ -0,0 +1,10 @@
+int printf();
+int count = 3;
+char *names[4];
+static int twice(int x);
+static int s = 1;
+static int zero;
+const int limit = 10;
+static const int secret = 7;
+int main() { return printf("%d", twice(count) + s + zero + limit + secret); }
+int twice(int x) { return x * 2; }
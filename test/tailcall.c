@@ -0,0 +1,21 @@
+// Compiled with the Makefile at -O1: self tail calls must reuse the
+// frame, or this many calls overflow the stack.
+int count(int n, int acc) {
+  if (n == 0)
+    return acc;
+  return count(n - 1, acc + 1);
+}
+
+long sum(long n, long acc) {
+  if (!n)
+    return acc;
+  return sum(n - 1, acc + n);
+}
+
+int main() {
+  if (count(10000000, 0) != 10000000)
+    return 1;
+  if (sum(10000000, 0) != 50000005000000)
+    return 2;
+  return 0;
+}
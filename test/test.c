@@ -1,8 +1,9 @@
 extern void *stderr;
 
-int printf();
-int fprintf();
+int printf(char *fmt, ...);
+int fprintf(void *fp, char *fmt, ...);
 int exit();
+int is_aligned();
 
 #define EXPECT(expected, expr)                                  \
   do {                                                          \
@@ -17,6 +18,19 @@ int exit();
     }                                                           \
   } while (0)
 
+int line_before_splice() { return __LINE__; }
+#define SUCC(x) \
+  ((x) + 1)
+#define SPLICED 1 \
+\
+  + 2
+int succ3() { return SUCC(3); }
+int spliced() { return SPLICED; }
+int line_after_splice() { return __LINE__; }
+
+#include "test/once.h"
+#include "./test/once.h"
+
 int one() { return 1; }
 int two() { return 2; }
 int plus(int x, int y) { return x + y; }
@@ -26,11 +40,129 @@ int add2(int (*a)[2]) { return a[0][0] + a[1][0]; }
 int add3(int a[][2]) { return a[0][0] + a[1][0]; }
 int add4(int a[2][2]) { return a[0][0] + a[1][0]; }
 void nop() {}
+int three(void) { return 3; }
+int four(void);
+extern int scale(int, int *);
+extern char first(char *[], int (*)[2]);
+int sprintf(char *buf, char *fmt, ...);
+int first_arg(int n, ...) { return n; }
+struct point { int x; int y; };
+struct nested { char c; struct point p[3]; int z; };
+#define offsetof(type, member) __builtin_offsetof(type, member)
+#define NULL 0
+int is_null(int *p) { return p == NULL; }
+int not_null(int *p) { return p != NULL; }
+int offsetof_y() { return offsetof(struct point, y); }
+int point_sum(struct point *p) { return p->x + p->y; }
+__extension__ int deref_sum(int *__restrict a, int *__restrict__ b) { return *a + *b; }
+int fact(int n, int acc) { if (n == 0) return acc; return fact(n - 1, acc * n); }
+int count(int n, int acc) { if (n == 0) return acc; return count(n - 1, acc + 1); }
+int tail_addr(int n, int *p) { int x = n + 10; if (!n) return *p; return tail_addr(n - 1, &x); }
+int is_even(int n) { return n == 0 ? 1 : is_odd(n - 1); }
+int is_odd(int n) { return n == 0 ? 0 : is_even(n - 1); }
+int xfunc() { return __func__[0]; }
+int func_len() { return sizeof(__func__); }
+int func_last() { char *p = __func__; return p[8]; }
+inline int ifunc() { return __func__[0]; }
+int func_shadowed() { int __func__ = 3; return __func__; }
+int count_up(int n) { int i = 0; loop: if (i >= n) goto done; i++; goto loop; done: return i; }
+int interp(int *prog) {
+  void *ops[3];
+  ops[0] = &&inc; ops[1] = &&dbl; ops[2] = &&halt;
+  int acc = 0;
+  goto *ops[*prog];
+inc: acc++; prog++; goto *ops[*prog];
+dbl: acc = acc * 2; prog++; goto *ops[*prog];
+halt: return acc;
+}
+enum { BUFSZ = 4 };
+int enum_buf[BUFSZ * 2];
+int var_inline;
+static inline int add_g(int a) { return a + var_inline; }
+inline int twice(int a) { return add_g(a) + add_g(a); }
+__inline__ int swap_sub(int b, int a) { return b - a; }
 
 int var1;
 int var2[5];
+int var3[3];
+char var4;
+char var5 __attribute__((aligned(32)));
 extern int global_arr[1];
+int g_init = 7;
+char g_neg = -2;
+int g_int_min = -2147483648;
+long g_long_big = 3000000000;
+unsigned g_uint_max = 0xffffffff;
+int g_multi1 = 1, g_multi2 = 2, *g_multi_ptr = &g_multi2, g_multi_bss, g_multi_ary[] = {3, 4};
+typedef int multi_int, *multi_ptr;
+int *g_ptr = &g_init;
+int **g_pptr = &g_ptr;
+int *g_elem = global_arr;
+int *g_null = 0;
+char *g_str = "str";
+const int g_table[] = {1, 2, 3, 4};
+const char g_name[8] = "const";
+int g_grid[2][3] = {{1, 2, 3}, {4, 5}};
+typeof(g_grid) g_grid2;
+int g_table_size = sizeof g_table;
+const int *g_third = &g_table[2];
+const int *g_second = g_table + 1;
+struct nested g_nested;
+int *g_nested_y = &g_nested.p[1].y;
+char *g_strs[] = {"ab", "cde", 0};
+#define SWAP(a, b) ({ typeof(a) tmp = a; a = b; b = tmp; return 0; })
+int typeof_param(char c) { __typeof__(c) d = 300; return d; }
+int typeof_swap() { int a = 12; int b = 21; SWAP(a, b); return a * 100 + b; }
+int param_size(int a[10]) { return sizeof(a); }
+int param_unsized(int a[]) { return sizeof(a); }
+int param_rows(int a[3][4]) { return sizeof(a) * 100 + sizeof(*a); }
+int param_grid(int a[][3], int i, int j) { return a[i][j]; }
+int param_write(int a[2]) { a[1] = 7; a = 0; return 0; }
+int same_ptr(void *p, void *q) { return p == q; }
+int fp_apply(int (*f)(int, int), int x, int y) { return f(x, y) * 10 + (*f)(y, x); }
+int (*g_fp)(int, int) = mul;
+typedef int (*fp_sum8)(int, int, int, int, int, int, int, int);
+int sum8(int a, int b, int c, int d, int e, int f, int g, int h) { return a + b + c + d + e + f + g + h; }
+int digits8(int a, int b, int c, int d, int e, int f, char g, int h) { return ((((((a * 10 + b) * 10 + c) * 10 + d) * 10 + e) * 10 + f) * 10 + g) * 10 + h; }
+int gcc_digits8();
+int gcc_calls_digits8();
+int last8(int a, int b, int c, int d, int e, int f, int g, int *h) { return *h; }
+int count8(int n, int b, int c, int d, int e, int f, int g, int h) { if (n == 0) return h; return count8(n - 1, b, c, d, e, f, g, h + 2); }
+int prime_at(int i) { static const int primes[] = {2, 3, 5, 7}; return primes[i]; }
+int static_count() { static int n; static int *p = &n; *p += 1; return n; }
+int static_pair() { static int a = 1, b = 5; static int (*p)[3]; a++; return a * 10 + b + !p; }
+static inline int static_inline() { static int n; return ++n; }
+int cleanup_log;
+void log_cleanup(int *p) { cleanup_log = cleanup_log * 10 + *p; }
+void zero(int *p) { *p = 0; }
+int cleanup_block() { cleanup_log = 0; { int a __attribute__((cleanup(log_cleanup))) = 1; int b __attribute__((cleanup(log_cleanup))) = 2; } return cleanup_log; }
+int cleanup_return(int x) { cleanup_log = 0; int a __attribute__((cleanup(log_cleanup))) = 3; if (x) { int b __attribute__((cleanup(log_cleanup))) = 4; return x + a; } return 0; }
+int cleanup_value() { int a __attribute__((cleanup(zero))) = 6; return a; }
+int cleanup_continue() { cleanup_log = 0; for (int i = 1; i <= 2; i++) { int a __attribute__((cleanup(log_cleanup))) = i; if (i == 1) continue; } return cleanup_log; }
+int cleanup_break() { cleanup_log = 0; while (1) { int a __attribute__((cleanup(log_cleanup))) = 5; { int b __attribute__((cleanup(log_cleanup))) = 6; break; } } return cleanup_log; }
+int cleanup_for_init(int n) { cleanup_log = 0; for (int a __attribute__((cleanup(log_cleanup))) = 7; a < 9; a++) if (a == n) break; return cleanup_log; }
+int cleanup_for_init_return() { cleanup_log = 0; for (int a __attribute__((cleanup(log_cleanup))) = 4; ; a++) { int b __attribute__((cleanup(log_cleanup))) = 2; return a; } }
+int cleanup_inner_break() { cleanup_log = 0; do { int a __attribute__((cleanup(log_cleanup))) = 7; for (;;) { int b __attribute__((cleanup(log_cleanup))) = 8; break; } } while (0); return cleanup_log; }
+#ifdef NULL
+int null_defined = 1;
+#else
+#error NULL should be defined
+#endif
+#ifdef NOT_DEFINED
+#error this group is skipped
+#ifndef NULL
+#error and so is this nested one
+#endif
+#endif
+int sse_callee();
+int sse_frame0() { return sse_callee(); }
+int sse_frame1() { char c = 1; return sse_callee() + c - 1; }
+int sse_frame2() { int a[3]; a[2] = 0; return sse_frame1() + a[2]; }
+int sse_frame3(int x) { char b[9]; b[8] = x; return sse_frame2() + b[8]; }
+extern char huge[65536][65536];
 typedef int myint;
+typedef _Bool bool;
+enum color { RED, GREEN = 5, BLUE, };
 
 // Single-line comment test
 
@@ -44,6 +176,34 @@ int main() {
   EXPECT(493, 0755);
   EXPECT(48879, 0xBEEF);
   EXPECT(255, 0Xff);
+  EXPECT(2147483647, 2147483647);
+  EXPECT(-2147483648, -2147483648);
+  EXPECT(-1, 0xffffffff);
+  EXPECT(1, -2147483648 < 0);
+  EXPECT(1, -2147483647 - 1 == -2147483648);
+  EXPECT(1, ({ int x = -2147483648; return x == -2147483647 - 1; }));
+  EXPECT(1, ({ int x = 2147483647; return x > 0 && x + 1 < 0; }));
+  EXPECT(-2147483648, ({ enum { MIN = -2147483648 } e; return MIN; }));
+  EXPECT(-2147483648, ({ enum { WRAP = -(-2147483647 - 1) } e; return WRAP; }));
+  EXPECT(73, ({ enum { SHADOW = 7 } e; int r; { int SHADOW = 3; r = SHADOW; } return SHADOW * 10 + r; }));
+  EXPECT(7, ({ int SHADOW = 3; int r; { enum { SHADOW = 7 } e; r = SHADOW; } return r; }));
+  EXPECT(1, ({ int x = -2147483647 - 1; return -x == x && -x < 0; }));
+  EXPECT(1, g_int_min < 0 && g_int_min == -2147483647 - 1);
+  EXPECT(1, 2147483648 > 0);
+  EXPECT(8, sizeof(2147483648));
+  EXPECT(1, ({ long x = 3000000000; return x > 0 && x / 1000 == 3000000; }));
+  EXPECT(1, g_long_big / 1000 == 3000000 && g_uint_max / 2 == 2147483647);
+  EXPECT(4, sizeof(0xffffffff));
+  EXPECT(1, 0xffffffff > 0);
+  EXPECT(8, sizeof(0x100000000));
+  EXPECT(8, sizeof(9223372036854775808));
+  EXPECT(4, sizeof(1u));
+  EXPECT(8, sizeof(1L));
+  EXPECT(8, sizeof(1uLL));
+  EXPECT(8, sizeof(1LU));
+  EXPECT(1, 1u - 2 > 0);
+  EXPECT(1, ({ enum { E = 2147483647 + 1L > 0 } e; return E; }));
+  EXPECT(1, ({ enum { E = 0u - 1 > 0 } e; return E; }));
   EXPECT(2, 1+1);
   EXPECT(10, 2*3+4);
   EXPECT(26, 2*3+4*5);
@@ -62,6 +222,89 @@ int main() {
   EXPECT(5, plus(2, 3));
   EXPECT(1, one());
   EXPECT(3, one()+two());
+  EXPECT(7, three() + four());
+  EXPECT(12, ({ int x = 3; return scale(4, &x); }));
+  EXPECT(97, ({ char *s[1]; int a[2][2]; s[0] = "abc"; return first(s, a); }));
+  EXPECT(3, point_sum(&(struct point){1, 2}));
+  EXPECT(7, ({ struct point p = {3, 4}; return p.x + p.y; }));
+  EXPECT(3, ({ struct point p = {3}; return p.x; }));
+  EXPECT(2, ((struct point){1, 2}).y);
+  EXPECT(3, ({ int *p = (int[]){1, 2, 3}; return p[2]; }));
+  EXPECT(12, sizeof((int[]){1, 2, 3}));
+  EXPECT(0, (&(struct point){5})->y);
+  EXPECT(0, ((int[4]){1})[3]);
+  // Each iteration reuses the storage that the previous one dirtied.
+  EXPECT(0, ({ int s = 0; for (int i = 0; i < 2; i++) { struct point *q = &(struct point){5}; s += q->y; q->y = 9; } return s; }));
+  EXPECT(0, ({ int s = 0; for (int i = 0; i < 2; i++) { int *p = (int[4]){1}; s += p[3]; p[3] = 9; } return s; }));
+  EXPECT(0, ({ int s = 0; for (int i = 0; i < 2; i++) { int a[4] = {1}; s += a[3]; a[3] = 9; } return s; }));
+  EXPECT(5, (int){5});
+  EXPECT(6, ({ int *p = (int[3]){1, 2, 3}; return p[0] + p[1] + p[2]; }));
+  EXPECT(-3, ({ int a[] = {1, -3}; return a[1]; }));
+  EXPECT(1, &one != 0);
+  EXPECT(1, is_even(10));
+  EXPECT(3628800, fact(10, 1));
+  EXPECT(10000, count(10000, 0));
+  EXPECT(11, ({ int y = 0; return tail_addr(3, &y); }));
+  EXPECT('x', xfunc());
+  EXPECT(9, func_len());
+  EXPECT('t', func_last());
+  EXPECT(3, func_shadowed());
+  EXPECT('i', ifunc());
+  EXPECT('m', __func__[0]);
+  EXPECT(5, count_up(5));
+  EXPECT(0, count_up(0));
+  EXPECT(10, ({ int prog[6]; prog[0] = 0; prog[1] = 0; prog[2] = 1; prog[3] = 0; prog[4] = 1; prog[5] = 2; return interp(prog); }));
+  EXPECT(0, ({ int prog[1]; prog[0] = 2; return interp(prog); }));
+  EXPECT(8, ({ void *p = &&here; here: return sizeof(p); }));
+  EXPECT(1, is_odd(7));
+  EXPECT(7, once_defined());
+  EXPECT(4, __builtin_offsetof(struct point, y));
+  EXPECT(0, __builtin_offsetof(struct point, x));
+  EXPECT(4, offsetof_y());
+  EXPECT(28, __builtin_offsetof(struct nested, z));
+  EXPECT(24, __builtin_offsetof(struct nested, p[2].y));
+  EXPECT(1, ({ struct nested n; char *base = &n; char *q = &n.p[1].x; return q == base + __builtin_offsetof(struct nested, p[1]); }));
+  EXPECT(8, ({ int a[__builtin_offsetof(struct point, y) / 2]; return sizeof(a); }));
+  EXPECT(32, sizeof(enum_buf));
+  EXPECT(16, ({ int b[BUFSZ]; return sizeof(b); }));
+  EXPECT(12, ({ enum { A, B, C }; char b[C][B + 5]; return sizeof(b) + sizeof(b[0]) - 6; }));
+  EXPECT(40, ({ int x = 5; return x * 8; }));
+  EXPECT(-40, ({ int x = -5; return 8 * x; }));
+  EXPECT(-1, ({ int x = -7; return x / 4; }));
+  EXPECT(-2, ({ int x = -8; return x / 4; }));
+  EXPECT(1, ({ int x = 7; return x / 4; }));
+  EXPECT(1, ({ unsigned x = -1; return x / 2 == 2147483647; }));
+  EXPECT(6, ({ int a[2]; a[1] = 3; int i = 1; return a[i] + a[i]; }));
+  EXPECT(6, ({ int a[2]; a[0] = 1; return a[0] + (a[0] = 5); }));
+  EXPECT(15, ({ int a[1]; int *p = a; a[0] = 1; return a[0] * 10 + (*p = 5) * 0 + a[0]; }));
+  EXPECT(1, ({ struct { char a; int b; } s; char *base = &s; char *q = &s.b; return q == base + 4; }));
+  EXPECT(1, ({ struct { char a; int b; char c; } s; struct { char a; int b; char c; } *p = &s; char *base = &s; char *q = &p->c; return q == base + 8; }));
+  EXPECT(42, ({ struct { char a; int b; } s; int *p = &s.b; *p = 42; return s.b; }));
+  EXPECT(1, ({ struct { int x; struct { char y; int z; } in; } t; char *base = &t; char *q = &t.in.z; return q == base + 8; }));
+  EXPECT(3, ({ int x = 0; int *p = &x; int **pp = &p; **pp = 3; return x; }));
+  EXPECT(-1, ({ int x = -1; int *p = &x; int **pp = &p; return **pp; }));
+  EXPECT(4, ({ int x; int *p = &x; int **pp = &p; return sizeof(**pp); }));
+  EXPECT(8, ({ int x; int *p = &x; int **pp = &p; return sizeof(*pp); }));
+  EXPECT(1, ({ char c[2]; char *q = c; char **qq = &q; char ***qqq = &qq; return sizeof(***qqq); }));
+  EXPECT(975, ({ char c[3]; c[2] = 9; char *q = c; char **qq = &q; char ***qqq = &qq; ***qqq = 5; *(**qqq + 1) = 7; return c[0] + c[1] * 10 + c[2] * 100; }));
+  EXPECT(5, ({ int x = 5; int * restrict p = &x; return *p; }));
+  EXPECT(6, ({ volatile int x = 3; return x + x; }));
+  EXPECT(7, ({ int volatile x = 3; x = 7; return x; }));
+  EXPECT(4, ({ int x = 2; volatile int * volatile p = &x; *p = *p * 2; return x; }));
+  EXPECT(9, ({ struct { int a; } s; volatile struct { int a; } *p = &s; p->a = 9; return s.a; }));
+  EXPECT(4, ({ __volatile__ int a[2]; return sizeof(a[1]); }));
+  EXPECT(3, ({ int x = 1; int y = 2; return deref_sum(&x, &y); }));
+  EXPECT(4, ({ __extension__ int x = 4; return x; }));
+  EXPECT(6, __extension__ ({ int x = 6; return x; }));
+  EXPECT(4, succ3());
+  EXPECT(3, spliced());
+  EXPECT(8, line_after_splice() - line_before_splice());
+  EXPECT(6, ({ var_inline = 5; return add_g(1); }));
+  EXPECT(6, ({ var_inline = 5; int var_inline = 100; return add_g(1); }));
+  EXPECT(16, ({ var_inline = 5; return twice(3); }));
+  EXPECT(3, ({ int a = 2; int b = 5; return swap_sub(b, a); }));
+  EXPECT(1, sizeof(ret_char()));
+  EXPECT(5, ret_char());
   EXPECT(6, mul(2, 3));
   EXPECT(21, add(1,2,3,4,5,6));
 
@@ -95,11 +338,100 @@ int main() {
   EXPECT(1, 5 >= 5);
   EXPECT(1, 6 >= 5);
 
+  EXPECT(3, ({ int a = 3; int b = 5; return (a < b) + (a > b) + (a <= b) + (a >= b) + (a == b) + (a != b); }));
+  EXPECT(2, ({ int a = 3; int b = 5; return (a < b) + 1; }));
+  EXPECT(1, ({ int x = 7; int c = x > 2; return c; }));
+  EXPECT(4, ({ char a = 1; char b = 2; return sizeof(a < b); }));
+  EXPECT(4, ({ int *p = 0; return sizeof(!p); }));
+  EXPECT(3, ({ int i = 3; sizeof(i++); return i; }));
+  EXPECT(3, ({ int i = 3; sizeof i--; return i; }));
+  EXPECT(4, ({ int i = 3; return sizeof(i = 7) + i - 3; }));
+  EXPECT(1, ({ char c = 1; int *p = 0; sizeof(*p = c++); return c; }));
+  EXPECT(2, ({ int *p = 0; return !p + 1; }));
+  EXPECT(1, ({ int x = 42; return !!x; }));
+  EXPECT(1, ({ int x = -7; return !!x; }));
+  EXPECT(0, ({ int x = 0; return !!x; }));
+  EXPECT(3, ({ int a = 5; int b = 0; int c = -1; return !!a + !!b + !!c + !!256; }));
+  EXPECT(1, ({ int x; int *p = &x; return !!p; }));
+  EXPECT(0, ({ int *p = 0; return !!p; }));
+  EXPECT(1, ({ unsigned u = 4294967295; return !!u; }));
+  EXPECT(1, ({ int *p = 0; return p == 0; }));
+  EXPECT(0, ({ int x; int *p = &x; return p == 0; }));
+  EXPECT(1, is_null(0));
+  EXPECT(0, ({ int x; return is_null(&x); }));
+  EXPECT(1, ({ int x; return not_null(&x); }));
+  EXPECT(1, ({ int x; int *p = &x; return 0 != p; }));
+  EXPECT(3, ({ int x; int *p = &x; p = 0; if (!p) return 3; return 4; }));
+  EXPECT(4, ({ int x; int *p = 0; p = &x; if (!p) return 3; return 4; }));
+  EXPECT(2, ({ int a = 3; return (a && 5) + (0 || a); }));
+
   EXPECT(8, 1 << 3);
   EXPECT(4, 16 >> 2);
+  EXPECT(1, (1 << 4) == 16);
+  EXPECT(-16, -64 >> 2);
+  EXPECT(-2147483648, 1 << 31);
+  EXPECT(1, ({ int x; return sizeof(x) << 32 >> 32 == 4; }));
+  EXPECT(-4, ({ int x = -8; return x >> 1; }));
+  EXPECT(1, ({ int x = -8; return (x >> 1) == -4; }));
+  EXPECT(-4, ({ int x = -8; unsigned n = 1; return x >> n; }));
+  EXPECT(-4, ({ int x = -8; x >>= 1; return x; }));
+  EXPECT(-1, ({ int x = 2147483647; return (x + 1) >> 31; }));
+  EXPECT(2147483644, ({ unsigned x = -8; return x >> 1; }));
+  EXPECT(2147483644, ({ unsigned x = -8; x >>= 1; return x; }));
+  EXPECT(4, ({ unsigned x = 2147483648; return (x * 2 + 8) >> 1; }));
+  EXPECT(100, ({ unsigned char c = 200; return c >> 1; }));
+
+  EXPECT(8, ({ unsigned const long int x; return sizeof(x); }));
+  EXPECT(8, ({ long long unsigned x; return sizeof(x); }));
+  EXPECT(8, ({ long unsigned long x; return sizeof(x); }));
+  EXPECT(8, ({ int long signed x; return sizeof(x); }));
+  EXPECT(4, ({ signed x; return sizeof(x); }));
+  EXPECT(4, ({ int const unsigned x = 3; return sizeof(x); }));
+  EXPECT(1, ({ char unsigned x = 255; return x == 255; }));
+  EXPECT(-1, ({ signed char x = 255; return x; }));
+  EXPECT(1, ({ long x = 1; return (x << 40 >> 40) == 1; }));
+  EXPECT(1, ({ long unsigned x = -1; return x >> 63; }));
+
+  EXPECT(32, ({ char c; return sizeof(c+c) + sizeof(c-c) + sizeof(c*c) + sizeof(c/c) + sizeof(c%c) + sizeof(c&c) + sizeof(c|c) + sizeof(c^c); }));
+  EXPECT(64, ({ long l; int i; return sizeof(i+l) + sizeof(l-i) + sizeof(i*l) + sizeof(l/i) + sizeof(i%l) + sizeof(l&i) + sizeof(i|l) + sizeof(l^i); }));
+  EXPECT(8, ({ int i = -1; unsigned u = 2; return (i+u > 0) + (i-u > 0) + (i*u > 0) + (i/u > 0) + (i%u > 0) + ((i&u) > 0) + ((i|u) > 0) + ((i^u) > 0); }));
+  EXPECT(16, ({ int *p; int i; return sizeof(p + i) + sizeof(i + p); }));
+  EXPECT(1, ({ unsigned u = -1; long l = 0; return l + (u + 1) == 0; }));
+  EXPECT(1, ({ unsigned u = -1; long l = 0; return (u + 1) == l; }));
+  EXPECT(1, ({ unsigned u = -1; long l = u + 1; return l == 0; }));
+  EXPECT(1, ({ unsigned u = -1; long l; l = u + 1; return l == 0; }));
+  EXPECT(1, ({ unsigned u = -1; long l = 5; l += u + 1; return l == 5; }));
+  EXPECT(7, ({ int a[4]; unsigned u = -1; a[1] = 7; return *(a + (u + 2)); }));
+  EXPECT(1, ({ long l = -1; unsigned u = 1; return l < u; }));
+  EXPECT(1, ({ long l = -1; unsigned u = 1; return l <= u; }));
+  EXPECT(-2, ({ long l = -5; unsigned u = 2; return l / u; }));
+  EXPECT(-1, ({ long l = -5; unsigned u = 2; return l % u; }));
+  EXPECT(1, ({ int i = -6; unsigned u = 2; i /= u; return i == 2147483645; }));
+  EXPECT(-3, ({ long l = -6; unsigned u = 2; l /= u; return l; }));
+  EXPECT(-1, ({ int i = 2147483647; long l = 0; return (l + (i + i)) >> 32; }));
+  EXPECT(3, ({ int a[4] = {0, 1, 2, 3}; int x = -8; return *(a + 5 + (x >> 2)); }));
 
   EXPECT(4, 19 % 5);
   EXPECT(0, 9 % 3);
+  EXPECT(3, ({ int x=3; return 9/x; }));
+  EXPECT(1, ({ int x=2; return 7%x; }));
+  EXPECT(0, ({ int x=0; return 0 && 1/x; }));
+
+  EXPECT(2, 7 / 3);
+  EXPECT(-2, -7 / 3);
+  EXPECT(-2, 7 / -3);
+  EXPECT(2, -7 / -3);
+  EXPECT(1, 7 % 3);
+  EXPECT(-1, -7 % 3);
+  EXPECT(1, 7 % -3);
+  EXPECT(-1, -7 % -3);
+  EXPECT(-2, ({ int x=-7; int y=3; return x/y; }));
+  EXPECT(-1, ({ int x=-7; int y=3; return x%y; }));
+  EXPECT(-7, ({ int x=-7; int y=3; return (x/y)*y + x%y; }));
+  EXPECT(-3, ({ int x=-7; x /= 2; return x; }));
+  EXPECT(-1, ({ int x=-7; x %= 2; return x; }));
+  EXPECT(1431655763, ({ unsigned x=-7; return x/3; }));
+  EXPECT(4, ({ unsigned x=-7; return x%5; }));
 
   EXPECT(0-3, -3);
 
@@ -113,6 +445,10 @@ int main() {
 
   EXPECT(5, 0 ? 3 : 5);
   EXPECT(3, 1 ? 3 : 5);
+  EXPECT(3, 3 ?: 5);
+  EXPECT(5, 0 ?: 5);
+  EXPECT(21, ({ int i = 1; int x = i++ ?: 7; return i * 10 + x; }));
+  EXPECT(17, ({ int i = 0; int x = i++ ?: 7; return i * 10 + x; }));
 
   EXPECT(3, (1, 2, 3));
 
@@ -126,15 +462,66 @@ int main() {
 
   EXPECT(3, ({ int x; int y; x=y=3; return x; }));
   EXPECT(3, ({ int x; int y; x=y=3; return y; }));
+  EXPECT(10, ({ int a; int b; a = b = 5; return a + b; }));
+  EXPECT(4, ({ int x; int y; x = (y = 3) + 1; return x; }));
+  EXPECT(7, ({ int x; int y; x = (y = 3) + 1; return x + y; }));
+  EXPECT(7, ({ int a = 1; int b = 7; int x = 0; x = (a++, b); return x; }));
+  EXPECT(2, ({ int a = 1; int b = 7; int x = 0; x = (a++, b); return a; }));
+  EXPECT(3, ({ int a = 1; a = 5, a = 3; return a; }));
+  EXPECT(4, ({ char *p = 0; int a = 1; return sizeof((p, a)); }));
+  EXPECT(8, ({ char *p = 0; int a = 1; return sizeof((a, p)); }));
+  EXPECT(8, ({ int a[4]; return sizeof((0, a)); }));
+
+  EXPECT(5, ({ int x = 5; int y = 0; int *p = &y; *p = x; return y; }));
+  EXPECT(6, ({ int x = 6; int a[3]; int i = 2; a[i] = x; return a[2]; }));
+  EXPECT(7, ({ int x = 7; struct point s; s.y = x; return s.y; }));
+  EXPECT(8, ({ int x = 8; struct point s; struct point *p = &s; p->x = x; return s.x; }));
+  EXPECT(9, ({ int x = 9; struct nested n; int i = 1; n.p[i].y = x; return n.p[1].y; }));
+  EXPECT(3, ({ int x = 3; enum_buf[1] = x; return enum_buf[1]; }));
+
+  EXPECT(16, ({ struct s16 { int a; int b; char c; int d; } x; return sizeof(x); }));
+  EXPECT(4, ({ struct s16 { int a; int b; char c; int d; } x; struct s16 y; x.a = 1; x.d = 4; __builtin_memcpy(&y, &x, sizeof(x)); return y.d; }));
+  EXPECT(3, ({ struct s16 { int a; int b; char c; int d; } x; struct s16 y; x.c = 3; __builtin_memcpy(&y, &x, sizeof(x)); return y.c; }));
+  EXPECT(0, ({ int a[8]; a[0] = a[3] = a[7] = 1; __builtin_memset(a, 0, sizeof(a)); return a[0] + a[3] + a[7]; }));
+  EXPECT(1, ({ int a[8]; a[7] = 1; __builtin_memset(a, 0, 28); return a[7]; }));
+  EXPECT(5, ({ char a[100]; __builtin_memset(a, 5, 100); return a[99]; }));
+  EXPECT(7, ({ char a[100]; char b[100]; b[98] = 7; __builtin_memcpy(a, b, 100); return a[98]; }));
+  EXPECT(9, ({ char a[7]; __builtin_memset(a, 9, 7); return a[6]; }));
+  EXPECT(2, ({ char a[4]; int n = 3; a[3] = 0; __builtin_memset(a, 1, n); return a[2] + a[3] + 1; }));
+  EXPECT(1, ({ char a[4]; char *p = __builtin_memset(a, 0, 4); return p == a; }));
+  EXPECT(6, ({ int x; int y; int z; x = y = z = 2; return x + y + z; }));
+  EXPECT(5, ({ int x; int *p = &x; return *p = 5; }));
+  EXPECT(2, ({ int ary[2]; ary[0] = ary[1] = 1; return ary[0] + ary[1]; }));
+  EXPECT(1, ({ _Bool b; return b = 5; }));
+  EXPECT(3, ({ int i = 0; int x; while ((x = i++) < 3); return x; }));
 
   EXPECT(60, ({ int sum=0; int i; for (i=10; i<15; i=i+1) sum = sum + i; return sum;}));
   EXPECT(89, ({ int i=1; int j=1; for (int k=0; k<10; k=k+1) { int m=i+j; i=j; j=m; } return i;}));
   EXPECT(1, ({ int i=1; for (int i = 5; i < 10; i++); return i; }));
+  EXPECT(10, ({ int s=0; for (int i=0; i<5; i++) s = s + i; int i=0; return s + i; }));
+  EXPECT(7, ({ for (int i=0; i<5; i++); int i=7; int *p=&i; for (int i=0; i<3; i++) *p = *p + 0; return i; }));
   EXPECT(5, ({ int i=0; for (0; i < 10; i++) if (i==5) break; return i; }));
   EXPECT(10, ({ int i=0; for (;;) { i++; if (i==10) break; } return i; }));
+  EXPECT(5, ({ int i=0; for (;; i++) { ; if (i==5) break; } return i; }));
+  EXPECT(3, ({ int i=0; for (;;) { ;; i++; ; if (i==3) break; } return i; }));
+  EXPECT(4, ({ int i=0; for (i = 4;;) break; return i; }));
+  EXPECT(7, ({ int i=0; for (; i < 7; i++); return i; }));
+  EXPECT(2, ({ int i=2; ; if (i) ; else i = 9; ; return i; }));
+  EXPECT(6, ({ int i=0; while (i < 6) i++; ; return i; }));
 
   EXPECT(45, ({ int i=0; int j=0; while (i<10) { j=j+i; i=i+1; } return j;}));
 
+  EXPECT(1, ({ int x; int *p = &x; if (p) return 1; return 0; }));
+  EXPECT(2, ({ int x; int *p = &x; return p ? 2 : 3; }));
+  EXPECT(3, ({ char *p = "abc"; int n = 0; while (*p) { n++; p++; } return n; }));
+  EXPECT(4, ({ char c = 4; int n = 0; while (c) { n++; c--; } return n; }));
+  EXPECT(0, ({ unsigned x = 65536; if (x * x) return 1; return 0; }));
+  EXPECT(0, ({ unsigned x = 65536; return x * x ? 1 : 0; }));
+  EXPECT(0, ({ unsigned x = 65536; return x * x && 1; }));
+  EXPECT(1, ({ int x; int *p = &x; return 0 || p; }));
+  EXPECT(5, ({ char c = 5; int n = 0; do { n++; } while (--c); return n; }));
+  EXPECT(2, ({ int n = 0; for (char c = 2; c; c--) n++; return n; }));
+
   EXPECT(3, ({ int ary[2]; *ary=1; *(ary+1)=2; return *ary + *(ary+1);}));
   EXPECT(5, ({ int x; int *p = &x; x = 5; return *p;}));
 
@@ -150,6 +537,46 @@ int main() {
 
   EXPECT(1, ({ char x; return sizeof x; }));
   EXPECT(4, ({ int x; return sizeof(x); }));
+  EXPECT(1, ({ int a[3]; return &a == a; }));
+  EXPECT(1, ({ int a[3]; return &a + 1 == a + 3; }));
+  EXPECT(8, ({ int a[3]; return sizeof(&a); }));
+  EXPECT(12, ({ int a[3]; return sizeof(*&a); }));
+  EXPECT(5, ({ int a[3]; a[1] = 5; return (*&a)[1]; }));
+  EXPECT(7, ({ int a[2][3]; int (*p)[3] = &a[1]; a[1][2] = 7; return (*p)[2]; }));
+  EXPECT(3, ({ int a[10]; return &a[5] - &a[2]; }));
+  EXPECT(-3, ({ int a[10]; int *p = &a[5]; int *q = &a[2]; return q - p; }));
+  EXPECT(8, ({ int a[10]; return sizeof(&a[5] - &a[2]); }));
+  EXPECT(3, ({ int *a[4]; return &a[3] - a; }));
+  EXPECT(2, ({ int a[4]; return &a[2] - a; }));
+  EXPECT(3, ({ char c[4]; void *p = c; void *q = p + 3; return q - p; }));
+  EXPECT(1, ({ char c[4]; void *p = c; p++; p += 2; return p == c + 3; }));
+  EXPECT(1, ({ int a[4]; return &a[2] == a + 2; }));
+  EXPECT(1, ({ int x; int *p = &x; return &*p == p; }));
+  EXPECT(5, ({ int a[4]; a[2] = 5; return *&a[2]; }));
+  EXPECT(2, ({ struct point a[4]; struct point *p = a + 2; return p - a; }));
+  EXPECT(1, ({ int a[2][3]; return &a[1] - &a[0]; }));
+  EXPECT(24, ({ int m[2][3]; return sizeof(m); }));
+  EXPECT(12, ({ int m[2][3]; return sizeof(m[0]); }));
+  EXPECT(4, ({ int m[2][3]; return sizeof(m[0][0]); }));
+  EXPECT(12, ({ int m[2][3]; return sizeof(*m); }));
+  EXPECT(8, ({ int m[2][3]; return sizeof(m + 0); }));
+  EXPECT(120, ({ char m[2][3][4][5]; return sizeof(m); }));
+  EXPECT(20, ({ char m[2][3][4][5]; return sizeof(m[1][2]); }));
+  EXPECT(2, ({ int m[2][3]; return sizeof(m) / sizeof(m[0]); }));
+  EXPECT(3, ({ int m[2][3]; return sizeof(m[0]) / sizeof(m[0][0]); }));
+  EXPECT(24, sizeof(g_grid));
+  EXPECT(12, sizeof(g_grid[1]));
+  EXPECT(5, ({ char *s = "abcdef"; return (s + 5) - s; }));
+  EXPECT(42, ({ int a[10]; a[1] = 42; int *p = &a[5]; return p[-4]; }));
+  EXPECT(42, ({ int a[10]; a[1] = 42; int *p = &a[5]; int i = -4; return *(p + i); }));
+  EXPECT(80, ({ int *a[10]; return sizeof(a); }));
+  EXPECT(8, ({ int *a[10]; return sizeof(a[0]); }));
+  EXPECT(8, ({ int (*a)[10]; return sizeof(a); }));
+  EXPECT(40, ({ int (*a)[10]; return sizeof(*a); }));
+  EXPECT(24, ({ int (*a[3])[10]; return sizeof(a); }));
+  EXPECT(40, ({ int (*a[3])[10]; return sizeof(*a[0]); }));
+  EXPECT(3, ({ int x = 3; int *p = &x; int *(*a)[2]; int *b[2]; a = &b; b[1] = p; return *(*a)[1]; }));
+  EXPECT(9, ({ int a[3]; a[2] = 9; int (*p)[3] = (int (*)[3]){&a}; return (*p)[2]; }));
   EXPECT(8, ({ int *x; return sizeof x; }));
   EXPECT(16, ({ int x[4]; return sizeof x; }));
 
@@ -159,8 +586,42 @@ int main() {
   EXPECT(4, ({ int x[4]; return _Alignof x; }));
   EXPECT(8, ({ int *x[4]; return _Alignof x; }));
   EXPECT(4, sizeof("abc"));
+  EXPECT(4, sizeof(u8"abc"));
+  EXPECT(98, u8"abc"[1]);
+  EXPECT(3, sizeof("a\n"));
 
   EXPECT(5, ({ char x = 5; return x; }));
+  EXPECT(3, ({ unsigned x = 3; return x; }));
+
+  EXPECT(1, ({ _Bool b = 5; return b; }));
+  EXPECT(1, ({ _Bool b = 5; return b == 1; }));
+  EXPECT(0, ({ _Bool b = 0; return b; }));
+  EXPECT(1, ({ _Bool b; b = 256; return b; }));
+  EXPECT(1, ({ _Bool b; int x; b = &x; return b; }));
+  EXPECT(11, ({ bool b = -3; return b + 10; }));
+  EXPECT(1, ({ _Bool b; return sizeof(b); }));
+  EXPECT(1, ({ bool b = 0; b += 7; return b; }));
+  EXPECT(1, ({ bool b = 1; b++; return b; }));
+  EXPECT(0, ({ bool b = 1; return !b; }));
+  EXPECT(10, ({ register int i = 0; for (register int j = 0; j < 5; j++) i += 2; return i; }));
+  EXPECT(3, ({ auto int x = 3; return x; }));
+  EXPECT(4, ({ register char *p = "abcd"; return sizeof(p) / 2; }));
+  EXPECT(4, ({ unsigned int x; return sizeof(x); }));
+  EXPECT(1, ({ unsigned char x; return sizeof(x); }));
+  EXPECT(8, ({ int x; return sizeof(sizeof(x)); }));
+  EXPECT(1, ({ int x; return sizeof(x) < -1; }));
+  EXPECT(1, ({ char c; return sizeof(c) - 2 > 0; }));
+  EXPECT(0, ({ int i = -1; return i < sizeof(i); }));
+  EXPECT(1, ({ int i = 2147483647; return sizeof(i) + i > 2147483647; }));
+  EXPECT(1, sizeof(huge) > 2147483647);
+  EXPECT(1, sizeof(huge) >> 32);
+  EXPECT(3, ({ char a[3] = {1, 2, 3}; int *p; char *q = a + sizeof(p) - 6; return *q; }));
+  EXPECT(1, ({ char c = -1; int i = -1; return c == i; }));
+  EXPECT(-56, ({ char c = 200; int x = c; return x; }));
+  EXPECT(200, ({ unsigned char c = 200; int x = c; return x; }));
+  EXPECT(255, ({ unsigned char c = -1; return c; }));
+  EXPECT(1, ({ char c = -128; return c < 0; }));
+  EXPECT(3, ({ int a[5]; a[2] = 3; int *p = a + 3; int i = -1; return p[i]; }));
   EXPECT(42, ({ int x = 0; char *p = &x; p[0] = 42; return x; }));
 
   EXPECT('a', ({ char *p = "abc"; return p[0]; }));
@@ -169,14 +630,180 @@ int main() {
   EXPECT(0, ({ char *p = "abc"; return p[3]; }));
   EXPECT(7, sizeof("abc" "def"));
   EXPECT(9, sizeof("ab\0c" "\0def"));
+  EXPECT(7, sizeof("ab" "cd" "ef"));
+  EXPECT(101, ({ char *s = "ab" /* comment */ "cd" "ef"; return s[4]; }));
 
   EXPECT(1, ({ int x = 1; { int x = 2; } return x; }));
+  EXPECT(21, ({ int x = 1; int r = 0; { int x = 2; r = x * 10; } return r + x; }));
+  EXPECT(32, ({ int x = 1; { x = 3; int x = 2; x = x * 10; } return x + 29; }));
+  EXPECT(421, ({ int x = 1; int r = 0; { int x = 2; { int x = 4; r = x * 100; } r = r + x * 10; } return r + x; }));
+  EXPECT(6, ({ int x = 1; for (int x = 5; x < 6; x++) ; return x + 5; }));
+  EXPECT(2, ({ char x = 1; { int x = 300; } x = x + 1; return x; }));
+
+  EXPECT(16, ({ struct { int a; } __attribute__((aligned(16))) x; return sizeof(x); }));
+  EXPECT(16, ({ struct { int a; } __attribute__((aligned(16))) x; return _Alignof(x); }));
+  EXPECT(32, ({ struct { char c; int a; } __attribute__((aligned(16))) x[2]; return sizeof(x); }));
+  EXPECT(16, ({ struct { char c; int a __attribute__((aligned(8))); } x; return sizeof(x); }));
+  EXPECT(4, ({ int x __attribute__((aligned(16))); return sizeof(x); }));
+  EXPECT(1, ({ char c; int x __attribute__((aligned(16))); return is_aligned(&x, 16); }));
+  EXPECT(4, sse_callee());
+  EXPECT(4, sse_frame0());
+  EXPECT(4, sse_frame1());
+  EXPECT(4, sse_frame2());
+  EXPECT(4, sse_frame3(0));
+  EXPECT(1, ({ char c; struct { int a; } __attribute__((__aligned__)) s; return is_aligned(&s, 16); }));
+  EXPECT(3, ({ int x __attribute__((unused, section(".data"))) = 3; return x; }));
+  EXPECT(1, is_aligned(&var5, 32));
+  EXPECT(1, ({ char c; int i; return is_aligned(&i, 4); }));
+  EXPECT(1, ({ char c; int *p; return is_aligned(&p, 8); }));
+  EXPECT(1, ({ char c; int i; char d; int *p; return is_aligned(&p, 8); }));
+  EXPECT(1, ({ char c; struct { char a; int *b; } s; return is_aligned(&s, 8); }));
+  EXPECT(1, ({ char c[3]; int a[3]; char d; int *p[2]; return is_aligned(&a, 4) && is_aligned(&p, 8); }));
 
   EXPECT(0, var1);
+  EXPECT(0, var3[2]);
+  EXPECT(0, var4);
   EXPECT(5, ({ var1 = 5; return var1; }));
   EXPECT(20, sizeof(var2));
   EXPECT(15, ({ var2[0] = 5; var2[4] = 10; return var2[0] + var2[4]; }));
   EXPECT(5, global_arr[0]);
+  EXPECT(7, g_init);
+  EXPECT(-2, g_neg);
+  EXPECT(7, *g_ptr);
+  EXPECT(8, ({ **g_pptr = 8; return g_init; }));
+  EXPECT(5, *g_elem);
+  EXPECT(1, !g_null);
+  EXPECT(116, g_str[1]);
+  EXPECT(16, sizeof(g_table));
+  EXPECT(10, g_table[0] + g_table[1] + g_table[2] + g_table[3]);
+  EXPECT(116, g_name[4]);
+  EXPECT(0, g_name[7]);
+  EXPECT(5, g_grid[1][1]);
+  EXPECT(16, g_table_size);
+  EXPECT(3, *g_third);
+  EXPECT(2, *g_second);
+  EXPECT(1, g_nested_y == &g_nested.p[1].y);
+  EXPECT(100, g_strs[1][1]);
+  EXPECT(0, g_strs[2]);
+  EXPECT(0, g_grid[1][2]);
+  EXPECT(24, sizeof(g_grid2));
+  EXPECT(44, typeof_param(0));
+  EXPECT(3, ({ int x = 3; typeof(x) y = x; return y; }));
+  EXPECT(8, ({ typeof(int *) p; return sizeof(p); }));
+  EXPECT(4, ({ char c; typeof(c + 1) y; return sizeof(y); }));
+  EXPECT(1, ({ int x; { char x; typeof(x) y; return sizeof(y); } }));
+  EXPECT(4, ({ int x; { char x; } typeof(x) y; return sizeof(y); }));
+  EXPECT(0, ({ int i = 0; typeof(i++) j; return i; }));
+  EXPECT(12, ({ int a[3]; typeof(a) b; return sizeof(b); }));
+  EXPECT(4, ({ typeof(typeof_param(1)) r; return sizeof(r); }));
+  EXPECT(1, ({ for (char i = 0; i < 1; i++) { typeof(i) j; return sizeof(j); } return 0; }));
+  EXPECT(2112, typeof_swap());
+  EXPECT(8, ({ int a[3]; typeof(a + 1) p; return sizeof(p); }));
+  EXPECT(8, ({ int *p; int *q; typeof(p - q) d; return sizeof(d); }));
+  EXPECT(1, ({ struct { int x; char c; } s; typeof(s.c) c; return sizeof(c); }));
+  EXPECT(8, ({ long *p; typeof(*p) v; return sizeof(v); }));
+  EXPECT(8, ({ int *p; typeof(1 ? 0 : p) q; return sizeof(q); }));
+  EXPECT(1, ({ int i; char c; typeof(i, c) d; return sizeof(d); }));
+  EXPECT(25, ({ int i = 0; int sum = 0; for (; i < 10; i++) { if (i % 2 == 0) continue; sum = sum + i; } return sum; }));
+  EXPECT(10, ({ int i = 0; int n = 0; for (; i < 10; i++) { n++; continue; n = 100; } return i; }));
+  EXPECT(25, ({ int i = 0; int sum = 0; while (i < 10) { i++; if (i % 2 == 1) continue; sum = sum + i - 1; } return sum; }));
+  EXPECT(5, ({ int i = 0; int n = 0; do { i++; if (i > 2) continue; n = n + 2; } while (i < 5); return i + n - 4; }));
+  EXPECT(12, ({ int n = 0; for (int i = 0; i < 3; i++) for (int j = 0; j < 4; j++) { if (j > 5) break; n++; continue; } return n; }));
+  EXPECT(6, ({ int n = 0; for (int i = 0; i < 3; i++) { int j = 0; while (j < 4) { j++; if (j % 2) continue; n++; } } return n; }));
+  EXPECT(30, ({ int n = 0; for (int i = 0; i < 10; i++) for (int j = 0; j < 10; j++) { if (j == 3) break; n++; } return n; }));
+  EXPECT(3, ({ int i = 0; while (1) { for (;;) break; i++; if (i == 3) break; } return i; }));
+  EXPECT(48, ({ int i = 0; int n = 0; do { int j = 0; while (j < 5) { j++; if (j == 2) continue; if (j == 4) break; n++; } i++; if (i == 4) break; } while (1); return i * 10 + n; }));
+  EXPECT(8, param_size(0));
+  EXPECT(8, param_unsized(0));
+  EXPECT(816, param_rows(0));
+  EXPECT(6, ({ int m[2][3]; m[1][2] = 6; m[0][2] = 3; return param_grid(m, 1, 2); }));
+  EXPECT(3, ({ int m[2][3]; m[1][2] = 6; m[0][2] = 3; return param_grid(m, 0, 2); }));
+  EXPECT(5, param_grid(g_grid, 1, 1));
+  EXPECT(7, ({ int a[2]; param_write(a); return a[1]; }));
+  EXPECT(1, ({ void *p = param_size; void *q = &param_size; return p == q; }));
+  EXPECT(1, ({ void *p = *param_size; return p == &param_size; }));
+  EXPECT(1, same_ptr(param_size, &param_size));
+  EXPECT(0, same_ptr(param_size, param_unsized));
+  EXPECT(8, sizeof(&param_size));
+  EXPECT(25, ({ int (*fp)(int, int, int, int, int, int, int, int) = sum8; return fp(1, 2, 3, 4, 5, 6, 2, 2); }));
+  EXPECT(36, ({ fp_sum8 fp = &sum8; return (*fp)(1, 2, 3, 4, 5, 6, 7, 8); }));
+  EXPECT(1, ({ int (*fp)(void *, void *) = same_ptr; return fp(param_size, &param_size); }));
+  EXPECT(0, ({ int (*fp)(void *, void *) = same_ptr; fp = 0; return fp == 0 ? 0 : 1; }));
+  EXPECT(16, ({ int (*fps[2])(void *, void *); return sizeof(fps); }));
+  EXPECT(33, fp_apply(plus, 1, 2));
+  EXPECT(66, fp_apply(mul, 2, 3));
+  EXPECT(12, g_fp(3, 4));
+  EXPECT(7, ({ int (*fps[2])(int, int) = {plus, mul}; return fps[0](1, 2) + fps[1](2, 2); }));
+  EXPECT(7, ({ int (*fp)(int, int) = mul; fp = plus; return fp(3, 4); }));
+  EXPECT(1, null_defined);
+  EXPECT(36, sum8(1, 2, 3, 4, 5, 6, 7, 8));
+  EXPECT(12345678, digits8(1, 2, 3, 4, 5, 6, 7, 8));
+  EXPECT(12345678, gcc_digits8(1, 2, 3, 4, 5, 6, 7, 8));
+  EXPECT(87654321, ({ int x = 8; return gcc_digits8(x, x - 1, 6, 5, 4, 3, x - 6, digits8(0, 0, 0, 0, 0, 0, 0, 1)); }));
+  EXPECT(46, ({ int x = 10; return sum8(1, 2, 3, 4, 5, 6, 7, 8) + x; }));
+  EXPECT(98765432, gcc_calls_digits8());
+  EXPECT(5, ({ int x = 5; return last8(0, 0, 0, 0, 0, 0, 0, &x); }));
+  EXPECT(10, count8(5, 0, 0, 0, 0, 0, 0, 0));
+  EXPECT(25, sum8(1, 2, 3, 4, 5, 6, sse_callee(), 0));
+  EXPECT(6, sum8(sse_callee(), 0, 0, 0, 0, 0, 0, 2));
+  EXPECT(2, ({ char buf[8]; return sprintf(buf, "%d%d", 4, 2); }));
+  EXPECT(42, ({ char buf[8]; sprintf(buf, "%d%d", 4, 2); return (buf[0] - '0') * 10 + buf[1] - '0'; }));
+  EXPECT(5, first_arg(5));
+  EXPECT(5, first_arg(5, 6, 7));
+  EXPECT(5, prime_at(2));
+  EXPECT(5, prime_at(2));
+  EXPECT(7, prime_at(3));
+  EXPECT(1, static_count());
+  EXPECT(2, static_count());
+  EXPECT(26, static_pair());
+  EXPECT(36, static_pair());
+  EXPECT(3, ({ static_inline(); static_inline(); return static_inline(); }));
+  EXPECT(16, ({ static char buf[16]; return sizeof(buf); }));
+  EXPECT(1, g_multi1);
+  EXPECT(2, g_multi2);
+  EXPECT(2, *g_multi_ptr);
+  EXPECT(0, g_multi_bss);
+  EXPECT(8, sizeof(g_multi_ary));
+  EXPECT(4, g_multi_ary[1]);
+  EXPECT(12, ({ multi_int x = 4; multi_ptr p = &x; return sizeof(x) + sizeof(p); }));
+  EXPECT(5, ({ int x = 5; int *p = &x; int *q = 1 ? p : 0; return q ? *q : 0; }));
+  EXPECT(5, ({ int x = 5; int *p = &x; int c = 1; return *(c ? p : 0); }));
+  EXPECT(5, ({ int x = 5; int *p = &x; int c = 0; return *(c ? 0 : p); }));
+  EXPECT(8, ({ int *p; int c = 0; return sizeof(c ? 0 : p); }));
+  EXPECT(1, ({ int *p; int c = 1; return (c ? 0 : p) == 0; }));
+  EXPECT(4, ({ char a = 1; char b = 2; int c = 1; return sizeof(c ? a : b); }));
+  EXPECT(8, ({ char a = 1; int c = 1; return sizeof(c ? a : sizeof(a)); }));
+  EXPECT(4, ({ char a = 1; char b = 2; return sizeof(a < b); }));
+  EXPECT(302, ({ char a = 1; char b = 2; int c = 0; return (a < b) + (c ? a : b) + 299; }));
+
+  EXPECT(21, cleanup_block());
+  EXPECT(8, cleanup_return(5));
+  EXPECT(43, cleanup_log);
+  EXPECT(0, cleanup_return(0));
+  EXPECT(3, cleanup_log);
+  EXPECT(12, cleanup_continue());
+  EXPECT(65, cleanup_break());
+  EXPECT(87, cleanup_inner_break());
+  EXPECT(9, cleanup_for_init(0));
+  EXPECT(7, cleanup_for_init(7));
+  EXPECT(4, cleanup_for_init_return());
+  EXPECT(24, cleanup_log);
+  EXPECT(6, cleanup_value());
+  EXPECT(5, ({ int a __attribute__((cleanup(log_cleanup))) = 5; cleanup_log = 0; return a; }));
+  EXPECT(5, cleanup_log);
+  EXPECT(12, ({ int a[1 ? 3 : 5]; return sizeof(a); }));
+  EXPECT(20, ({ int a[0 ? 3 : 5]; return sizeof(a); }));
+  EXPECT(8, ({ int x = 4; int a[1 ? 2 : x]; return sizeof(a); }));
+  EXPECT(8, ({ int a[2 ?: 9]; return sizeof(a); }));
+  EXPECT(7, ({ enum { A = 2, B = A > 1 ? 7 : 0 }; return B; }));
+  EXPECT(2, ({ int x = (1, 2); return x; }));
+  EXPECT(12, ({ int a[1 ? 3 : (4, 5)]; return sizeof(a); }));
+  EXPECT(20, ({ int a[0 ? (3, 4) : 5]; return sizeof(a); }));
+  EXPECT(3, ({ const int x = 3; return x; }));
+  EXPECT(6, ({ const int a[] = {1, 2, 3}; return a[0] + a[1] + a[2]; }));
+  EXPECT(4, ({ int x = 2; int *const p = &x; *p = 4; return x; }));
+  EXPECT(5, ({ int x = 5; const int *p = &x; p = &x; return *p; }));
+  EXPECT(9, ({ int x = 9; int *p = &x; int **pp = &p; return **pp; }));
 
   EXPECT(8, ({ return 3 + ({ return 5; }); }));
 
@@ -203,11 +830,45 @@ int main() {
 	return x.a[0].b + x.a[0].c[1];
       }));
 
+  EXPECT(7, ({ struct { int a; struct { char b; struct { int c; int d; } in; } mid; } x; x.mid.in.d = 7; return x.mid.in.d; }));
+  EXPECT(1, ({ struct { int a; struct { char b; struct { int c; int d; } in; } mid; } x; char *base = &x; char *q = &x.mid.in.d; return q == base + 12; }));
+  EXPECT(8, ({ struct { int a; struct { char b; struct { int c; int d; } in; } mid; } x; return sizeof(x.mid.in); }));
+  EXPECT(9, ({ struct point pt; struct inner { struct point *p; } in; struct { int n; struct inner *in; } out; out.in = &in; in.p = &pt; struct { int n; struct inner *in; } *o = &out; o->in->p->y = 9; return pt.y; }));
+  EXPECT(5, ({ struct nested n; struct { struct nested *n; } w = {&n}; struct { struct nested *n; } *pw = &w; n.p[2].x = 5; return pw->n->p[2].x; }));
+
   EXPECT(3, ({ typedef int foo; foo x = 3; return x; }));
   EXPECT(4, ({ myint foo = 3; return sizeof(foo); }));
 
   EXPECT(1, ({ typedef struct foo_ foo; return 1; }));
 
+  EXPECT(0, ({ struct empty {} x; return sizeof(x); }));
+  EXPECT(0, ({ struct empty {}; struct empty x; return sizeof(x); }));
+  EXPECT(0, ({ int x[0]; return sizeof(x); }));
+  EXPECT(4, ({ struct { int n; int a[0]; } x; return sizeof(x); }));
+  EXPECT(7, ({ int buf[4]; struct { int n; int a[0]; } *p = buf; p->a[1] = 7; return buf[2]; }));
+
+  EXPECT(4, ({ struct fam { int len; char data[]; } x; return sizeof(x); }));
+  EXPECT(4, ({ struct { char c; int data[]; } x; return sizeof(x); }));
+  EXPECT(8, ({ struct { int len; char data[0]; int end; } x; return sizeof(x); }));
+  EXPECT(9, ({ char mem[16]; struct fam { int len; char data[]; } *p = mem; p->len = 3; p->data[5] = 9; return mem[9]; }));
+  EXPECT(3, ({ char mem[16]; struct fam { int len; char data[]; } *p = mem; p->len = 3; p->data[0] = 1; return p->len; }));
+
+  EXPECT(4, ({ struct { unsigned a : 3; unsigned b : 5; } x; return sizeof(x); }));
+  EXPECT(8, ({ struct { unsigned a : 30; unsigned b : 5; } x; return sizeof(x); }));
+  EXPECT(5, ({ struct { unsigned a : 3; unsigned b : 5; } x; x.a = 5; x.b = 17; return x.a; }));
+  EXPECT(17, ({ struct { unsigned a : 3; unsigned b : 5; } x; x.a = 5; x.b = 17; return x.b; }));
+  EXPECT(141, ({ struct { unsigned a : 3; unsigned b : 5; } x; int *p = &x; *p = 0; x.a = 5; x.b = 17; return *p; }));
+  EXPECT(6, ({ struct { unsigned a : 3; unsigned b : 5; } x; int *p = &x; *p = 141; return x.a + 1; }));
+  EXPECT(1, ({ struct { unsigned a : 3; unsigned b : 5; } x; x.a = 9; return x.a; }));
+  EXPECT(7, ({ struct { unsigned a : 3; unsigned b : 5; } x; x.a = 7; return x.a++; }));
+  EXPECT(0, ({ struct { unsigned a : 3; unsigned b : 5; } x; x.a = 7; x.a++; return x.a; }));
+  EXPECT(3, ({ struct { char c; unsigned a : 2; int i; } x; x.c = 1; x.a = 3; x.i = 4; return x.a; }));
+  EXPECT(-1, ({ struct { int a : 3; int b : 5; } x; x.a = -1; return x.a; }));
+  EXPECT(-16, ({ struct { int a : 3; int b : 5; } x; x.a = 3; x.b = 16; return x.b; }));
+  EXPECT(3, ({ struct { int a : 3; int b : 5; } x; x.b = -1; x.a = 3; return x.a; }));
+  EXPECT(-4, ({ struct { char c; int a : 3; int i; } x; x.c = 1; x.a = 4; x.i = -1; return x.a; }));
+  EXPECT(1, ({ struct { long a : 40; long b : 20; } x; x.a = -5; x.b = 7; return x.a == -5 && x.b == 7; }));
+
   EXPECT(15, ({ int i=5; i*=3; return i; }));
   EXPECT(1, ({ int i=5; i/=3; return i; }));
   EXPECT(2, ({ int i=5; i%=3; return i; }));
@@ -218,9 +879,33 @@ int main() {
   EXPECT(1, ({ int i=5; i&=3; return i; }));
   EXPECT(6, ({ int i=5; i^=3; return i; }));
   EXPECT(7, ({ int i=5; i|=3; return i; }));
+  EXPECT(5, ({ struct { int count; char c; } s; s.count = 4; s.count += 1; return s.count; }));
+  EXPECT(12, ({ struct { int count; char c; } s; s.c = 3; s.c <<= 2; return s.c; }));
+  EXPECT(10, ({ int a[3]; int i = 1; a[1] = 5; a[i] *= 2; return a[1]; }));
+  EXPECT(1, ({ int a[3]; int i = 0; a[0] = 0; a[1] = 0; a[i++] += 1; return i; }));
+  EXPECT(10, ({ int a[3]; int i = 0; a[0] = 0; a[1] = 0; a[i++] += 1; return a[0] * 10 + a[1]; }));
+  EXPECT(7, ({ struct point p[2]; int i = 1; p[1].y = 10; p[i--].y -= 3; return p[1].y; }));
+  EXPECT(0, ({ struct point p[2]; int i = 1; p[1].y = 10; p[i--].y -= 3; return i; }));
+  EXPECT(4, ({ int a[2]; int *p = a; a[0] = 2; a[1] = 3; *p++ *= 2; return a[0] * (p - a); }));
 
   EXPECT(11, ({ int x[3] = {10, 11, 12}; return x[1]; }));
+  EXPECT(12, ({ int x[3] = {10, 11, 12,}; return x[2]; }));
+  EXPECT(12, ({ int x[] = {10, 11, 12,}; return sizeof(x); }));
+
+  EXPECT(0, RED);
+  EXPECT(5, GREEN);
+  EXPECT(6, BLUE);
+  EXPECT(4, ({ enum color c = BLUE; return sizeof(c); }));
+  EXPECT(3, ({ enum { X, Y, Z, } e; return X + Y + Z; }));
+  EXPECT(10, ({ int a = 1; int b = 2; int c = 3; return a + (b + (c + clobber_rbx())); }));
 
   printf("OK\n");
   return 0;
  }
+
+// Keeps a value in every register, rbx included.
+int clobber_rbx() { int a = 1; return a + (a + (a + a)); }
+char ret_char() { return 5; }
+int four() { return 4; }
+int scale(int n, int *p) { return n * *p; }
+char first(char *s[], int (*a)[2]) { return s[0][0]; }
@@ -0,0 +1,10 @@
+// warning: array index 5 is past the end of the array
+int main() {
+  int a[3];
+  int *p;
+  a[5];
+  // The address one past the end is valid.
+  for (p = a; p < &a[3]; p++)
+    *p = 0;
+  return a[2] - a[2];
+}
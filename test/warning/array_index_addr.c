@@ -0,0 +1,6 @@
+// warning: array index 4 is past the end of the array
+int main() {
+  int a[3];
+  int *p = &a[4];
+  return p - &a[3];
+}
@@ -0,0 +1,7 @@
+// warning: comparisons like 'X<=Y<=Z' do not have their mathematical meaning
+int main() {
+  int a = 1;
+  int b = 2;
+  int c = 3;
+  return a < b < c;
+}
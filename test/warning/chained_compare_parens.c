@@ -0,0 +1,6 @@
+// warning: comparisons like 'X<=Y<=Z' do not have their mathematical meaning
+int main() {
+  int a = 1;
+  int b = 2;
+  return (a < b) == 0;
+}
@@ -0,0 +1,15 @@
+// warning: control reaches end of non-void function
+_Noreturn void die(int code);
+void fail(void) __attribute__((noreturn));
+__attribute__((noreturn)) void stop();
+void quit();
+_Noreturn void quit();
+
+int after_die(int x) { if (x) return 1; die(2); }
+int after_fail(int x) { if (x) return 1; fail(); }
+int after_stop(int x) { if (x) return 1; stop(); }
+int after_quit(int x) { if (x) return 1; quit(); }
+int loops(int x) { for (;;) { while (x) break; if (x) return 1; } }
+int both(int x) { if (x) return 1; else return 2; }
+int main() { return 0; }
+int falls_off(int x) { if (x) return 1; }
@@ -0,0 +1,5 @@
+// warning: array index -1 is before the beginning of the array
+int main() {
+  int a[3];
+  return a[-1] - a[-1 + 1] + a[0];
+}
@@ -0,0 +1,5 @@
+// warning: right shift count is negative
+int main() {
+  int x = 8;
+  return x >> -1;
+}
@@ -0,0 +1,6 @@
+// warning: comparison between pointer and integer
+int main() {
+  int x = 0;
+  int *p = &x;
+  return p == 1;
+}
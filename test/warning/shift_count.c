@@ -0,0 +1,6 @@
+// warning: left shift count >= width of type
+int main() {
+  int x = 1 << 31;
+  int y = sizeof(x) << 40;
+  return 1 << 40;
+}
@@ -0,0 +1,7 @@
+// warning: comparison between signed and unsigned integers
+int main() {
+  int i = -1;
+  unsigned u = 1;
+  long l = -1;
+  return (i < u) + (l < u);
+}
@@ -0,0 +1,15 @@
+// warning: code will never be executed
+int after_return(int x) {
+  if (x)
+    return 1;
+  else
+    return 2;
+  x = 3;
+  x = 4;
+  return x;
+}
+int after_loop(int x) { for (;;) { if (x) break; } return x; }
+int after_goto(int x) { while (1) { if (x) goto out; } out: return x; }
+int after_do(int x) { do { if (x) return 2; } while (0); return x; }
+int break_after_return(int x) { while (x) { return 1; break; } return 0; }
+int main() { return 0; }
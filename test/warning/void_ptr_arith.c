@@ -0,0 +1,2 @@
+// warning: pointer of type 'void *' used in arithmetic
+int main() { char c[2]; void *p = c; return p + 1 == c + 1; }
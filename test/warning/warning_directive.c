@@ -0,0 +1,8 @@
+// warning: #warning this header is deprecated
+#define OLD_API
+#ifndef OLD_API
+#warning this branch is inactive
+#else
+#warning this header is deprecated
+#endif
+int main() { return 0; }
@@ -0,0 +1,409 @@
+// Tests for the library entry point, r9cc::compile().
+
+use r9cc::{compile, compile_with, Options};
+
+#[test]
+fn compiles_to_assembly() {
+    let asm = compile("int main() { return 42; }").unwrap();
+    assert!(asm.starts_with(".intel_syntax noprefix\n"));
+    assert!(asm.contains("main:\n"));
+    assert!(asm.contains("mov r10, 42"));
+}
+
+#[test]
+fn reports_errors() {
+    let errors = compile("int main() { return 1/0; }").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "division by zero");
+    assert!(!errors[0].internal);
+    assert!(errors[0].context.starts_with("error at <input>:1:22\n"));
+
+    let errors = compile("int main() { return x; }").unwrap_err();
+    assert_eq!(errors[0].message, "undeclared identifier: x");
+    assert!(errors[0].context.starts_with("error at <input>:1:21\n"));
+
+    let errors = compile("int main() { int a[2]; a = 0; return *a(); }").unwrap_err();
+    assert_eq!(errors[0].message, "array is not assignable");
+    assert!(errors[0].context.starts_with("error at <input>:1:26\n"));
+}
+
+#[test]
+fn is_reusable() {
+    let src = "int g; char *s() { return \"hi\"; } int f(int x) { return x ? f(x - 1) : g; }";
+    let first = compile(src).unwrap();
+    assert!(compile("int main() { return }").is_err());
+    assert_eq!(compile(src).unwrap(), first);
+}
+
+#[test]
+fn takes_options() {
+    let src = "int x; int main() { return x; }";
+    let opts = Options {
+        pic: true,
+        ..Default::default()
+    };
+    assert!(compile_with("a.c", src, &opts)
+        .unwrap()
+        .value
+        .contains("[rip + x]"));
+}
+
+#[test]
+fn returns_warnings_and_dumps() {
+    let src = "#warning hi\nint main() { return 1 << 40; }";
+    let out = compile_with("<input>", src, &Options::default()).unwrap();
+    assert_eq!(
+        out.warnings,
+        [
+            "<input>:1:1: warning: #warning hi",
+            "<input>:2:23: warning: left shift count >= width of type",
+        ]
+    );
+    assert_eq!(out.dump, "");
+    let out = compile_with("a.c", "int main() { return 0; }", &Options::default()).unwrap();
+    assert!(out.warnings.is_empty());
+
+    let opts = Options {
+        dump_ir1: true,
+        dump_ir2: true,
+        ..Default::default()
+    };
+    let out = compile_with("a.c", "int main() { return 42; }", &opts).unwrap();
+    assert_eq!(out.dump.matches("main():").count(), 2);
+}
+
+#[test]
+fn runs_the_passes_by_hand() {
+    use r9cc::gen_ir::gen_ir;
+    use r9cc::gen_x86::gen_x86;
+    use r9cc::parse::parse;
+    use r9cc::preprocess::Preprocessor;
+    use r9cc::regalloc::alloc_regs;
+    use r9cc::sema::sema;
+    use r9cc::token::tokenize_str;
+
+    let src = "int main() { return 42; }";
+    let tokens = tokenize_str("a.c".into(), src, &mut Preprocessor::new());
+    let (nodes, globals) = sema(parse(&tokens), false);
+    let mut fns = gen_ir(nodes);
+    alloc_regs(&mut fns);
+    assert_eq!(gen_x86(globals, fns, false), compile(src).unwrap());
+}
+
+#[test]
+fn compiles_on_many_threads() {
+    let src = "int g; int f(int x) { return x ? f(x - 1) : g; } int main() { return f(3); }";
+    let expected = compile(src).unwrap();
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            std::thread::spawn(move || {
+                for _ in 0..20 {
+                    if i % 2 == 0 {
+                        assert!(compile("int main() { return x; }").is_err());
+                    } else {
+                        compile(src).unwrap();
+                    }
+                }
+                compile(src).unwrap()
+            })
+        })
+        .collect();
+    for t in threads {
+        assert_eq!(t.join().unwrap(), expected);
+    }
+}
+
+#[test]
+fn reports_unexpected_end_of_file() {
+    let src = "struct p { int x ; } ; int f ( int a , int b ) { \
+               for ( int i = 0 ; i < a ; i ++ ) { b = b + i * ( a - 1 ) ; } \
+               return sizeof ( a ) + b ; }";
+    let words: Vec<&str> = src.split_whitespace().collect();
+    // Every prefix is incomplete, except the one that ends after the struct.
+    for len in (1..words.len()).filter(|&len| len != 8) {
+        let prefix = words[..len].join(" ");
+        let errors = compile(&prefix).expect_err(&prefix);
+        assert_eq!(errors[0].message, "unexpected end of file", "{:?}", prefix);
+    }
+}
+
+#[test]
+fn inlines_memcpy_and_memset_of_constant_size() {
+    let asm = compile(
+        "int main() { char a[200]; char b[200]; \
+         __builtin_memcpy(a, b, 16); __builtin_memset(a, 0, 200); return 0; }",
+    )
+    .unwrap();
+    assert!(!asm.contains("call"));
+    assert!(asm.contains("rep stosb"));
+
+    let asm = compile("char *f(char *a, int n) { return __builtin_memcpy(a, a + n, n); }").unwrap();
+    assert!(asm.contains("call memcpy"));
+}
+
+#[test]
+fn optimizes_at_o1() {
+    let src = "int f(int *a, int i) { return a[i] * 8 + a[i] / 4; } \
+               int g(int n) { return n ? g(n - 1) : 0; }";
+    let count = |opts: &Options| {
+        let asm = compile_with("a.c", src, opts).unwrap().value;
+        asm.lines().filter(|line| line.starts_with('\t')).count()
+    };
+    let o0 = Options::default();
+    let o1 = Options {
+        opt_level: 1,
+        ..Default::default()
+    };
+    assert!(count(&o1) < count(&o0));
+
+    let no_cse = Options {
+        disabled_passes: vec!["cse".into()],
+        ..o1.clone()
+    };
+    assert!(count(&o1) < count(&no_cse));
+    assert!(count(&no_cse) < count(&o0));
+}
+
+#[test]
+fn keeps_every_volatile_load() {
+    let o1 = Options {
+        opt_level: 1,
+        ..Default::default()
+    };
+    let loads = |src: &str| {
+        let asm = compile_with("a.c", src, &o1).unwrap().value;
+        asm.matches("movsxd").count()
+    };
+    assert_eq!(loads("int f(int *p) { return *p + *p; }"), 1);
+    assert_eq!(loads("int f(volatile int *p) { return *p + *p; }"), 2);
+    assert_eq!(loads("int f(int *p) { *p; return 0; }"), 0);
+    assert_eq!(loads("int f(int volatile *p) { *p; return 0; }"), 1);
+    assert_eq!(
+        loads("int x; int f() { volatile int *p = &x; *p; return 0; }"),
+        1
+    );
+}
+
+#[test]
+fn joins_adjacent_string_literals() {
+    use r9cc::driver::tokens;
+    use r9cc::TokenType;
+
+    let toks = tokens("a.c", "\"ab\" \"cd\"").unwrap().value;
+    assert_eq!(toks[0].ty, TokenType::Str("abcd".into(), 5));
+    assert_eq!(toks[1].ty, TokenType::Eof);
+
+    let toks = tokens("a.c", "#define S \"ab\"\nS \"cd\" S").unwrap().value;
+    assert_eq!(toks[0].ty, TokenType::Str("abcdab".into(), 7));
+}
+
+#[test]
+fn places_const_globals_in_rodata() {
+    let asm =
+        compile("const int t[] = {1, 2}; int u[] = {3}; int main() { return t[1]; }").unwrap();
+    let rodata = asm.find(".section .rodata").unwrap();
+    assert!(asm.find("t:").unwrap() > rodata);
+    assert!(asm.find("u:").unwrap() < rodata);
+    assert!(asm.contains(".ascii \"\\001\\000\\000\\000\\002\\000\\000\\000\""));
+}
+
+#[test]
+fn limits_nesting_depth() {
+    let parens = |n| {
+        format!(
+            "int main() {{ return {}1{}; }}",
+            "(".repeat(n),
+            ")".repeat(n)
+        )
+    };
+    let negs = |n| format!("int main() {{ return {}1; }}", "- ".repeat(n));
+    let blocks = |n| {
+        format!(
+            "int main() {{ {}{} return 0; }}",
+            "{".repeat(n),
+            "}".repeat(n)
+        )
+    };
+
+    // The default limit assumes the stack of a main thread.
+    let big_stack = std::thread::Builder::new().stack_size(8 << 20);
+    big_stack
+        .spawn(move || {
+            for src in &[parens(100_000), negs(100_000), blocks(100_000)] {
+                let errors = compile(src).unwrap_err();
+                assert_eq!(errors[0].message, "maximum nesting depth exceeded");
+            }
+            for src in &[parens(200), negs(200), blocks(200)] {
+                assert!(compile(src).is_ok());
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    // A chain of operators nests as deeply as it is long, even though
+    // it is read by a loop.
+    let sum = |n| format!("int main() {{ return 1{}; }}", "+1".repeat(n));
+    let assigns = |n| format!("int main() {{ int a; {}1; return a; }}", "a=".repeat(n));
+    let commas = |n| {
+        format!(
+            "int main() {{ int a = 0; {}a; return a; }}",
+            "a += 1, ".repeat(n)
+        )
+    };
+    let conds = |n| {
+        format!(
+            "int main() {{ return {}1{}; }}",
+            "1 ? ".repeat(n),
+            " : 0".repeat(n)
+        )
+    };
+    let big_stack = std::thread::Builder::new().stack_size(8 << 20);
+    big_stack
+        .spawn(move || {
+            for src in &[sum(1000), assigns(5000), commas(100_000), conds(50_000)] {
+                let errors = compile(src).unwrap_err();
+                assert_eq!(errors[0].message, "maximum nesting depth exceeded");
+            }
+            for src in &[sum(200), assigns(200), commas(200), conds(5)] {
+                assert!(compile(src).is_ok());
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    let shallow = Options {
+        max_depth: Some(10),
+        ..Default::default()
+    };
+    assert!(compile_with("a.c", &parens(5), &shallow).is_ok());
+    let errors = compile_with("a.c", &parens(20), &shallow).unwrap_err();
+    assert_eq!(errors[0].message, "maximum nesting depth exceeded");
+}
+
+#[test]
+fn folds_constant_shifts() {
+    let asm = compile("int main() { return 1 << 4; }").unwrap();
+    assert!(asm.contains("mov r10, 16"));
+    assert!(!asm.contains("shl"));
+
+    let asm = compile("int main() { return -64 >> 2; }").unwrap();
+    assert!(asm.contains("mov r10, -16"));
+    assert!(!asm.contains("sar"));
+
+    // An out-of-range count is undefined, and is left to the CPU.
+    let asm = compile("int main() { return 1 << 40; }").unwrap();
+    assert!(asm.contains("shl"));
+}
+
+#[test]
+fn emits_symbol_types_and_sizes() {
+    let asm = compile("int x = 3; int y; char *s = \"hi\"; int main() { return x; }").unwrap();
+    for name in &["x", "y", "s"] {
+        assert!(asm.contains(&format!("\t.type {}, @object\n{}:\n", name, name)));
+        assert!(asm.contains(&format!("\t.size {}, .-{}\n", name, name)));
+    }
+    assert!(asm.contains("\t.type main, @function\nmain:\n"));
+    assert!(asm.contains("\tret\n\t.size main, .-main\n"));
+    assert!(!asm.contains(".type .L"));
+}
+
+#[test]
+fn expands_predefined_macros() {
+    use r9cc::driver::tokens;
+    use r9cc::TokenType;
+
+    let src =
+        "int a;\n#define LINE __LINE__\nint b = __LINE__ + LINE;\n__FILE__; __DATE__; __TIME__";
+    let toks = tokens("dir/a.c", src).unwrap().value;
+    let types: Vec<&TokenType> = toks.iter().map(|t| &t.ty).collect();
+    assert_eq!(types[6], &TokenType::Num(3, 4, false));
+    assert_eq!(types[8], &TokenType::Num(3, 4, false));
+    assert_eq!(types[10], &TokenType::Str("dir/a.c".into(), 8));
+
+    // "Oct 16 2026" and "08:30:43"
+    match (types[12], types[14]) {
+        (TokenType::Str(date, 12), TokenType::Str(time, 9)) => {
+            assert_eq!(date.as_bytes()[3], b' ');
+            assert!(date[7..].parse::<u32>().unwrap() >= 2024);
+            assert_eq!(time.matches(':').count(), 2);
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn takes_element_address_without_load() {
+    use r9cc::driver::ir;
+    use r9cc::gen_ir::IROp;
+
+    let fns = ir(
+        "a.c",
+        "int a[4]; int *f() { return &a[2]; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .value;
+    assert!(fns[0]
+        .ir
+        .iter()
+        .all(|ir| !std::matches!(ir.op, IROp::Load(..) | IROp::LoadSx(..))));
+}
+
+#[test]
+fn places_each_global_of_a_declaration() {
+    let asm = compile("int a = 1, b, *c = &a;").unwrap();
+    let bss = asm.find(".bss").unwrap();
+    assert!(asm.find("a:").unwrap() < bss);
+    assert!(asm.find("c:").unwrap() < bss);
+    assert!(asm.find("b:").unwrap() > bss);
+    assert!(asm.contains("c:\n\t.quad a\n"));
+}
+
+#[test]
+fn relocates_addresses_in_global_initializers() {
+    let asm =
+        compile("int a[4]; int *p = &a[2]; int *q = a + 3 - 1; char *s[] = {0, \"x\"};").unwrap();
+    assert!(asm.contains("p:\n\t.quad a+8\n"));
+    assert!(asm.contains("q:\n\t.quad a+8\n"));
+    assert!(asm
+        .contains("s:\n\t.ascii \"\\000\\000\\000\\000\\000\\000\\000\\000\"\n\t.quad .L.str0\n"));
+}
+
+#[test]
+fn places_static_locals_in_globals() {
+    let asm = compile(
+        "int f(int i) { static const int t[] = {2, 3, 5}; static int n; return t[i] + n; }",
+    )
+    .unwrap();
+    let rodata = asm.find(".section .rodata").unwrap();
+    assert!(asm.find(".L.t.0:\n\t.ascii").unwrap() > rodata);
+    assert!(asm.contains(".bss\n\t.align 4\n.L.n.1:\n\t.zero 4\n"));
+    assert!(!asm.contains(".global .L"));
+}
+
+#[test]
+fn aligns_every_global() {
+    let asm =
+        compile("char c = 1; int a[2] = {1, 2}; int *p = a; char *s = \"x\"; const int k = 5;")
+            .unwrap();
+    assert!(asm.contains(".data\n.global c\n\t.align 1\n\t.type c, @object\nc:\n"));
+    assert!(asm.contains(".global a\n\t.align 4\n\t.type a, @object\na:\n"));
+    assert!(asm.contains(".global p\n\t.align 8\n\t.type p, @object\np:\n"));
+    assert!(asm.contains("\t.align 1\n.L.str0:\n"));
+    assert!(asm.contains(".section .rodata\n.global k\n\t.align 4\n\t.type k, @object\nk:\n"));
+}
+
+#[test]
+fn exports_only_non_static_globals() {
+    let asm = compile(
+        "int a = 1; static int b = 2; int c; static int d; int f() { static int e; return e; }",
+    )
+    .unwrap();
+    assert!(asm.contains(".global a\n"));
+    assert!(asm.contains(".global c\n"));
+    assert!(!asm.contains(".global b\n"));
+    assert!(!asm.contains(".global d\n"));
+    assert!(!asm.contains(".global .L"));
+}
@@ -0,0 +1,249 @@
+// Feeds random input to the compiler and checks that it is rejected with
+// a diagnostic instead of an internal panic, and that it does not hang.
+//
+// The inputs are random bytes, random sequences of C tokens, and the
+// programs in tests/programs and examples with a few tokens inserted,
+// removed or swapped. A fixed seed keeps failures reproducible.
+
+use r9cc::{compile_with, Options};
+
+use std::fs;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// xorshift64*, so that a failure is reproducible from the seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+const WORDS: &[&str] = &[
+    "int",
+    "char",
+    "_Bool",
+    "void",
+    "unsigned",
+    "struct",
+    "enum",
+    "typedef",
+    "extern",
+    "static",
+    "inline",
+    "register",
+    "auto",
+    "restrict",
+    "__attribute__",
+    "__extension__",
+    "if",
+    "else",
+    "for",
+    "do",
+    "while",
+    "break",
+    "return",
+    "sizeof",
+    "_Alignof",
+    "__builtin_offsetof",
+    "x",
+    "y",
+    "main",
+    "f",
+    "0",
+    "1",
+    "42",
+    "0x10",
+    "1.5",
+    "'a'",
+    "\"s\"",
+    "+",
+    "-",
+    "*",
+    "/",
+    "%",
+    "&",
+    "|",
+    "^",
+    "!",
+    "?",
+    ":",
+    ",",
+    ".",
+    "->",
+    "=",
+    "==",
+    "!=",
+    "<",
+    ">",
+    "<=",
+    ">=",
+    "<<",
+    ">>",
+    "&&",
+    "||",
+    "++",
+    "--",
+    "+=",
+    "-=",
+    "*=",
+    "/=",
+    "%=",
+    "<<=",
+    ">>=",
+    "&=",
+    "|=",
+    "^=",
+    "(",
+    ")",
+    "[",
+    "]",
+    "{",
+    "}",
+    ";",
+    "#define",
+    "#include",
+    "#if",
+    "#endif",
+    "\n",
+];
+
+// Printable ASCII, mixed with non-ASCII characters and with integer
+// literals at and past the limits of their types.
+const EXTRA: &[&str] = &[
+    "\0",
+    "\u{80}",
+    "\u{ff}",
+    "\u{3bb}",
+    "\u{fffd}",
+    "\u{1f600}",
+    "-2147483648",
+    "2147483648",
+    "4294967296",
+    "-9223372036854775808",
+    "18446744073709551615",
+    "18446744073709551616",
+    "99999999999999999999999999",
+    "0xffffffffffffffffff",
+    "0777777777777777777777777",
+];
+
+fn random_bytes(rng: &mut Rng) -> String {
+    let len = rng.below(40);
+    let mut s = String::new();
+    for _ in 0..len {
+        if rng.below(8) == 0 {
+            s.push_str(EXTRA[rng.below(EXTRA.len())]);
+        } else {
+            s.push((b' ' + rng.below(96) as u8) as char);
+        }
+    }
+    s
+}
+
+fn random_tokens(rng: &mut Rng) -> String {
+    let len = rng.below(30);
+    let mut s = String::new();
+    for _ in 0..len {
+        s.push_str(WORDS[rng.below(WORDS.len())]);
+        s.push(' ');
+    }
+    s
+}
+
+fn mutate(rng: &mut Rng, src: &str) -> String {
+    let mut words: Vec<&str> = src.split_whitespace().collect();
+    for _ in 0..1 + rng.below(3) {
+        if words.is_empty() {
+            break;
+        }
+        let i = rng.below(words.len());
+        match rng.below(3) {
+            0 => {
+                words.remove(i);
+            }
+            1 => words.insert(i, WORDS[rng.below(WORDS.len())]),
+            _ => {
+                let j = rng.below(words.len());
+                words.swap(i, j);
+            }
+        }
+    }
+    words.join(" ")
+}
+
+// Programs to mutate, without comments and preprocessor directives so
+// that joining their words on one line keeps them valid.
+fn programs() -> Vec<String> {
+    let mut v = vec![];
+    for dir in &["tests/programs", "examples"] {
+        for entry in fs::read_dir(dir).unwrap() {
+            let src = fs::read_to_string(entry.unwrap().path()).unwrap();
+            let lines: Vec<&str> = src
+                .lines()
+                .filter(|l| !l.trim_start().starts_with("//") && !l.starts_with('#'))
+                .collect();
+            v.push(lines.join("\n"));
+        }
+    }
+    v.sort();
+    v
+}
+
+// Compiles src on a separate thread, so that a hang can be reported,
+// and checks that any error is a diagnostic rather than a Rust panic,
+// which is reported as an internal error.
+fn check(src: String) {
+    let (tx, rx) = mpsc::channel();
+    let input = src.clone();
+    let opts = Options {
+        opt_level: 1,
+        ..Default::default()
+    };
+    thread::spawn(move || {
+        tx.send(compile_with("<input>", &input, &opts).err())
+            .unwrap()
+    });
+    let errors = match rx.recv_timeout(Duration::from_secs(5)) {
+        Ok(errors) => errors,
+        Err(_) => panic!("compiler hangs on {:?}", src),
+    };
+    for e in errors.into_iter().flatten() {
+        assert!(!e.internal, "internal error {:?} on {:?}", e.message, src);
+    }
+}
+
+#[test]
+fn random_bytes_are_rejected() {
+    let mut rng = Rng(0x9cc);
+    for _ in 0..2000 {
+        check(random_bytes(&mut rng));
+    }
+}
+
+#[test]
+fn random_tokens_are_rejected() {
+    let mut rng = Rng(0x9cc);
+    for _ in 0..2000 {
+        check(random_tokens(&mut rng));
+    }
+}
+
+#[test]
+fn mutated_programs_do_not_crash() {
+    let mut rng = Rng(0x9cc);
+    let programs = programs();
+    for _ in 0..5000 {
+        let src = &programs[rng.below(programs.len())];
+        check(mutate(&mut rng, src));
+    }
+}
@@ -0,0 +1,88 @@
+// Runs r9cc with --phase=<name> and checks that it prints what the
+// phase it stopped after produced.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const SRC: &str = "int main() { return 42; }";
+
+fn run(flag: &str) -> Output {
+    run_on(flag, SRC)
+}
+
+fn run_on(flag: &str, src: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_r9cc"))
+        .args([flag, "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // r9cc exits without reading its input if a flag is bad, in which
+    // case the write fails with a broken pipe.
+    let _ = child.stdin.take().unwrap().write_all(src.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn phase(name: &str) -> String {
+    let out = run(&format!("--phase={}", name));
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(!stdout.is_empty());
+    stdout
+}
+
+#[test]
+fn stops_after_tokenizing() {
+    let out = phase("tokens");
+    assert!(out.starts_with("-:1:1\tInt\n"));
+    assert!(out.contains("\tNum(42, 4, false)\n"));
+    assert!(out.trim_end().ends_with("\tEof"));
+    assert!(!out.contains("Func"));
+}
+
+#[test]
+fn stops_after_parsing() {
+    let out = phase("ast");
+    assert_eq!(
+        out,
+        "Func main int()\n  CompStmt\n    Return\n      Num 42\n"
+    );
+}
+
+#[test]
+fn prints_one_line_per_node() {
+    let src = format!("int main() {{ return 1{}; }}", "+1".repeat(99));
+    let out = run_on("--phase=ast", &src);
+    assert!(out.status.success());
+    // Func, CompStmt, Return, 99 additions and 100 numbers
+    assert_eq!(out.stdout.iter().filter(|c| **c == b'\n').count(), 202);
+    assert!(out.stdout.len() < 30_000);
+}
+
+#[test]
+fn stops_after_generating_ir() {
+    let out = phase("ir");
+    assert!(out.starts_with("main():"));
+    assert!(out.contains("MOV r0, 42\n"));
+    assert!(!out.contains("intel_syntax"));
+}
+
+#[test]
+fn stops_after_generating_assembly() {
+    let out = phase("asm");
+    assert!(out.starts_with(".intel_syntax noprefix"));
+    assert!(out.contains("main:"));
+    assert!(out.contains("ret"));
+}
+
+#[test]
+fn rejects_unknown_phase() {
+    let out = run("--phase=link");
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).starts_with("Usage:"));
+}
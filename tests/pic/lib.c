@@ -0,0 +1,6 @@
+// Built by r9cc with -fPIC into a shared library; see main.c.
+int counter;
+extern int shared_base;
+int printf();
+int bump(int n) { counter += n; return counter + shared_base; }
+int hello() { printf("%s\n", "hello from a shared library"); return bump(2); }
@@ -0,0 +1,14 @@
+// Compiled with gcc and linked against lib.c, which r9cc builds as a
+// shared library.
+int bump(int n);
+int hello(void);
+
+int shared_base = 10;
+
+int main(void) {
+  if (hello() != 12)
+    return 1;
+  if (bump(3) != 15)
+    return 2;
+  return 0;
+}
@@ -0,0 +1,194 @@
+// End-to-end tests: compiles each program in tests/programs with r9cc,
+// assembles and links it with gcc, runs it, and checks its exit code
+// and standard output.
+//
+// Each program states what it expects in leading comments:
+//
+//   // exit: 42          expected exit code (0 if omitted)
+//   // stdout: a line    one expected line of standard output
+//   // flags: -O1        options passed to r9cc
+//
+// pic_shared_library builds tests/pic/lib.c with -fPIC into a shared
+// library and runs tests/pic/main.c against it.
+//
+// These tests need gcc and are only run when R9CC_E2E is set:
+//
+//   $ R9CC_E2E=1 cargo test --test programs
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+struct Expected {
+    exit: i32,
+    stdout: String,
+    flags: Vec<String>,
+}
+
+fn expected(src: &str) -> Expected {
+    let mut exit = 0;
+    let mut stdout = String::new();
+    let mut flags = vec![];
+    for line in src.lines() {
+        if let Some(code) = line.strip_prefix("// exit: ") {
+            exit = code.trim().parse().expect("bad exit code");
+        } else if let Some(text) = line.strip_prefix("// stdout: ") {
+            stdout.push_str(text);
+            stdout.push('\n');
+        } else if let Some(text) = line.strip_prefix("// flags: ") {
+            flags.extend(text.split_whitespace().map(String::from));
+        } else {
+            break;
+        }
+    }
+    Expected {
+        exit,
+        stdout,
+        flags,
+    }
+}
+
+fn enabled() -> bool {
+    if env::var_os("R9CC_E2E").is_none() {
+        eprintln!("skipped: set R9CC_E2E=1 to run the end-to-end tests");
+        return false;
+    }
+    true
+}
+
+fn tmpdir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("r9cc-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Compiles `path` with r9cc into an assembly file in `tmpdir`.
+fn compile(path: &Path, flags: &[&str], tmpdir: &Path) -> Result<PathBuf, String> {
+    let stem = path.file_stem().unwrap().to_str().unwrap();
+    let asm = tmpdir.join(format!("{}.s", stem));
+    let out = Command::new(env!("CARGO_BIN_EXE_r9cc"))
+        .args(flags)
+        .arg(path)
+        .output()
+        .unwrap();
+    if !out.status.success() {
+        return Err(format!(
+            "compile failed:\n{}",
+            String::from_utf8_lossy(&out.stderr)
+        ));
+    }
+    fs::write(&asm, &out.stdout).unwrap();
+    Ok(asm)
+}
+
+fn gcc(args: &[&OsStr]) -> Result<(), String> {
+    let out = Command::new("gcc")
+        .args(args)
+        .output()
+        .expect("gcc is required to run these tests");
+    if !out.status.success() {
+        return Err(format!(
+            "gcc failed:\n{}",
+            String::from_utf8_lossy(&out.stderr)
+        ));
+    }
+    Ok(())
+}
+
+fn run(path: &Path, tmpdir: &Path) -> Result<(), String> {
+    let src = fs::read_to_string(path).unwrap();
+    let expected = expected(&src);
+    let flags: Vec<&str> = expected.flags.iter().map(String::as_str).collect();
+    let asm = compile(path, &flags, tmpdir)?;
+    let exe = asm.with_extension("");
+    gcc(&[
+        "-static".as_ref(),
+        "-o".as_ref(),
+        exe.as_ref(),
+        asm.as_ref(),
+    ])?;
+
+    let out = Command::new(&exe).output().unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    if out.status.code() != Some(expected.exit) {
+        return Err(format!(
+            "exit code {} expected, but got {:?}",
+            expected.exit,
+            out.status.code()
+        ));
+    }
+    if stdout != expected.stdout {
+        return Err(format!(
+            "stdout {:?} expected, but got {:?}",
+            expected.stdout, stdout
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn programs() {
+    if !enabled() {
+        return;
+    }
+
+    let tmpdir = tmpdir("programs");
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+        .collect();
+    paths.sort();
+
+    let mut failures = vec![];
+    for path in &paths {
+        if let Err(msg) = run(path, &tmpdir) {
+            failures.push(format!("{}: {}", path.display(), msg));
+        }
+    }
+    fs::remove_dir_all(&tmpdir).unwrap();
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn pic_shared_library() {
+    if !enabled() {
+        return;
+    }
+
+    let tmpdir = tmpdir("pic");
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/pic");
+    let asm = compile(&dir.join("lib.c"), &["-fPIC"], &tmpdir).unwrap();
+    let lib = tmpdir.join("libr9cc.so");
+    gcc(&[
+        "-shared".as_ref(),
+        "-o".as_ref(),
+        lib.as_ref(),
+        asm.as_ref(),
+    ])
+    .unwrap();
+
+    let exe = tmpdir.join("main");
+    let rpath = format!("-Wl,-rpath,{}", tmpdir.display());
+    gcc(&[
+        "-o".as_ref(),
+        exe.as_ref(),
+        dir.join("main.c").as_ref(),
+        lib.as_ref(),
+        rpath.as_ref(),
+    ])
+    .unwrap();
+
+    let out = Command::new(&exe).output().unwrap();
+    fs::remove_dir_all(&tmpdir).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout),
+        "hello from a shared library\n"
+    );
+    assert_eq!(out.status.code(), Some(0));
+}
@@ -0,0 +1,15 @@
+// exit: 239
+struct flags {
+  int a : 3;
+  int b : 5;
+  int c : 8;
+};
+
+int main() {
+  struct flags f;
+  f.a = 7;
+  f.b = 9;
+  f.c = 7;
+  f.b = f.b + 7;
+  return f.a + f.b;
+}
@@ -0,0 +1,11 @@
+// exit: 1
+// stdout: -1 255 -56
+int printf();
+
+int main() {
+  char c = -1;
+  unsigned char u = -1;
+  char d = 200;
+  printf("%d %d %d\n", c, u, d);
+  return c == -1;
+}
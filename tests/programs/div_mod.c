@@ -0,0 +1,15 @@
+// stdout: 2 1
+// stdout: -2 -1
+// stdout: -2 1
+// stdout: 2 -1
+int printf();
+
+int main() {
+  int a = 7;
+  int b = 3;
+  printf("%d %d\n", a / b, a % b);
+  printf("%d %d\n", -a / b, -a % b);
+  printf("%d %d\n", a / -b, a % -b);
+  printf("%d %d\n", -a / -b, -a % -b);
+  return 0;
+}
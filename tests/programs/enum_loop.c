@@ -0,0 +1,17 @@
+// stdout: 0 5 6
+int printf();
+
+enum color { RED, GREEN = 5, BLUE };
+
+int main() {
+  enum color c[3];
+  c[0] = RED;
+  c[1] = GREEN;
+  c[2] = BLUE;
+  int i = 0;
+  do {
+    printf(i ? " %d" : "%d", c[i]);
+  } while (++i < 3);
+  printf("\n");
+  return 0;
+}
@@ -0,0 +1,15 @@
+// exit: 6
+int counter;
+int table[3];
+
+void bump(int n) {
+  counter += n;
+  table[n - 1] = counter;
+}
+
+int main() {
+  bump(1);
+  bump(2);
+  bump(3);
+  return table[2];
+}
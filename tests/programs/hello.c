@@ -0,0 +1,7 @@
+// stdout: hello, world
+int printf();
+
+int main() {
+  printf("hello, world\n");
+  return 0;
+}
@@ -0,0 +1,10 @@
+// exit: 7
+int strlen();
+
+int main(int argc, char **argv) {
+  if (argc != 1)
+    return 1;
+  if (strlen(argv[0]) == 0)
+    return 2;
+  return argc + 6;
+}
@@ -0,0 +1,27 @@
+// exit: 92
+int conflict(int *cols, int row, int col) {
+  for (int i = 0; i < row; i++) {
+    int d = cols[i] - col;
+    if (d == 0 || d == row - i || d == i - row)
+      return 1;
+  }
+  return 0;
+}
+
+int solve(int *cols, int row) {
+  if (row == 8)
+    return 1;
+  int n = 0;
+  for (int col = 0; col < 8; col++) {
+    if (!conflict(cols, row, col)) {
+      cols[row] = col;
+      n += solve(cols, row + 1);
+    }
+  }
+  return n;
+}
+
+int main() {
+  int cols[8];
+  return solve(cols, 0);
+}
@@ -0,0 +1,8 @@
+// exit: 3
+// Unknown pragmas are ignored.
+#pragma pack(1)
+#pragma once
+
+int main() {
+  return 3;
+}
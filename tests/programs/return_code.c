@@ -0,0 +1,2 @@
+// exit: 42
+int main() { return 42; }
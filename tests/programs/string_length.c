@@ -0,0 +1,16 @@
+// exit: 11
+// stdout: 6 6
+int printf();
+
+int len(char *s) {
+  int n = 0;
+  while (s[n])
+    n++;
+  return n;
+}
+
+int main() {
+  char *s = "hello " "world";
+  printf("%d %d\n", sizeof("hello"), len("abc\tef"));
+  return len(s);
+}
@@ -0,0 +1,17 @@
+// stdout: 1 1 12
+int printf();
+
+struct point {
+  char tag;
+  int x;
+  int y;
+};
+
+int main() {
+  struct point p;
+  char *base = &p;
+  char *x = &p.x;
+  char *y = &p.y;
+  printf("%d %d %d\n", x == base + 4, y == base + 8, sizeof(p));
+  return 0;
+}
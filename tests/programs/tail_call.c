@@ -0,0 +1,22 @@
+// exit: 0
+// stdout: 3628800 50000000
+// flags: -O1
+int printf();
+
+int fact(int n, int acc) {
+  if (n == 0)
+    return acc;
+  return fact(n - 1, acc * n);
+}
+
+// Without tail call optimization, this recursion overflows the stack.
+int count(int n, int acc) {
+  if (n == 0)
+    return acc;
+  return count(n - 1, acc + 1);
+}
+
+int main() {
+  printf("%d %d\n", fact(10, 1), count(50000000, 0));
+  return 0;
+}
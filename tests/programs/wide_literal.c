@@ -0,0 +1,14 @@
+// exit: 0
+// stdout: abc
+// Wide literals are read as narrow ones for now.
+int printf();
+
+int main() {
+  char *s = L"abc";
+  printf("%s\n", s);
+  if (L'a' != 97)
+    return 1;
+  if (sizeof(L"abc") != 4)
+    return 2;
+  return 0;
+}